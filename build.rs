@@ -0,0 +1,78 @@
+use serde::Deserialize;
+use std::fmt::Write;
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct Scale {
+    id: u16,
+    name: String,
+}
+#[derive(Deserialize)]
+struct Scales {
+    #[serde(rename = "scale")]
+    inner: Vec<Scale>,
+}
+
+/// Turn a human scale name like "Industry: Cheese Exports"
+/// into a variant name like `IndustryCheeseExports`.
+fn variant_name(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            core::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+fn generate_census(out_dir: &Path) {
+    let source = "data/census.xml";
+    println!("cargo:rerun-if-changed={}", source);
+    let text = std::fs::read_to_string(source).unwrap();
+    let scales: Scales = quick_xml::de::from_str(&text).unwrap();
+    let scales = scales.inner;
+
+    let mut code = String::new();
+    writeln!(code, "/// A census scale tracked by NationStates.").unwrap();
+    writeln!(code, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]").unwrap();
+    writeln!(code, "pub enum CensusScale {{").unwrap();
+    for scale in &scales {
+        writeln!(code, "    /// {}", scale.name).unwrap();
+        writeln!(code, "    {} = {},", variant_name(&scale.name), scale.id).unwrap();
+    }
+    writeln!(code, "}}").unwrap();
+
+    writeln!(code, "impl CensusScale {{").unwrap();
+    writeln!(code, "    /// Every known scale, in order of ID.").unwrap();
+    writeln!(code, "    pub const ALL: &[CensusScale] = &[").unwrap();
+    for scale in &scales {
+        writeln!(code, "        CensusScale::{},", variant_name(&scale.name)).unwrap();
+    }
+    writeln!(code, "    ];").unwrap();
+    writeln!(code, "    /// Look up a scale by its numeric ID.").unwrap();
+    writeln!(code, "    pub fn from_id(id: u16) -> Option<Self> {{").unwrap();
+    writeln!(code, "        match id {{").unwrap();
+    for scale in &scales {
+        writeln!(code, "            {} => Some(CensusScale::{}),", scale.id, variant_name(&scale.name)).unwrap();
+    }
+    writeln!(code, "            _ => None,").unwrap();
+    writeln!(code, "        }}").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "    /// Human readable name, as shown on the site.").unwrap();
+    writeln!(code, "    pub fn name(self) -> &'static str {{").unwrap();
+    writeln!(code, "        match self {{").unwrap();
+    for scale in &scales {
+        writeln!(code, "            CensusScale::{} => {:?},", variant_name(&scale.name), scale.name).unwrap();
+    }
+    writeln!(code, "        }}").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "}}").unwrap();
+
+    std::fs::write(out_dir.join("census.rs"), code).unwrap();
+}
+
+fn main() {
+    let out_dir = std::env::var_os("OUT_DIR").unwrap();
+    generate_census(Path::new(&out_dir));
+}
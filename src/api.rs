@@ -0,0 +1,1787 @@
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use core::fmt::Display;
+use core::str::FromStr;
+use itertools::Itertools;
+use crate::{Auth, Login, Nation, Pin, PinState};
+use crate::profile::Details;
+use crate::banners::Banners;
+use crate::cards::{Deck, Markets, Trades};
+use crate::cassette;
+use crate::http::{Body, HttpClient, HttpError};
+use crate::metrics;
+use crate::ratelimit::{self, RateLimit};
+use crate::census::{CensusScale, Ranks};
+use crate::dispatch::{Dispatch, DispatchList};
+use crate::economy::Sectors;
+use crate::happenings::{self, Happenings, View};
+use crate::issues::{Issues, NationalPolicies};
+use crate::region::{Authority, Embassies, Officers, Tags, TagFilter};
+use crate::session::{RetryPolicy, Session};
+use crate::telegram;
+use crate::wa::{Badges, Council, Proposals, Resolution};
+use crate::world::NewNationDetails;
+use crate::zday::Zombie;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use reqwest::StatusCode;
+use thiserror::Error;
+#[derive(Debug)]
+pub enum Shard {
+    Ping,
+    /// Display name of a nation or region.
+    Name,
+    /// Name of a nation with its pretitle, like "The Republic of Testlandia".
+    FullName,
+    Motto,
+    /// Pretitle of a nation, like "Republic" (nation only).
+    Pretitle,
+    /// Capital city, blank if the nation hasn't named one (nation only).
+    Capital,
+    /// Leader, blank if the nation hasn't named one (nation only).
+    Leader,
+    /// Religion, blank if the nation hasn't named one (nation only).
+    Religion,
+    /// Currency (nation only).
+    Currency,
+    /// National animal (nation only).
+    Animal,
+    /// Government classification, like "Civil Rights Lovefest".
+    Category,
+    /// Descriptions of the three freedoms, like "Excellent".
+    Freedom,
+    /// Region a nation resides in.
+    Region,
+    /// World Assembly membership status.
+    Wa,
+    /// Security Council resolutions commending, condemning, or liberating
+    /// a nation or region.
+    WaBadges,
+    /// Survivors, zombies, and dead during Z-Day, and for nations,
+    /// what they're doing about it.
+    Zombie,
+    /// Population, in millions.
+    Population,
+    /// Regional influence level, like "Eminence Grise".
+    Influence,
+    /// Gross domestic product (nation only).
+    Gdp,
+    /// Average income (nation only).
+    Income,
+    /// Average income of the richest 10% (nation only).
+    Richest,
+    /// Average income of the poorest 10% (nation only).
+    Poorest,
+    /// Average income tax rate, in percent (nation only).
+    Tax,
+    /// Largest private industry, like "Arms Manufacturing" (nation only).
+    MajorIndustry,
+    /// Shares of the economy by sector (nation only).
+    Sectors,
+    /// Nations endorsing this nation.
+    Endorsements,
+    /// URL of the nation's flag image (nation only).
+    Flag,
+    /// When the nation was founded (nation only).
+    FoundedTime,
+    /// When the nation first logged in (nation only).
+    FirstLogin,
+    /// When the nation last logged in (nation only).
+    LastLogin,
+    /// Nations residing in a region (region only).
+    Nations,
+    /// Region founder, or `0` if there is none.
+    Founder,
+    /// Powers held by the founder.
+    FounderAuth,
+    /// WA delegate, or `0` if there is none.
+    Delegate,
+    /// Powers held by the delegate.
+    DelegateAuth,
+    /// Votes the delegate casts in the WA, one more than their endorsements.
+    /// Not to be confused with `DelegateVotes` on a resolution.
+    DelegateVoteCount,
+    /// Regional officers and their powers.
+    Officers,
+    /// Tags on a region.
+    Tags,
+    /// A region's factbook, in BBCode (region only).
+    Factbook,
+    /// A region's embassies, including ones asked for and being closed (region only).
+    Embassies,
+    /// Posts on a region's message board, up to `limit` (at most 100) of
+    /// them. `offset` skips back past the latest posts; `from_id` starts
+    /// at a post instead.
+    Messages { limit: Option<u32>, offset: Option<u32>, from_id: Option<u64> },
+    /// Nations in order of a census scale, twenty at a time from `start`.
+    /// Region or world.
+    CensusRanks { scale: CensusScale, start: Option<u32> },
+    /// Regions with or without the given tags (world only).
+    /// The API accepts at most ten tags.
+    RegionsByTag { tags: Vec<TagFilter> },
+    /// Census scores and ranks.
+    /// An empty list of scales requests all of them, and an empty list of
+    /// modes whatever the API sends by default. `from` and `to` narrow
+    /// down `CensusMode::History`, and mean nothing without it.
+    Census { scales: Vec<CensusScale>, modes: Vec<CensusMode>, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>> },
+    /// Proposals currently in the queue (WA only).
+    Proposals,
+    /// Every WA member nation (WA only). Both councils have the same members.
+    Members,
+    /// The resolution at vote, or a passed one by ID (WA only).
+    Resolution { id: Option<u32> },
+    /// Hourly vote totals for the resolution at vote.
+    /// Only valid together with `Resolution`.
+    VoteTrack,
+    /// Log of delegate votes for the resolution at vote.
+    /// Only valid together with `Resolution`.
+    DelegateLog,
+    /// Current delegate votes for the resolution at vote.
+    /// Only valid together with `Resolution`.
+    DelegateVotes,
+    /// Recent happenings (world only),
+    /// optionally narrowed down to a nation or region and event categories.
+    Happenings {
+        view: Option<View>,
+        filter: Vec<happenings::Category>,
+        limit: Option<u32>,
+    },
+    /// The most recently founded nations (world only).
+    NewNations,
+    /// The most recently founded nations, with when and where (world only).
+    NewNationDetails,
+    /// Whether a nation can receive recruitment telegrams,
+    /// optionally from a specific region (nation only).
+    TgCanRecruit { from: Option<String> },
+    /// Lengths of the telegram delivery queues (world only).
+    TgQueue,
+    /// Trading cards a nation owns (world only).
+    Deck { nation: String },
+    /// Recent trades of a card (world only).
+    CardTrades { id: u64, season: u8 },
+    /// Open asks and bids on a card (world only).
+    CardMarkets { id: u64, season: u8 },
+    /// Issues waiting for an answer (nation only, private).
+    Issues,
+    /// Banners a nation may display (nation only).
+    Banners,
+    /// Code of the banner a nation displays (nation only).
+    Banner,
+    /// National policies in effect (nation only).
+    Policies,
+    /// Number of dispatches a nation has published (nation only).
+    Dispatches,
+    /// Dispatches a nation has published, without their text (nation only).
+    DispatchList,
+    /// A dispatch with its text, by ID (world only).
+    Dispatch { id: u64 },
+}
+impl Shard {
+    fn to_query_segment(&self) -> Cow<'_, str> {
+        // This may end up generated.
+        match self {
+            Shard::Ping => "ping".into(),
+            Shard::Name => "name".into(),
+            Shard::FullName => "fullname".into(),
+            Shard::Motto => "motto".into(),
+            Shard::Pretitle => "type".into(),
+            Shard::Capital => "capital".into(),
+            Shard::Leader => "leader".into(),
+            Shard::Religion => "religion".into(),
+            Shard::Currency => "currency".into(),
+            Shard::Animal => "animal".into(),
+            Shard::Category => "category".into(),
+            Shard::Freedom => "freedom".into(),
+            Shard::Region => "region".into(),
+            Shard::Wa => "wa".into(),
+            Shard::WaBadges => "wabadges".into(),
+            Shard::Zombie => "zombie".into(),
+            Shard::Population => "population".into(),
+            Shard::Influence => "influence".into(),
+            Shard::Gdp => "gdp".into(),
+            Shard::Income => "income".into(),
+            Shard::Richest => "richest".into(),
+            Shard::Poorest => "poorest".into(),
+            Shard::Tax => "tax".into(),
+            Shard::MajorIndustry => "majorindustry".into(),
+            Shard::Sectors => "sectors".into(),
+            Shard::Endorsements => "endorsements".into(),
+            Shard::Flag => "flag".into(),
+            Shard::FoundedTime => "foundedtime".into(),
+            Shard::FirstLogin => "firstlogin".into(),
+            Shard::LastLogin => "lastlogin".into(),
+            Shard::Nations => "nations".into(),
+            Shard::RegionsByTag { .. } => "regionsbytag".into(),
+            Shard::Founder => "founder".into(),
+            Shard::FounderAuth => "founderauth".into(),
+            Shard::Delegate => "delegate".into(),
+            Shard::DelegateAuth => "delegateauth".into(),
+            Shard::DelegateVoteCount => "delegatevotes".into(),
+            Shard::Officers => "officers".into(),
+            Shard::Tags => "tags".into(),
+            Shard::Factbook => "factbook".into(),
+            Shard::Embassies => "embassies".into(),
+            Shard::Messages { .. } => "messages".into(),
+            Shard::CensusRanks { .. } => "censusranks".into(),
+            Shard::Census { .. } => "census".into(),
+            Shard::Proposals => "proposals".into(),
+            Shard::Members => "members".into(),
+            Shard::Resolution { .. } => "resolution".into(),
+            Shard::VoteTrack => "votetrack".into(),
+            Shard::DelegateLog => "dellog".into(),
+            Shard::DelegateVotes => "delvotes".into(),
+            Shard::Happenings { .. } => "happenings".into(),
+            Shard::NewNations => "newnations".into(),
+            Shard::NewNationDetails => "newnationdetails".into(),
+            Shard::TgCanRecruit { .. } => "tgcanrecruit".into(),
+            Shard::TgQueue => "tgqueue".into(),
+            Shard::Deck { .. } => "cards deck".into(),
+            Shard::CardTrades { .. } => "card trades".into(),
+            Shard::CardMarkets { .. } => "card markets".into(),
+            Shard::Issues => "issues".into(),
+            Shard::Banners => "banners".into(),
+            Shard::Banner => "banner".into(),
+            Shard::Policies => "policies".into(),
+            Shard::Dispatches => "dispatches".into(),
+            Shard::DispatchList => "dispatchlist".into(),
+            Shard::Dispatch { .. } => "dispatch".into(),
+        }
+    }
+    /// Additional query parameters this shard needs.
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Shard::Census { scales, modes, from, to } => {
+                let scales = if scales.is_empty() { "all".into() } else { scales.iter().map(|s| s.id()).join(" ") };
+                let mut params = vec![("scale", scales)];
+                if !modes.is_empty() {
+                    params.push(("mode", modes.iter().map(|x| x.id()).join(" ")));
+                }
+                if let Some(from) = from {
+                    params.push(("from", from.timestamp().to_string()));
+                }
+                if let Some(to) = to {
+                    params.push(("to", to.timestamp().to_string()));
+                }
+                params
+            },
+            Shard::Resolution { id: Some(id) } => vec![("id", id.to_string())],
+            Shard::Happenings { view, filter, limit } => {
+                let mut params = Vec::new();
+                if let Some(view) = view {
+                    params.push(("view", view.to_parameter()));
+                }
+                let filter: Vec<_> = filter.iter().filter_map(|c| c.api_filter()).collect();
+                if !filter.is_empty() {
+                    params.push(("filter", filter.join(" ")));
+                }
+                if let Some(limit) = limit {
+                    params.push(("limit", limit.to_string()));
+                }
+                params
+            },
+            Shard::TgCanRecruit { from: Some(region) } => vec![("from", region.clone())],
+            Shard::Dispatch { id } => vec![("dispatchid", id.to_string())],
+            Shard::RegionsByTag { tags } => vec![("tags", tags.iter().join(","))],
+            Shard::Deck { nation } => vec![("nationname", nation.clone())],
+            Shard::CardTrades { id, season } | Shard::CardMarkets { id, season } => {
+                vec![("cardid", id.to_string()), ("season", season.to_string())]
+            },
+            Shard::Messages { limit, offset, from_id } => {
+                let mut params = Vec::new();
+                if let Some(limit) = limit {
+                    params.push(("limit", limit.to_string()));
+                }
+                if let Some(offset) = offset {
+                    params.push(("offset", offset.to_string()));
+                }
+                if let Some(from_id) = from_id {
+                    params.push(("fromid", from_id.to_string()));
+                }
+                params
+            },
+            Shard::CensusRanks { scale, start } => {
+                let mut params = vec![("scale", scale.id().to_string())];
+                if let Some(start) = start {
+                    params.push(("start", start.to_string()));
+                }
+                params
+            },
+            Shard::Ping
+            | Shard::Name
+            | Shard::FullName
+            | Shard::Motto
+            | Shard::Pretitle
+            | Shard::Capital
+            | Shard::Leader
+            | Shard::Religion
+            | Shard::Currency
+            | Shard::Animal
+            | Shard::Category
+            | Shard::Freedom
+            | Shard::Region
+            | Shard::Wa
+            | Shard::WaBadges
+            | Shard::Zombie
+            | Shard::Population
+            | Shard::Influence
+            | Shard::Gdp
+            | Shard::Income
+            | Shard::Richest
+            | Shard::Poorest
+            | Shard::Tax
+            | Shard::MajorIndustry
+            | Shard::Sectors
+            | Shard::Endorsements
+            | Shard::Flag
+            | Shard::FoundedTime
+            | Shard::FirstLogin
+            | Shard::LastLogin
+            | Shard::Nations
+            | Shard::Founder
+            | Shard::FounderAuth
+            | Shard::Delegate
+            | Shard::DelegateAuth
+            | Shard::DelegateVoteCount
+            | Shard::Officers
+            | Shard::Tags
+            | Shard::Factbook
+            | Shard::Embassies
+            | Shard::Issues
+            | Shard::Banners
+            | Shard::Banner
+            | Shard::Policies
+            | Shard::Dispatches
+            | Shard::DispatchList
+            | Shard::NewNations
+            | Shard::NewNationDetails
+            | Shard::TgQueue
+            | Shard::TgCanRecruit { from: None }
+            | Shard::Proposals
+            | Shard::Members
+            | Shard::Resolution { id: None }
+            | Shard::VoteTrack
+            | Shard::DelegateLog
+            | Shard::DelegateVotes => Vec::new(),
+        }
+    }
+}
+/// What the `census` shard reports for each scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CensusMode {
+    Score,
+    /// World rank.
+    Rank,
+    /// Region rank.
+    RegionRank,
+    /// World rank as a percentage.
+    PercentRank,
+    /// Region rank as a percentage.
+    RegionPercentRank,
+    /// Scores over time, as [`CensusScore::points`]. Can't be combined with the others.
+    History,
+}
+impl CensusMode {
+    fn id(self) -> &'static str {
+        match self {
+            CensusMode::Score => "score",
+            CensusMode::Rank => "rank",
+            CensusMode::RegionRank => "rrank",
+            CensusMode::PercentRank => "prank",
+            CensusMode::RegionPercentRank => "prrank",
+            CensusMode::History => "history",
+        }
+    }
+}
+
+/// Why a [`ShardSet`] couldn't be built.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ShardError {
+    #[error("{shard} doesn't take {parameter}")]
+    Unsupported { parameter: &'static str, shard: String },
+    #[error("nothing to apply {0} to; add a shard first")]
+    NoShard(&'static str),
+    #[error("census history can't be requested together with other modes")]
+    HistoryMixed,
+    #[error("from and to only apply to census history")]
+    RangeWithoutHistory,
+    #[error("{shard} takes at most {max} for {parameter}")]
+    TooMany { parameter: &'static str, shard: String, max: usize },
+    #[error("{0} is only valid together with resolution")]
+    NeedsResolution(String),
+    /// The API takes each parameter once for the whole request,
+    /// so two shards can't ask for different values of it.
+    #[error("{parameter} can't be both {first} and {second} in one request")]
+    Conflict { parameter: &'static str, first: String, second: String },
+}
+
+/// Shards for a request, with their parameters checked before it's sent.
+///
+/// Parameters apply to the shard added last:
+///
+/// ```
+/// use nation::api::{CensusMode, ShardSet};
+/// use nation::census::CensusScale;
+/// # use chrono::TimeZone;
+/// let shards = ShardSet::new()
+///     .name()
+///     .census([CensusScale::Economy])
+///     .mode(CensusMode::History)
+///     .from(chrono::Utc.timestamp_opt(1_600_000_000, 0).unwrap())
+///     .build()
+///     .unwrap();
+/// # assert_eq!(shards.len(), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct ShardSet {
+    shards: Vec<Shard>,
+    /// The first mistake made while building, reported by `build`.
+    error: Option<ShardError>,
+}
+impl ShardSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add any shard as it is, to be checked along with the rest.
+    pub fn shard(mut self, shard: Shard) -> Self {
+        self.shards.push(shard);
+        self
+    }
+    pub fn ping(self) -> Self {
+        self.shard(Shard::Ping)
+    }
+    pub fn name(self) -> Self {
+        self.shard(Shard::Name)
+    }
+    pub fn region(self) -> Self {
+        self.shard(Shard::Region)
+    }
+    pub fn wa(self) -> Self {
+        self.shard(Shard::Wa)
+    }
+    pub fn population(self) -> Self {
+        self.shard(Shard::Population)
+    }
+    pub fn influence(self) -> Self {
+        self.shard(Shard::Influence)
+    }
+    /// The `census` shard for `scales`, or every scale if there are none.
+    /// Takes `mode`, `from`, and `to`.
+    pub fn census(self, scales: impl IntoIterator<Item = CensusScale>) -> Self {
+        self.shard(Shard::Census { scales: scales.into_iter().collect(), modes: Vec::new(), from: None, to: None })
+    }
+    /// The `censusranks` shard. Takes `start`.
+    pub fn census_ranks(self, scale: CensusScale) -> Self {
+        self.shard(Shard::CensusRanks { scale, start: None })
+    }
+    /// The `messages` shard. Takes `limit`, `offset`, and `from_id`.
+    pub fn messages(self) -> Self {
+        self.shard(Shard::Messages { limit: None, offset: None, from_id: None })
+    }
+    /// The `happenings` shard. Takes `view`, `filter`, and `limit`.
+    pub fn happenings(self) -> Self {
+        self.shard(Shard::Happenings { view: None, filter: Vec::new(), limit: None })
+    }
+    /// The `resolution` shard, for the one at vote unless given an `id`.
+    pub fn resolution(self) -> Self {
+        self.shard(Shard::Resolution { id: None })
+    }
+    /// Apply a parameter to the shard added last, if it takes it.
+    fn last(mut self, parameter: &'static str, apply: impl FnOnce(&mut Shard) -> bool) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        self.error = match self.shards.last_mut() {
+            None => Some(ShardError::NoShard(parameter)),
+            Some(shard) => if apply(shard) {
+                None
+            } else {
+                Some(ShardError::Unsupported { parameter, shard: shard.to_query_segment().into_owned() })
+            },
+        };
+        self
+    }
+    /// What `census` reports. Can be given more than once.
+    pub fn mode(self, mode: CensusMode) -> Self {
+        self.last("mode", |shard| match shard {
+            Shard::Census { modes, .. } => { modes.push(mode); true },
+            _ => false,
+        })
+    }
+    /// Where `census` history starts.
+    pub fn from(self, time: DateTime<Utc>) -> Self {
+        self.last("from", |shard| match shard {
+            Shard::Census { from, .. } => { *from = Some(time); true },
+            _ => false,
+        })
+    }
+    /// Where `census` history ends.
+    pub fn to(self, time: DateTime<Utc>) -> Self {
+        self.last("to", |shard| match shard {
+            Shard::Census { to, .. } => { *to = Some(time); true },
+            _ => false,
+        })
+    }
+    /// Where `censusranks` starts counting.
+    pub fn start(self, rank: u32) -> Self {
+        self.last("start", |shard| match shard {
+            Shard::CensusRanks { start, .. } => { *start = Some(rank); true },
+            _ => false,
+        })
+    }
+    /// How many `messages` or `happenings` to ask for.
+    pub fn limit(self, count: u32) -> Self {
+        self.last("limit", |shard| match shard {
+            Shard::Messages { limit, .. } | Shard::Happenings { limit, .. } => { *limit = Some(count); true },
+            _ => false,
+        })
+    }
+    /// How many of the latest `messages` to skip.
+    pub fn offset(self, count: u32) -> Self {
+        self.last("offset", |shard| match shard {
+            Shard::Messages { offset, .. } => { *offset = Some(count); true },
+            _ => false,
+        })
+    }
+    /// The post `messages` start at.
+    pub fn from_id(self, id: u64) -> Self {
+        self.last("fromid", |shard| match shard {
+            Shard::Messages { from_id, .. } => { *from_id = Some(id); true },
+            _ => false,
+        })
+    }
+    /// Whose `happenings` to ask for.
+    pub fn view(self, to: View) -> Self {
+        self.last("view", |shard| match shard {
+            Shard::Happenings { view, .. } => { *view = Some(to); true },
+            _ => false,
+        })
+    }
+    /// Which kinds of `happenings` to ask for. Can be given more than once.
+    pub fn filter(self, category: happenings::Category) -> Self {
+        self.last("filter", |shard| match shard {
+            Shard::Happenings { filter, .. } => { filter.push(category); true },
+            _ => false,
+        })
+    }
+    /// Which passed `resolution` to ask for.
+    pub fn id(self, resolution: u32) -> Self {
+        self.last("id", |shard| match shard {
+            Shard::Resolution { id } => { *id = Some(resolution); true },
+            _ => false,
+        })
+    }
+    /// The shards, if they make a valid request together.
+    pub fn build(self) -> Result<Vec<Shard>, ShardError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        for shard in &self.shards {
+            let name = || shard.to_query_segment().into_owned();
+            match shard {
+                Shard::Census { modes, from, to, .. } => {
+                    let history = modes.contains(&CensusMode::History);
+                    if history && modes.iter().any(|&x| x != CensusMode::History) {
+                        return Err(ShardError::HistoryMixed);
+                    }
+                    if !history && (from.is_some() || to.is_some()) {
+                        return Err(ShardError::RangeWithoutHistory);
+                    }
+                },
+                Shard::Messages { limit: Some(limit), .. } if *limit > 100 => {
+                    return Err(ShardError::TooMany { parameter: "limit", shard: name(), max: 100 });
+                },
+                Shard::RegionsByTag { tags } if tags.len() > 10 => {
+                    return Err(ShardError::TooMany { parameter: "tags", shard: name(), max: 10 });
+                },
+                Shard::VoteTrack | Shard::DelegateLog | Shard::DelegateVotes
+                    if !self.shards.iter().any(|x| matches!(x, Shard::Resolution { .. })) => {
+                    return Err(ShardError::NeedsResolution(name()));
+                },
+                _ => (),
+            }
+        }
+        let mut seen: Vec<(&'static str, String)> = Vec::new();
+        for (parameter, value) in self.shards.iter().flat_map(Shard::parameters) {
+            match seen.iter().find(|(x, _)| *x == parameter) {
+                Some((_, first)) if *first != value => {
+                    return Err(ShardError::Conflict { parameter, first: first.clone(), second: value });
+                },
+                Some(_) => (),
+                None => seen.push((parameter, value)),
+            }
+        }
+        Ok(self.shards)
+    }
+}
+
+fn query_string(shards: &[Shard]) -> String {
+    shards.iter().map(Shard::to_query_segment).join(" ")
+}
+
+/// Builds an API URL one parameter at a time, percent-encoding every value.
+/// Spaces are encoded as `+`, so lists the API wants `+` separated
+/// should be joined with spaces.
+pub(crate) struct Query {
+    url: reqwest::Url,
+}
+impl Query {
+    pub(crate) fn new(base: &str) -> Self {
+        Self {
+            url: reqwest::Url::parse(base).expect("API base is a valid URL"),
+        }
+    }
+    pub(crate) fn param(mut self, key: &str, value: impl AsRef<str>) -> Self {
+        self.url.query_pairs_mut().append_pair(key, value.as_ref());
+        self
+    }
+    pub(crate) fn build(self) -> String {
+        self.url.into()
+    }
+}
+/// Deserialize text content through `FromStr`.
+///
+/// Enums sent as text (rather than as element names)
+/// don't deserialize directly through quick-xml.
+pub(crate) fn from_text<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let text = String::deserialize(deserializer)?;
+    text.parse().map_err(serde::de::Error::custom)
+}
+/// Like `from_text`, for fields that may be missing.
+pub(crate) fn from_text_optional<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    from_text(deserializer).map(Some)
+}
+/// Deserialize a number that may be left empty.
+pub(crate) fn optional_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let text = String::deserialize(deserializer)?;
+    match text.trim() {
+        "" => Ok(None),
+        text => text.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+/// Deserialize a Unix time, where `0` means the API doesn't know,
+/// as with nations founded before it kept track.
+pub(crate) fn optional_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+    use chrono::TimeZone;
+    let seconds: i64 = from_text(deserializer)?;
+    Ok(Some(seconds).filter(|&x| x > 0).and_then(|x| Utc.timestamp_opt(x, 0).single()))
+}
+/// Deserialize a colon separated list of names,
+/// which is how the API sends most lists of nations.
+pub(crate) fn colon_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Ok(text.split(':').filter(|x| !x.is_empty()).map(String::from).collect())
+}
+/// Deserialize a comma separated list of names,
+/// which is how the API sends the rest of them.
+pub(crate) fn comma_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Ok(text.split(',').filter(|x| !x.is_empty()).map(String::from).collect())
+}
+#[derive(Debug, Deserialize)]
+pub struct CensusScore {
+    pub id: u16,
+    #[serde(rename = "SCORE")]
+    pub score: Option<f64>,
+    /// World rank.
+    #[serde(rename = "RANK")]
+    pub rank: Option<u32>,
+    /// Region rank.
+    #[serde(rename = "RRANK")]
+    pub region_rank: Option<u32>,
+    /// World rank as a percentage, where 1 means the top 1%.
+    #[serde(rename = "PRANK")]
+    pub percent_rank: Option<f64>,
+    /// Region rank as a percentage.
+    #[serde(rename = "PRRANK")]
+    pub region_percent_rank: Option<f64>,
+    /// Scores over time, oldest first, with `CensusMode::History`.
+    #[serde(rename = "POINT", default)]
+    pub points: Vec<CensusPoint>,
+}
+#[derive(Debug, Deserialize)]
+pub struct CensusPoint {
+    #[serde(rename = "TIMESTAMP", with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+    #[serde(rename = "SCORE")]
+    pub score: f64,
+}
+#[derive(Debug, Deserialize)]
+pub struct Census {
+    #[serde(rename = "SCALE", default)]
+    pub scales: Vec<CensusScore>,
+}
+/// Descriptions of a nation's freedoms, from the `freedom` shard.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Freedom {
+    #[serde(rename(deserialize = "CIVILRIGHTS"))]
+    pub civil_rights: String,
+    #[serde(rename(deserialize = "ECONOMY"))]
+    pub economy: String,
+    #[serde(rename(deserialize = "POLITICALFREEDOM"))]
+    pub political_freedom: String,
+}
+#[derive(Debug, Deserialize)]
+pub enum ResolvedShard {
+    #[serde(rename(deserialize = "PING"))]
+    Ping,
+    #[serde(rename(deserialize = "NAME"))]
+    Name(String),
+    #[serde(rename(deserialize = "FULLNAME"))]
+    FullName(String),
+    #[serde(rename(deserialize = "MOTTO"))]
+    Motto(String),
+    #[serde(rename(deserialize = "TYPE"))]
+    Pretitle(String),
+    #[serde(rename(deserialize = "CAPITAL"))]
+    Capital(String),
+    #[serde(rename(deserialize = "LEADER"))]
+    Leader(String),
+    #[serde(rename(deserialize = "RELIGION"))]
+    Religion(String),
+    #[serde(rename(deserialize = "CURRENCY"))]
+    Currency(String),
+    #[serde(rename(deserialize = "ANIMAL"))]
+    Animal(String),
+    #[serde(rename(deserialize = "CATEGORY"))]
+    Category(String),
+    #[serde(rename(deserialize = "FREEDOM"))]
+    Freedom(Freedom),
+    #[serde(rename(deserialize = "REGION"))]
+    Region(String),
+    /// Sent for the `wa` shard.
+    #[serde(rename(deserialize = "UNSTATUS"))]
+    WaStatus(String),
+    #[serde(rename(deserialize = "WABADGES"))]
+    WaBadges(Badges),
+    #[serde(rename(deserialize = "ZOMBIE"))]
+    Zombie(Zombie),
+    #[serde(rename(deserialize = "POPULATION"))]
+    Population(u64),
+    #[serde(rename(deserialize = "INFLUENCE"))]
+    Influence(String),
+    #[serde(rename(deserialize = "GDP"))]
+    Gdp(u64),
+    #[serde(rename(deserialize = "INCOME"))]
+    Income(u64),
+    #[serde(rename(deserialize = "RICHEST"))]
+    Richest(u64),
+    #[serde(rename(deserialize = "POOREST"))]
+    Poorest(u64),
+    #[serde(rename(deserialize = "TAX"))]
+    Tax(f64),
+    #[serde(rename(deserialize = "MAJORINDUSTRY"))]
+    MajorIndustry(String),
+    #[serde(rename(deserialize = "SECTORS"))]
+    Sectors(Sectors),
+    #[serde(rename(deserialize = "ENDORSEMENTS"))]
+    Endorsements(#[serde(deserialize_with = "comma_list")] Vec<String>),
+    #[serde(rename(deserialize = "FLAG"))]
+    Flag(String),
+    #[serde(rename(deserialize = "FOUNDEDTIME"))]
+    FoundedTime(#[serde(deserialize_with = "optional_timestamp")] Option<DateTime<Utc>>),
+    #[serde(rename(deserialize = "FIRSTLOGIN"))]
+    FirstLogin(#[serde(deserialize_with = "optional_timestamp")] Option<DateTime<Utc>>),
+    #[serde(rename(deserialize = "LASTLOGIN"))]
+    LastLogin(#[serde(deserialize_with = "optional_timestamp")] Option<DateTime<Utc>>),
+    #[serde(rename(deserialize = "NATIONS"))]
+    Nations(#[serde(deserialize_with = "colon_list")] Vec<String>),
+    /// Sent for the `regionsbytag` shard.
+    #[serde(rename(deserialize = "REGIONS"))]
+    Regions(#[serde(deserialize_with = "comma_list")] Vec<String>),
+    #[serde(rename(deserialize = "FOUNDER"))]
+    Founder(String),
+    #[serde(rename(deserialize = "FOUNDERAUTH"))]
+    FounderAuth(#[serde(deserialize_with = "from_text")] Authority),
+    #[serde(rename(deserialize = "DELEGATE"))]
+    Delegate(String),
+    #[serde(rename(deserialize = "DELEGATEAUTH"))]
+    DelegateAuth(#[serde(deserialize_with = "from_text")] Authority),
+    #[serde(rename(deserialize = "DELEGATEVOTES"))]
+    DelegateVoteCount(u32),
+    #[serde(rename(deserialize = "OFFICERS"))]
+    Officers(Officers),
+    #[serde(rename(deserialize = "TAGS"))]
+    Tags(Tags),
+    #[serde(rename(deserialize = "FACTBOOK"))]
+    Factbook(String),
+    #[serde(rename(deserialize = "EMBASSIES"))]
+    Embassies(Embassies),
+    #[serde(rename(deserialize = "MESSAGES"))]
+    Messages(crate::rmb::Messages),
+    #[serde(rename(deserialize = "CENSUSRANKS"))]
+    CensusRanks(Ranks),
+    #[serde(rename(deserialize = "CENSUS"))]
+    Census(Census),
+    #[serde(rename(deserialize = "PROPOSALS"))]
+    Proposals(Proposals),
+    #[serde(rename(deserialize = "MEMBERS"))]
+    Members(#[serde(deserialize_with = "comma_list")] Vec<String>),
+    #[serde(rename(deserialize = "RESOLUTION"))]
+    Resolution(Box<Resolution>),
+    #[serde(rename(deserialize = "HAPPENINGS"))]
+    Happenings(Happenings),
+    #[serde(rename(deserialize = "DECK"))]
+    Deck(Deck),
+    /// Sent alongside card shards.
+    #[serde(rename(deserialize = "CARDID"))]
+    CardId(u64),
+    /// Sent alongside card shards.
+    #[serde(rename(deserialize = "SEASON"))]
+    Season(u8),
+    #[serde(rename(deserialize = "TRADES"))]
+    Trades(Trades),
+    #[serde(rename(deserialize = "MARKETS"))]
+    Markets(Markets),
+    #[serde(rename(deserialize = "ISSUES"))]
+    Issues(Issues),
+    #[serde(rename(deserialize = "BANNERS"))]
+    Banners(Banners),
+    #[serde(rename(deserialize = "BANNER"))]
+    Banner(String),
+    #[serde(rename(deserialize = "POLICIES"))]
+    Policies(NationalPolicies),
+    #[serde(rename(deserialize = "NEWNATIONS"))]
+    NewNations(#[serde(deserialize_with = "comma_list")] Vec<String>),
+    #[serde(rename(deserialize = "NEWNATIONDETAILS"))]
+    NewNationDetails(NewNationDetails),
+    /// `1` if recruitment telegrams can be sent, `0` otherwise.
+    #[serde(rename(deserialize = "TGCANRECRUIT"))]
+    TgCanRecruit(u8),
+    #[serde(rename(deserialize = "TGQUEUE"))]
+    TgQueue(telegram::Queue),
+    #[serde(rename(deserialize = "DISPATCHES"))]
+    Dispatches(u32),
+    #[serde(rename(deserialize = "DISPATCHLIST"))]
+    DispatchList(DispatchList),
+    #[serde(rename(deserialize = "DISPATCH"))]
+    Dispatch(Dispatch),
+    /// An element this version doesn't know, most likely a field
+    /// the API has added since. Kept as it was sent.
+    #[serde(skip_deserializing)]
+    Unknown { name: String, raw_xml: String },
+}
+/// What a request is about.
+#[derive(Debug, Clone, Copy)]
+pub enum Target<'a> {
+    Nation(&'a str),
+    Region(&'a str),
+    World,
+    Wa(Council),
+}
+#[derive(Debug)]
+pub struct Request<'a> {
+    pub target: Target<'a>,
+    /// Credentials for private shards.
+    /// Public shards can be requested with `None`.
+    pub auth: Option<&'a Auth>,
+    pub shards: Vec<Shard>,
+    /// API version to ask for, normally [`crate::API_VERSION`].
+    pub version: u16,
+}
+impl Request<'_> {
+    // There are a bunch of copies and allocations
+    // involved in building this string,
+    // but it's not an optimization priority.
+    // LLVM probably sees through them anyway.
+    pub fn url(&self) -> String {
+        self.url_at(base())
+    }
+    /// URL of the request against an API at `base` instead of the usual one.
+    pub fn url_at(&self, base: &str) -> String {
+        let query = Query::new(base);
+        let mut query = match self.target {
+            Target::Nation(name) => query.param("nation", name),
+            Target::Region(name) => query.param("region", name),
+            Target::World => query,
+            Target::Wa(council) => query.param("wa", council.id().to_string()),
+        };
+        query = query.param("q", query_string(&self.shards));
+        for (key, value) in self.shards.iter().flat_map(Shard::parameters) {
+            query = query.param(key, value);
+        }
+        query.param("v", self.version.to_string()).build()
+    }
+}
+#[derive(Debug, Deserialize)]
+pub struct Data {
+    #[serde(rename(deserialize = "$value"))]
+    pub inner: Vec<ResolvedShard>,
+}
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Response<T = Data> {
+    pub data: T,
+    pub autologin: Option<String>,
+    pub pin: Option<Pin>,
+    pub rate_limit: RateLimit,
+    /// Whether the stored pin was refused and the autologin or password used instead.
+    pub pin_refused: bool,
+    /// Whether the stored autologin was refused and the password used instead.
+    /// `autologin` then holds the new one.
+    pub autologin_refused: bool,
+}
+#[derive(Debug, Error)]
+pub enum Failure {
+    #[error("no credentials stored")]
+    NoAuth,
+    #[error("credentials rejected")]
+    BadAuth,
+    // Bad pins are special because pins expire,
+    // so this is potentially recoverable.
+    // Also, pins can be invalidated by logging in separately.
+    // The `.valid()` method on pins is likely to
+    // handle pin expiration, but not arbitrary pin invalidation.
+    #[error("pin rejected")]
+    BadPin,
+    /// Too many requests. The API says how many seconds to wait in `Retry-After`.
+    #[error("rate limited{}", retry_after_message(.0))]
+    RateLimited(Option<u64>),
+    #[error("malformed response: {0}")]
+    Malformed(String),
+    /// Replaying a cassette that has no response for this URL.
+    #[error("no recorded response for {0}")]
+    NotRecorded(String),
+    /// The request or response didn't make it, like when the connection drops.
+    #[error("network error: {0}")]
+    Network(HttpError),
+    /// Any other status, with the reason the response body gives, if any.
+    #[error("unexpected response: {0}{}", reason_message(.1))]
+    Other(StatusCode, Option<String>),
+    /// A command that would change something, refused without being sent
+    /// because the nation, or everything, is read-only. Says which.
+    #[error("not sent, since {0} is read-only")]
+    ReadOnly(String),
+}
+fn retry_after_message(retry_after: &Option<u64>) -> String {
+    match retry_after {
+        Some(seconds) => format!(", retry after {} seconds", seconds),
+        None => String::new(),
+    }
+}
+fn reason_message(reason: &Option<String>) -> String {
+    match reason {
+        Some(reason) => format!(" ({})", reason),
+        None => String::new(),
+    }
+}
+impl From<RetryFailure> for Failure {
+    fn from(f: RetryFailure) -> Self {
+        match f {
+            RetryFailure::NoAuth => Self::NoAuth,
+            RetryFailure::BadAuth => Self::BadAuth,
+            RetryFailure::RateLimited(x) => Self::RateLimited(x),
+            RetryFailure::Malformed(x) => Self::Malformed(x),
+            RetryFailure::NotRecorded(x) => Self::NotRecorded(x),
+            RetryFailure::Network(x) => Self::Network(x),
+            RetryFailure::Other(code, reason) => Self::Other(code, reason),
+            RetryFailure::ReadOnly(x) => Self::ReadOnly(x),
+        }
+    }
+}
+#[derive(Debug, Error)]
+pub enum RetryFailure {
+    #[error("no credentials stored")]
+    NoAuth,
+    #[error("credentials rejected")]
+    BadAuth,
+    #[error("rate limited{}", retry_after_message(.0))]
+    RateLimited(Option<u64>),
+    #[error("malformed response: {0}")]
+    Malformed(String),
+    /// Replaying a cassette that has no response for this URL.
+    #[error("no recorded response for {0}")]
+    NotRecorded(String),
+    #[error("network error: {0}")]
+    Network(HttpError),
+    #[error("unexpected response: {0}{}", reason_message(.1))]
+    Other(StatusCode, Option<String>),
+    #[error("not sent, since {0} is read-only")]
+    ReadOnly(String),
+}
+impl Request<'_> {
+    pub async fn send<C: HttpClient>(&self, client: &C) -> Result<Response, Failure> {
+        self.send_at(client, base()).await
+    }
+    /// Send request, and retry if the pin on hand has been invalidated.
+    // This will never return BadPin.
+    pub async fn send_retry<C: HttpClient>(&self, client: &C) -> Result<Response, RetryFailure> {
+        self.send_retry_at(client, base()).await
+    }
+    /// Like `send`, against an API other than the usual one,
+    /// such as a mock server.
+    pub async fn send_at<C: HttpClient>(&self, client: &C, base: &str) -> Result<Response, Failure> {
+        send_url(client, &self.url_at(base), self.auth, true).await
+    }
+    /// Like `send_retry`, against an API other than the usual one.
+    pub async fn send_retry_at<C: HttpClient>(&self, client: &C, base: &str) -> Result<Response, RetryFailure> {
+        send_url_retry(client, &self.url_at(base), self.auth).await
+    }
+}
+
+/// Something a private command can do to a nation.
+///
+/// Only what the API offers is here. Applying to and resigning from the
+/// World Assembly, for one, can only be done on the site, as can
+/// suppressing posts on a message board and everything done during N-Day,
+/// for which the API has neither commands nor shards.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Answer an issue. Option `-1` dismisses it.
+    AnswerIssue { issue: u32, option: i32 },
+}
+impl Action {
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Action::AnswerIssue { issue, option } => vec![
+                ("c", "issue".into()),
+                ("issue", issue.to_string()),
+                ("option", option.to_string()),
+            ],
+        }
+    }
+}
+/// A private command, which changes a nation rather than just reading from it.
+#[derive(Debug)]
+pub struct Command<'a> {
+    pub nation: &'a str,
+    pub auth: &'a Auth,
+    pub action: Action,
+    /// API version to ask for, normally [`crate::API_VERSION`].
+    pub version: u16,
+    /// Refuse to send it, as for nations marked [`Nation::readonly`].
+    pub readonly: bool,
+}
+impl Command<'_> {
+    pub fn url(&self) -> String {
+        self.url_at(base())
+    }
+    /// URL of the command against an API at `base` instead of the usual one.
+    pub fn url_at(&self, base: &str) -> String {
+        let mut query = Query::new(base).param("nation", self.nation);
+        for (key, value) in self.action.parameters() {
+            query = query.param(key, value);
+        }
+        query.param("v", self.version.to_string()).build()
+    }
+    /// What sending the command would do, with credentials redacted.
+    pub fn preview(&self) -> String {
+        match auth_header(self.auth, true) {
+            Some((name, _)) => format!("GET {}\n{}: <redacted>", self.url(), name),
+            None => format!("GET {}\n(no credentials stored)", self.url()),
+        }
+    }
+    /// Send the command, retrying if the pin on hand has been invalidated.
+    /// Each command has its own response format, hence `T`.
+    pub async fn send<T: DeserializeOwned + Send + 'static>(&self, client: &impl HttpClient) -> Result<Response<T>, RetryFailure> {
+        send_command(client, self.nation, self.readonly, &self.url(), self.auth, RetryPolicy::default()).await
+            .map_err(retry_failure)
+    }
+}
+
+/// Confirm a nation's pin still works with a ping, ahead of a batch of private commands.
+/// A refused pin is marked as rejected and replaced by logging in with the other credentials.
+/// The nation's [`Details`] are refreshed along the way.
+pub async fn validate<C: HttpClient>(client: &C, nation: &mut Nation, version: u16) -> Result<PinState, Failure> {
+    // Public details come along for free, so they're kept up to date too.
+    let shards = Some(Shard::Ping).into_iter().chain(Details::shards()).collect();
+    let response = Session::new(client, nation).version(version).request(shards).await?;
+    nation.details = Details::from_shards(&response.data.inner).or(nation.details.take());
+    nation.last_ping = Some(Utc::now());
+    metrics::pinged();
+    Ok(nation.auth.pin_state())
+}
+
+/// Items from a shard that comes a page at a time, like `censusranks`,
+/// fetched a page at a time as they're asked for.
+/// Pages are spaced out like any other request, by [`crate::ratelimit`].
+pub struct Paginated<'a, C, T> {
+    client: &'a C,
+    target: Target<'a>,
+    version: u16,
+    /// The shard for the page starting at an offset.
+    page: Box<dyn Fn(u32) -> Shard + Send + Sync + 'a>,
+    /// The items on a page.
+    items: fn(Data) -> Vec<T>,
+    offset: u32,
+    /// A page shorter than this is the last one.
+    page_size: usize,
+    buffer: std::collections::VecDeque<T>,
+    done: bool,
+}
+impl<'a, C: HttpClient, T> Paginated<'a, C, T> {
+    /// Pages are fetched with `page(offset)`, starting from `first`.
+    /// Each one moves the offset on by the number of items it had.
+    pub fn new(client: &'a C, target: Target<'a>, version: u16, first: u32, page_size: usize,
+               page: impl Fn(u32) -> Shard + Send + Sync + 'a, items: fn(Data) -> Vec<T>) -> Self {
+        Self {
+            client,
+            target,
+            version,
+            page: Box::new(page),
+            items,
+            offset: first,
+            page_size,
+            buffer: Default::default(),
+            done: false,
+        }
+    }
+    /// The next item, fetching another page if need be.
+    /// `None` once the last page runs out, or after an error.
+    pub async fn next(&mut self) -> Option<Result<T, Failure>> {
+        if self.buffer.is_empty() && !self.done {
+            if let Err(e) = self.fetch().await {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        self.buffer.pop_front().map(Ok)
+    }
+    async fn fetch(&mut self) -> Result<(), Failure> {
+        let request = Request {
+            target: self.target,
+            auth: None,
+            shards: vec![(self.page)(self.offset)],
+            version: self.version,
+        };
+        let items = (self.items)(request.send(self.client).await?.data);
+        self.done = items.len() < self.page_size;
+        self.offset += items.len() as u32;
+        self.buffer.extend(items);
+        Ok(())
+    }
+}
+impl<'a, C: HttpClient> Paginated<'a, C, crate::census::Rank> {
+    /// Nations in a region or the world by a census scale, best first.
+    pub fn census_ranks(client: &'a C, target: Target<'a>, scale: CensusScale, version: u16) -> Self {
+        let items = |data: Data| data.inner.into_iter()
+            .find_map(|shard| match shard {
+                ResolvedShard::CensusRanks(x) => Some(x.nations.inner),
+                _ => None,
+            })
+            .unwrap_or_default();
+        // Ranks count from 1, twenty to a page.
+        Self::new(client, target, version, 1, 20, move |start| Shard::CensusRanks { scale, start: Some(start) }, items)
+    }
+}
+impl<'a, C: HttpClient> Paginated<'a, C, crate::rmb::Post> {
+    /// Posts on a region's message board, latest first,
+    /// fetched a hundred at a time.
+    pub fn messages(client: &'a C, region: &'a str, version: u16) -> Self {
+        // Pages come oldest first.
+        let items = |data: Data| data.inner.into_iter()
+            .find_map(|shard| match shard {
+                ResolvedShard::Messages(x) => Some(x.posts.into_iter().rev().collect()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        Self::new(client, Target::Region(region), version, 0, 100, |offset| Shard::Messages {
+            limit: Some(100),
+            offset: Some(offset),
+            from_id: None,
+        }, items)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum VersionError {
+    #[error("{0}")]
+    Request(#[from] HttpError),
+    #[error("unexpected response: {0}")]
+    Status(StatusCode),
+    #[error("couldn't read version from {0:?}")]
+    Parse(String),
+}
+
+/// Ask the API which version is current.
+/// Requests made against older versions keep working until they're retired,
+/// but may miss newer shards and fields.
+pub async fn current_version<C: HttpClient>(client: &C) -> Result<u16, VersionError> {
+    ratelimit::wait_turn().await;
+    let response = client.get(&Query::new(base()).param("a", "version").build(), &[]).await?;
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    if status != StatusCode::OK {
+        return Err(VersionError::Status(status));
+    }
+    let text = response.body.text().await?;
+    text.trim().parse().map_err(|_| VersionError::Parse(text))
+}
+
+/// The header to authenticate with, preferring a pin if `use_pin` is set.
+fn auth_header(auth: &Auth, use_pin: bool) -> Option<(&'static str, String)> {
+    match auth {
+        // Note that pins fail more easily than autologins or passwords.
+        // If a pin fails and we have another credential on hand,
+        // we should retry and save the pin we get next.
+        // This function won't control that behavior, though.
+        // It will simply return a distinct error code for that case.
+        Auth { pin: Some(pin), pin_friendly: false, .. } if use_pin && auth.pin_state() == PinState::Valid => {
+            Some(("X-Pin", pin.value.to_string()))
+        },
+        _ => match auth.login()? {
+            Login::Autologin(autologin) => Some(("X-Autologin", autologin.to_owned())),
+            Login::Password(password) => Some(("X-Password", password.to_owned())),
+        },
+    }
+}
+
+/// Whether to refuse every command that would change something.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Refuse every command that would change something from now on, like
+/// answering issues or sending telegrams, whatever the nation.
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+pub fn read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+/// Refuse a command because `what` is read-only, counting it like any other failure.
+pub(crate) fn refuse(what: &str) -> Failure {
+    let failure = Failure::ReadOnly(what.to_owned());
+    metrics::failed(&failure);
+    failure
+}
+
+/// Where requests go unless they're sent somewhere in particular.
+static BASE: RwLock<&str> = RwLock::new(crate::API_BASE);
+
+/// Base URL requests are sent to unless they're given one,
+/// [`crate::API_BASE`] unless [`set_base`] says otherwise.
+pub fn base() -> &'static str {
+    *BASE.read().unwrap()
+}
+
+#[derive(Error, Debug)]
+#[error("API base {0:?} isn't a URL: {1}")]
+pub struct InvalidBase(String, String);
+
+/// Send requests to `base` from now on instead of the live API,
+/// like a test server or a caching proxy in front of it.
+pub fn set_base(base: &str) -> Result<(), InvalidBase> {
+    reqwest::Url::parse(base).map_err(|e| InvalidBase(base.to_owned(), e.to_string()))?;
+    // Set once at startup, so leaking it costs nothing to speak of.
+    *BASE.write().unwrap() = Box::leak(base.to_owned().into_boxed_str());
+    Ok(())
+}
+
+/// Whether to describe every request and response on stderr.
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Describe every request and response on stderr from now on, rate limit headers included.
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// Describe a response on stderr, if that's been asked for, and keep its rate limit headers.
+fn observe(url: &str, status: StatusCode, using_pin: bool, rate_limit: RateLimit) {
+    ratelimit::observe(rate_limit);
+    if VERBOSE.load(Ordering::Relaxed) {
+        eprintln!("GET {} -> {}{} ({})", cassette::redact(url), status,
+                  if using_pin { ", using pin" } else { "" }, rate_limit);
+    }
+}
+
+async fn send_url<T: DeserializeOwned + Send + 'static, C: HttpClient>(client: &C, url: &str, auth: Option<&Auth>, use_pin: bool)
+                                       -> Result<Response<T>, Failure> {
+    let result = send_url_uncounted(client, url, auth, use_pin).await;
+    if let Err(e) = &result {
+        metrics::failed(e);
+    }
+    result
+}
+async fn send_url_uncounted<T: DeserializeOwned + Send + 'static, C: HttpClient>(client: &C, url: &str, auth: Option<&Auth>,
+                                                                  use_pin: bool) -> Result<Response<T>, Failure> {
+    let mut headers = Vec::new();
+    let mut using_pin = false;
+    if let Some(auth) = auth {
+        let (name, value) = auth_header(auth, use_pin).ok_or(Failure::NoAuth)?;
+        using_pin = name == "X-Pin";
+        headers.push((name, value));
+    }
+    ratelimit::wait_turn().await;
+    metrics::requested();
+    if cassette::mode() == Some(cassette::Mode::Replay) {
+        let interaction = cassette::play(url).ok_or_else(|| Failure::NotRecorded(cassette::redact(url)))?;
+        let status = StatusCode::from_u16(interaction.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let rate_limit = RateLimit::from_headers(|name| interaction.header(name));
+        observe(url, status, using_pin, rate_limit);
+        if status != StatusCode::OK {
+            let retry_after = interaction.header("Retry-After").and_then(|x| x.trim().parse().ok());
+            return Err(failure(status, retry_after, using_pin, &interaction.body));
+        }
+        let data = parse(&interaction.body)?;
+        return Ok(Response { data, autologin: None, pin: None, rate_limit, pin_refused: false, autologin_refused: false });
+    }
+    let response = client.get(url, &headers).await.map_err(Failure::Network)?;
+    let timestamp = Utc::now();
+    let (pin_value, autologin) = (response.header("X-Pin").and_then(|x| x.parse().ok()),
+                                  response.header("X-Autologin").map(String::from));
+    let pin = pin_value.map(|value| Pin {
+        value, timestamp,
+    });
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let rate_limit = RateLimit::from_headers(|name| response.header(name));
+    observe(url, status, using_pin, rate_limit);
+    let retry_after = response.header("Retry-After").and_then(|x| x.trim().parse().ok());
+    let (headers, body) = (response.headers, response.body);
+    // Recording needs the whole body, so it can't be parsed as it streams in.
+    let data = if cassette::mode() == Some(cassette::Mode::Record) {
+        let body = body.text().await.map_err(Failure::Network)?;
+        let interaction = cassette::Interaction::new(url, status.as_u16(), &headers, body.clone());
+        if let Err(e) = cassette::record(interaction) {
+            eprintln!("Couldn't record response: {}", e);
+        }
+        if status != StatusCode::OK {
+            return Err(failure(status, retry_after, using_pin, &body));
+        }
+        parse(&body)?
+    } else {
+        if status != StatusCode::OK {
+            // Error bodies are short, and only read for the reason they give.
+            let body = body.text().await.unwrap_or_default();
+            return Err(failure(status, retry_after, using_pin, &body));
+        }
+        parse_streaming(body).await?
+    };
+    Ok(Response { data, autologin, pin, rate_limit, pin_refused: false, autologin_refused: false })
+}
+/// What a response other than 200 means.
+fn failure(status: StatusCode, retry_after: Option<u64>, using_pin: bool, body: &str) -> Failure {
+    if status == StatusCode::FORBIDDEN {
+        if using_pin { Failure::BadPin } else { Failure::BadAuth }
+    } else if status == StatusCode::TOO_MANY_REQUESTS {
+        Failure::RateLimited(retry_after)
+    } else {
+        Failure::Other(status, error_reason(body))
+    }
+}
+/// The reason an error response gives, from a body like
+/// `<h1>Not Found</h1><p>Unknown nation: "nowhere".`, `<ERROR>Invalid option.</ERROR>`,
+/// or the plain text the telegram API sends.
+pub(crate) fn error_reason(body: &str) -> Option<String> {
+    const LONGEST: usize = 300;
+    // Tags are matched up to their name, so `<p style="...">` counts as `<p`.
+    let inner = |open: &str, close: &str| {
+        let start = body.match_indices(open)
+            .map(|(i, _)| i + open.len())
+            .find(|&i| matches!(body[i..].chars().next(), Some('>') | Some(' ')))?;
+        let start = start + body[start..].find('>')? + 1;
+        let len = body[start..].find(close).unwrap_or(body.len() - start);
+        Some(&body[start..start + len])
+    };
+    // The heading only repeats the status, so the paragraph is what's worth keeping.
+    let text = inner("<ERROR", "</ERROR>").or_else(|| inner("<p", "</p>")).unwrap_or(body);
+    let mut plain = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                plain.push(' ');
+            },
+            _ if !in_tag => plain.push(c),
+            _ => (),
+        }
+    }
+    let plain = plain.split_whitespace().join(" ");
+    let plain = match quick_xml::escape::unescape(plain.as_bytes()) {
+        Ok(x) => String::from_utf8_lossy(&x).into_owned(),
+        Err(_) => plain,
+    };
+    match plain.char_indices().nth(LONGEST) {
+        _ if plain.is_empty() => None,
+        Some((end, _)) => Some(format!("{}...", &plain[..end])),
+        None => Some(plain),
+    }
+}
+/// Response body handed over to a blocking reader one chunk at a time.
+#[cfg(feature = "native")]
+struct ChunkReader<B> {
+    chunks: tokio::sync::mpsc::Receiver<B>,
+    current: B,
+    offset: usize,
+    /// Everything read so far, if it's to be kept.
+    kept: Option<Vec<u8>>,
+}
+#[cfg(feature = "native")]
+impl<B: AsRef<[u8]>> ChunkReader<B> {
+    /// The whole body, if it was kept, waiting for the rest of it to arrive.
+    fn into_kept(mut self) -> Option<Vec<u8>> {
+        let mut kept = self.kept.take()?;
+        kept.extend_from_slice(&self.current.as_ref()[self.offset..]);
+        while let Some(chunk) = self.chunks.blocking_recv() {
+            kept.extend_from_slice(chunk.as_ref());
+        }
+        Some(kept)
+    }
+}
+#[cfg(feature = "native")]
+impl<B: AsRef<[u8]>> std::io::Read for ChunkReader<B> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.offset == self.current.as_ref().len() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.current = chunk;
+                    self.offset = 0;
+                },
+                None => return Ok(0),
+            }
+        }
+        let rest = &self.current.as_ref()[self.offset..];
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        if let Some(kept) = &mut self.kept {
+            kept.extend_from_slice(&rest[..len]);
+        }
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+/// Whether a response is being parsed as shards.
+fn is_data<T: 'static>() -> bool {
+    core::any::TypeId::of::<T>() == core::any::TypeId::of::<Data>()
+}
+/// Whether parsing failed on an element the types here don't have.
+fn is_unknown_element(e: &quick_xml::DeError) -> bool {
+    e.to_string().starts_with("unknown variant")
+}
+
+/// Deserialize a whole response body. Elements in shard responses that
+/// this version doesn't know are kept as [`ResolvedShard::Unknown`]
+/// rather than failing the whole response.
+fn parse<T: DeserializeOwned + 'static>(xml: &str) -> Result<T, Failure> {
+    match quick_xml::de::from_str(xml) {
+        Ok(x) => Ok(x),
+        Err(e) if is_data::<T>() && is_unknown_element(&e) => {
+            let data: Box<dyn core::any::Any> = Box::new(parse_leniently(xml)?);
+            Ok(*data.downcast().expect("checked to be Data"))
+        },
+        Err(e) => Err(Failure::Malformed(e.to_string())),
+    }
+}
+
+/// Parse shards one at a time, so one that can't be parsed
+/// because its element is unknown doesn't take the rest with it.
+fn parse_leniently(xml: &str) -> Result<Data, Failure> {
+    use quick_xml::events::Event;
+    let malformed = |e: &dyn Display| Failure::Malformed(e.to_string());
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut buf = Vec::new();
+    // The root's start tag, attributes and all, and its name.
+    let mut root = ("", String::new());
+    let mut depth = 0;
+    let mut element = (0, String::new());
+    let mut inner = Vec::new();
+    let mut parse_one = |root: &(&str, String), name: &str, raw: &str| -> Result<(), Failure> {
+        match quick_xml::de::from_str::<Data>(&format!("{}{}</{}>", root.0, raw, root.1)) {
+            Ok(data) => inner.extend(data.inner),
+            Err(e) if is_unknown_element(&e) => {
+                warn_unknown(name);
+                inner.push(ResolvedShard::Unknown { name: name.to_owned(), raw_xml: raw.to_owned() });
+            },
+            Err(e) => return Err(malformed(&e)),
+        }
+        Ok(())
+    };
+    loop {
+        let start = reader.buffer_position();
+        let event = reader.read_event(&mut buf).map_err(|e| malformed(&e))?;
+        let end = reader.buffer_position();
+        match event {
+            Event::Start(tag) => {
+                depth += 1;
+                let name = String::from_utf8_lossy(tag.name()).into_owned();
+                match depth {
+                    1 => root = (&xml[start..end], name),
+                    2 => element = (start, name),
+                    _ => (),
+                }
+            },
+            Event::Empty(tag) if depth == 1 => {
+                parse_one(&root, &String::from_utf8_lossy(tag.name()), &xml[start..end])?;
+            },
+            Event::End(_) => {
+                if depth == 2 {
+                    parse_one(&root, &element.1, &xml[element.0..end])?;
+                }
+                depth -= 1;
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(Data { inner })
+}
+
+/// Elements already warned about, so each only gets the one warning.
+static UNKNOWN_SEEN: std::sync::Mutex<std::collections::BTreeSet<String>> =
+    std::sync::Mutex::new(std::collections::BTreeSet::new());
+fn warn_unknown(name: &str) {
+    if UNKNOWN_SEEN.lock().unwrap().insert(name.to_owned()) {
+        eprintln!("warning: skipping unknown <{}> in a response; a newer version may know what it is", name);
+    }
+}
+
+/// Deserialize a response body as it arrives.
+/// Happenings, census ranks, and region nation lists can run to megabytes,
+/// which this only holds whole for shard responses, in case an unknown
+/// element means they have to be parsed again more carefully.
+#[cfg(feature = "native")]
+async fn parse_streaming<T: DeserializeOwned + Send + 'static>(mut body: Body) -> Result<T, Failure> {
+    let (sender, chunks) = tokio::sync::mpsc::channel(16);
+    let kept = if is_data::<T>() { Some(Vec::new()) } else { None };
+    let parser = tokio::task::spawn_blocking(move || {
+        let mut reader = ChunkReader { chunks, current: Default::default(), offset: 0, kept };
+        match quick_xml::de::from_reader(std::io::BufReader::new(&mut reader)) {
+            Ok(x) => Ok(x),
+            Err(e) if is_unknown_element(&e) => Err((e, reader.into_kept())),
+            Err(e) => Err((e, None)),
+        }
+    });
+    while let Some(chunk) = body.chunk().await.map_err(Failure::Network)? {
+        // The parser only hangs up early if it's already failed.
+        if sender.send(chunk).await.is_err() {
+            break;
+        }
+    }
+    drop(sender);
+    match parser.await.expect("response parser panicked") {
+        Ok(x) => Ok(x),
+        Err((_, Some(kept))) => parse(&String::from_utf8_lossy(&kept)),
+        Err((e, None)) => Err(Failure::Malformed(e.to_string())),
+    }
+}
+/// Without a runtime to parse on the side, read the whole body first.
+#[cfg(not(feature = "native"))]
+async fn parse_streaming<T: DeserializeOwned + Send + 'static>(body: Body) -> Result<T, Failure> {
+    let text = body.text().await.map_err(Failure::Network)?;
+    parse(&text)
+}
+
+// This will never return BadPin.
+async fn send_url_retry<T: DeserializeOwned + Send + 'static, C: HttpClient>(client: &C, url: &str, auth: Option<&Auth>)
+                                             -> Result<Response<T>, RetryFailure> {
+    send_url_policy(client, url, auth, RetryPolicy::default()).await.map_err(retry_failure)
+}
+// Only for failures after retrying a refused pin, which can't be BadPin.
+fn retry_failure(failure: Failure) -> RetryFailure {
+    match failure {
+        Failure::BadPin => unreachable!("bad pin on retry"),
+        Failure::NoAuth => RetryFailure::NoAuth,
+        Failure::BadAuth => RetryFailure::BadAuth,
+        Failure::RateLimited(x) => RetryFailure::RateLimited(x),
+        Failure::Malformed(x) => RetryFailure::Malformed(x),
+        Failure::NotRecorded(x) => RetryFailure::NotRecorded(x),
+        Failure::Network(x) => RetryFailure::Network(x),
+        Failure::Other(code, reason) => RetryFailure::Other(code, reason),
+        Failure::ReadOnly(x) => RetryFailure::ReadOnly(x),
+    }
+}
+/// Send a private command for `nation`, unless the nation (`readonly`)
+/// or everything is read-only, and log it to the audit log either way.
+/// Every private command goes through here.
+pub(crate) async fn send_command<T: DeserializeOwned + Send + 'static, C: HttpClient>(client: &C, nation: &str, readonly: bool,
+                                                                     url: &str, auth: &Auth, policy: RetryPolicy)
+                                                                     -> Result<Response<T>, Failure> {
+    let result = if readonly {
+        Err(refuse(nation))
+    } else if read_only() {
+        Err(refuse("everything"))
+    } else {
+        send_url_policy(client, url, Some(auth), policy).await
+    };
+    crate::audit::record(Some(nation), url, result.as_ref().map(|_| ()).map_err(ToString::to_string));
+    result
+}
+/// Send with `auth`, logging in again when credentials are refused as far as `policy` allows.
+pub(crate) async fn send_url_policy<T: DeserializeOwned + Send + 'static, C: HttpClient>(client: &C, url: &str, auth: Option<&Auth>,
+                                                                        policy: RetryPolicy) -> Result<Response<T>, Failure> {
+    let mut pin_refused = false;
+    let result = match send_url(client, url, auth, true).await {
+        Err(Failure::BadPin) if policy.refresh_pin => {
+            pin_refused = true;
+            send_url(client, url, auth, false).await
+        },
+        result => result,
+    };
+    // Changing the password on the site invalidates the autologin,
+    // so a password that's still on hand gets one more try.
+    let result = match (result, auth.and_then(Auth::password_fallback)) {
+        (Err(Failure::BadAuth), Some(fallback)) if policy.password_fallback => {
+            let nation = reqwest::Url::parse(url).ok()
+                .and_then(|x| x.query_pairs().find(|(key, _)| key == "nation").map(|(_, value)| value.into_owned()))
+                .unwrap_or_default();
+            eprintln!("warning: the autologin for {} was refused, likely because its password was changed on the site; \
+                       logging in with the stored password instead", nation);
+            send_url(client, url, Some(&fallback), false).await.map(|response| Response { autologin_refused: true, ..response })
+        },
+        (result, _) => result,
+    };
+    result.map(|response| Response { pin_refused, ..response })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nation_url(name: &str, shards: Vec<Shard>) -> String {
+        Request {
+            target: Target::Nation(name),
+            auth: None,
+            shards,
+            version: 11,
+        }.url()
+    }
+
+    #[test]
+    fn plain_name() {
+        assert_eq!(nation_url("testlandia", vec![Shard::Ping]),
+                   "https://www.nationstates.net/cgi-bin/api.cgi?nation=testlandia&q=ping&v=11");
+    }
+
+    #[test]
+    fn name_with_spaces_and_apostrophe() {
+        assert_eq!(nation_url("Mad Hatter's Tea", vec![Shard::Name]),
+                   "https://www.nationstates.net/cgi-bin/api.cgi?nation=Mad+Hatter%27s+Tea&q=name&v=11");
+    }
+
+    #[test]
+    fn name_with_query_characters() {
+        let url = nation_url("a&q=issues#x", vec![Shard::Name]);
+        assert_eq!(url, "https://www.nationstates.net/cgi-bin/api.cgi?nation=a%26q%3Dissues%23x&q=name&v=11");
+        let parsed = reqwest::Url::parse(&url).unwrap();
+        let pairs: Vec<_> = parsed.query_pairs().collect();
+        assert_eq!(pairs[0].1, "a&q=issues#x");
+        assert_eq!(pairs.len(), 3);
+    }
+
+    #[test]
+    fn non_ascii_name() {
+        assert_eq!(nation_url("Été", vec![Shard::Name]),
+                   "https://www.nationstates.net/cgi-bin/api.cgi?nation=%C3%89t%C3%A9&q=name&v=11");
+    }
+
+    #[test]
+    fn multiple_shards_and_parameters() {
+        let url = nation_url("testlandia", vec![
+            Shard::Name,
+            Shard::Deck { nation: "Card Collector".into() },
+        ]);
+        assert_eq!(url, "https://www.nationstates.net/cgi-bin/api.cgi\
+                         ?nation=testlandia&q=name+cards+deck&nationname=Card+Collector&v=11");
+    }
+
+    #[test]
+    fn region_and_world() {
+        let region = Request {
+            target: Target::Region("the north pacific"),
+            auth: None,
+            shards: vec![Shard::Founder],
+            version: 12,
+        };
+        assert_eq!(region.url(),
+                   "https://www.nationstates.net/cgi-bin/api.cgi?region=the+north+pacific&q=founder&v=12");
+        let world = Request {
+            target: Target::World,
+            auth: None,
+            shards: vec![Shard::RegionsByTag { tags: Vec::new() }],
+            version: 11,
+        };
+        assert!(world.url().starts_with("https://www.nationstates.net/cgi-bin/api.cgi?q=regionsbytag&tags="));
+    }
+
+    #[test]
+    fn census_history() {
+        use chrono::TimeZone;
+        let shards = ShardSet::new()
+            .census([CensusScale::Economy])
+            .mode(CensusMode::History)
+            .from(Utc.timestamp_opt(1_600_000_000, 0).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(nation_url("testlandia", shards),
+                   "https://www.nationstates.net/cgi-bin/api.cgi\
+                    ?nation=testlandia&q=census&scale=1&mode=history&from=1600000000&v=11");
+    }
+
+    #[test]
+    fn shard_set_mistakes() {
+        assert_eq!(ShardSet::new().name().mode(CensusMode::Score).build().unwrap_err(),
+                   ShardError::Unsupported { parameter: "mode", shard: "name".into() });
+        assert_eq!(ShardSet::new().limit(10).build().unwrap_err(), ShardError::NoShard("limit"));
+        assert_eq!(ShardSet::new().census([]).mode(CensusMode::History).mode(CensusMode::Rank).build().unwrap_err(),
+                   ShardError::HistoryMixed);
+        assert_eq!(ShardSet::new().census([]).to(Utc::now()).build().unwrap_err(), ShardError::RangeWithoutHistory);
+        assert_eq!(ShardSet::new().shard(Shard::VoteTrack).build().unwrap_err(),
+                   ShardError::NeedsResolution("votetrack".into()));
+        assert!(matches!(ShardSet::new().census([CensusScale::Economy]).census_ranks(CensusScale::Influence).build(),
+                         Err(ShardError::Conflict { parameter: "scale", .. })));
+    }
+
+    #[test]
+    fn error_reasons() {
+        assert_eq!(error_reason("<ERROR>Invalid option.</ERROR>").as_deref(), Some("Invalid option."));
+        assert_eq!(error_reason("Client Not Registered For API\n").as_deref(), Some("Client Not Registered For API"));
+        assert_eq!(error_reason("<h1>Bad Request</h1><p>Missing <b>q</b> parameter.</p>").as_deref(),
+                   Some("Missing q parameter."));
+        assert_eq!(error_reason("  "), None);
+    }
+
+    #[test]
+    fn bad_base_is_refused() {
+        assert!(set_base("not a url").is_err());
+        assert_eq!(base(), crate::API_BASE);
+    }
+
+    #[test]
+    fn command() {
+        let auth = Auth::default();
+        let command = Command {
+            nation: "Mad Hatter's Tea",
+            auth: &auth,
+            action: Action::AnswerIssue { issue: 12, option: -1 },
+            version: 11,
+            readonly: false,
+        };
+        assert_eq!(command.url(),
+                   "https://www.nationstates.net/cgi-bin/api.cgi\
+                    ?nation=Mad+Hatter%27s+Tea&c=issue&issue=12&option=-1&v=11");
+    }
+
+    #[test]
+    fn unknown_shards_are_kept() {
+        let xml = "<NATION id=\"testlandia\"><MOTTO>Hi</MOTTO><NEWTHING><X>1</X></NEWTHING>\
+                   <NAME>Testlandia</NAME><EMPTYTHING/></NATION>";
+        let data: Data = parse(xml).unwrap();
+        match &data.inner[..] {
+            [ResolvedShard::Motto(motto), ResolvedShard::Unknown { name, raw_xml }, ResolvedShard::Name(_),
+             ResolvedShard::Unknown { name: empty, .. }] => {
+                assert_eq!(motto, "Hi");
+                assert_eq!(name, "NEWTHING");
+                assert_eq!(raw_xml, "<NEWTHING><X>1</X></NEWTHING>");
+                assert_eq!(empty, "EMPTYTHING");
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
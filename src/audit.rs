@@ -0,0 +1,124 @@
+//! An append-only log of every request that changes something, like
+//! answering an issue or sending a telegram, so people running nations
+//! together can tell who did what and when.
+//!
+//! The log is JSON lines, one [`Entry`] per request, with credentials
+//! redacted the same way cassettes redact them. Nothing is logged until
+//! [`log_to`] is called, and replayed requests are never logged.
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+
+use crate::cassette::{self, Mode};
+
+/// One request that changed something.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub time: DateTime<Utc>,
+    /// Who ran it, as configured or from the `USER` or `USERNAME` variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    /// Nation acting, where the request says.
+    /// Telegrams only say who they're to, which is in `params`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nation: Option<String>,
+    /// What was done, like `issue` or `sendTG`.
+    pub command: String,
+    /// Where the request went, without its query.
+    pub endpoint: String,
+    /// Query parameters, with credentials redacted.
+    pub params: BTreeMap<String, String>,
+    /// `ok`, or what went wrong.
+    pub outcome: String,
+}
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("line {0}: {1}")]
+    Json(usize, serde_json::Error),
+}
+
+struct Log {
+    path: PathBuf,
+    operator: Option<String>,
+}
+
+lazy_static! {
+    static ref LOG: Mutex<Option<Log>> = Mutex::new(None);
+}
+
+/// Append an entry for every request that changes something to `path` from now on.
+/// Without an `operator`, the name of the user running the tool is used.
+pub fn log_to(path: &Path, operator: Option<String>) {
+    let operator = operator.or_else(|| std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok());
+    *LOG.lock().unwrap() = Some(Log { path: path.to_owned(), operator });
+}
+
+/// Log a request to `url` made by `nation`, if logging is on.
+/// Failing to write the log doesn't undo the request, so it's only warned about.
+pub(crate) fn record(nation: Option<&str>, url: &str, outcome: Result<(), String>) {
+    if cassette::mode() == Some(Mode::Replay) {
+        return;
+    }
+    let log = LOG.lock().unwrap();
+    let log = match &*log {
+        Some(x) => x,
+        None => return,
+    };
+    let url = cassette::redact(url);
+    let (endpoint, params) = match reqwest::Url::parse(&url) {
+        Ok(parsed) => {
+            let params: BTreeMap<_, _> = parsed.query_pairs().map(|(k, v)| (k.into_owned(), v.into_owned())).collect();
+            let endpoint = parsed.as_str().split('?').next().unwrap_or_default().to_owned();
+            (endpoint, params)
+        },
+        Err(_) => (url, BTreeMap::new()),
+    };
+    let entry = Entry {
+        time: Utc::now(),
+        operator: log.operator.clone(),
+        nation: nation.map(str::to_owned),
+        command: params.get("c").or_else(|| params.get("a")).cloned().unwrap_or_default(),
+        endpoint,
+        params,
+        outcome: outcome.err().unwrap_or_else(|| "ok".to_owned()),
+    };
+    if let Err(e) = append(&log.path, &entry) {
+        eprintln!("Warning: couldn't write to the audit log at {}: {}", log.path.display(), e);
+    }
+}
+
+fn append(path: &Path, entry: &Entry) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    // One write per entry, so entries from processes running at once don't interleave.
+    std::fs::OpenOptions::new().append(true).create(true).open(path)?.write_all(line.as_bytes())
+}
+
+/// Every entry in the log at `path`, oldest first. A missing log is an empty one.
+pub fn read(path: &Path) -> Result<Vec<Entry>, AuditError> {
+    let file = match std::fs::File::open(path) {
+        Ok(x) => x,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries = Vec::new();
+    for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line).map_err(|e| AuditError::Json(i + 1, e))?);
+    }
+    Ok(entries)
+}
@@ -0,0 +1,17 @@
+//! Banners, the header images a nation can display.
+//!
+//! The API only sends banner codes like `v1`, not names,
+//! so banners are shown by code and image.
+use serde::Deserialize;
+
+/// Where the site serves a banner's image.
+pub fn image_url(code: &str) -> String {
+    format!("https://www.nationstates.net/images/banners/{}.jpg", code)
+}
+
+/// Banners a nation may display, from the `banners` shard.
+#[derive(Debug, Deserialize, Default)]
+pub struct Banners {
+    #[serde(rename = "BANNER", default)]
+    pub codes: Vec<String>,
+}
@@ -0,0 +1,430 @@
+//! Rendering the BBCode NationStates uses for dispatches, factbooks, issues,
+//! and RMB posts as something readable outside a browser: plain text, text
+//! styled with ANSI escape codes for terminals, or Markdown.
+//!
+//! Links, including `[nation]` and `[region]` tags, keep their URLs, since a
+//! terminal can't follow a link it can't see. Tables are laid out in
+//! columns, and spoilers are shown opened.
+//!
+//! Tags are matched case-insensitively. A closing tag with nothing open to
+//! close, or an opening tag that is never closed, is left as written, the
+//! way the site shows it. Tags this doesn't know keep their contents and
+//! lose the markup.
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// What to render BBCode as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Text with the markup dropped, and links written out after their text.
+    Plain,
+    /// Like `Plain`, with bold, italics, underlines, and strikethroughs
+    /// shown with ANSI escape codes.
+    Ansi,
+    Markdown,
+}
+
+const SITE: &str = "https://www.nationstates.net";
+
+lazy_static! {
+    static ref TAG: Regex = Regex::new(r"\[(/?)([a-zA-Z][a-zA-Z0-9]*|\*)(?:=([^\]]*))?\]").unwrap();
+    static ref BLANK_LINES: Regex = Regex::new(r"\n{3,}").unwrap();
+    static ref ESCAPE_CODE: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Tag { name: String, arg: Option<String>, children: Vec<Node> },
+}
+
+/// A tag still waiting for its closing tag.
+struct Open {
+    name: String,
+    arg: Option<String>,
+    /// The opening tag as written, for when it turns out not to be closed.
+    raw: String,
+    children: Vec<Node>,
+}
+
+/// Tags that stand alone, with no closing tag.
+fn is_void(name: &str) -> bool {
+    name == "hr"
+}
+
+/// Tags whose contents are shown as written rather than parsed.
+fn is_verbatim(name: &str) -> bool {
+    matches!(name, "code" | "pre" | "noparse")
+}
+
+fn parse(text: &str) -> Vec<Node> {
+    let mut root = Vec::new();
+    let mut stack: Vec<Open> = Vec::new();
+    let mut pos = 0;
+    while let Some(captures) = TAG.captures_at(text, pos) {
+        let whole = captures.get(0).unwrap();
+        push_text(top(&mut stack, &mut root), &text[pos..whole.start()]);
+        pos = whole.end();
+        let name = captures[2].to_lowercase();
+        let arg = captures.get(3).map(|x| x.as_str().trim().trim_matches('"').to_owned());
+        if !captures[1].is_empty() {
+            match stack.iter().rposition(|x| x.name == name) {
+                Some(i) => {
+                    // Anything opened inside and left open, like list items, ends here too.
+                    while stack.len() > i {
+                        close(&mut stack, &mut root);
+                    }
+                }
+                None => push_text(top(&mut stack, &mut root), whole.as_str()),
+            }
+        } else if is_void(&name) {
+            top(&mut stack, &mut root).push(Node::Tag { name, arg, children: Vec::new() });
+        } else if is_verbatim(&name) {
+            let end = format!("[/{}]", name);
+            let rest = &text[pos..];
+            match rest.to_lowercase().find(&end) {
+                Some(i) => {
+                    let children = vec![Node::Text(rest[..i].to_owned())];
+                    top(&mut stack, &mut root).push(Node::Tag { name, arg, children });
+                    pos += i + end.len();
+                }
+                None => push_text(top(&mut stack, &mut root), whole.as_str()),
+            }
+        } else {
+            if name == "*" && stack.last().is_some_and(|x| x.name == "*") {
+                close(&mut stack, &mut root);
+            }
+            stack.push(Open { name, arg, raw: whole.as_str().to_owned(), children: Vec::new() });
+        }
+    }
+    push_text(top(&mut stack, &mut root), &text[pos..]);
+    while let Some(open) = stack.pop() {
+        let parent = top(&mut stack, &mut root);
+        if open.name == "*" {
+            parent.push(Node::Tag { name: open.name, arg: open.arg, children: open.children });
+        } else {
+            push_text(parent, &open.raw);
+            parent.extend(open.children);
+        }
+    }
+    root
+}
+
+/// Where the next node goes: inside the innermost open tag, if there is one.
+fn top<'a>(stack: &'a mut [Open], root: &'a mut Vec<Node>) -> &'a mut Vec<Node> {
+    stack.last_mut().map_or(root, |x| &mut x.children)
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match nodes.last_mut() {
+        Some(Node::Text(x)) => x.push_str(text),
+        _ => nodes.push(Node::Text(text.to_owned())),
+    }
+}
+
+/// Close the innermost open tag, adding it to whatever encloses it.
+fn close(stack: &mut Vec<Open>, root: &mut Vec<Node>) {
+    let open = stack.pop().unwrap();
+    top(stack, root).push(Node::Tag { name: open.name, arg: open.arg, children: open.children });
+}
+
+/// Render BBCode `text` as `format`.
+///
+/// ```
+/// use nation::bbcode::{render, Format};
+/// let text = "[b]Welcome[/b] to [region]the_north_pacific[/region]!";
+/// assert_eq!(render(text, Format::Plain),
+///            "Welcome to the north pacific (https://www.nationstates.net/region=the_north_pacific)!");
+/// assert_eq!(render(text, Format::Markdown),
+///            "**Welcome** to [the north pacific](https://www.nationstates.net/region=the_north_pacific)!");
+/// ```
+pub fn render(text: &str, format: Format) -> String {
+    let mut out = String::new();
+    Renderer { format, lists: Vec::new() }.nodes(&parse(text), &mut out);
+    let out = out.lines().map(str::trim_end).collect::<Vec<_>>().join("\n");
+    BLANK_LINES.replace_all(out.trim_matches('\n'), "\n\n").into_owned()
+}
+
+struct Renderer {
+    format: Format,
+    /// Item counters for the lists being rendered, innermost last.
+    /// `None` for bulleted lists.
+    lists: Vec<Option<u32>>,
+}
+
+impl Renderer {
+    fn markdown(&self) -> bool {
+        self.format == Format::Markdown
+    }
+
+    fn ansi(&self) -> bool {
+        self.format == Format::Ansi
+    }
+
+    /// `text` with the ANSI attribute `on` set, then reset with `off`, for ANSI only.
+    fn style(&self, (on, off): (u8, u8), text: &str) -> String {
+        if self.ansi() && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[{}m", on, text, off)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    fn nodes(&mut self, nodes: &[Node], out: &mut String) {
+        for node in nodes {
+            match node {
+                Node::Text(text) if self.markdown() => out.push_str(&escape(text)),
+                Node::Text(text) => out.push_str(text),
+                Node::Tag { name, arg, children } => self.tag(name, arg.as_deref(), children, out),
+            }
+        }
+    }
+
+    fn inner(&mut self, nodes: &[Node]) -> String {
+        let mut out = String::new();
+        self.nodes(nodes, &mut out);
+        out
+    }
+
+    /// Rendered children wrapped in `mark` on both sides for Markdown,
+    /// or styled with `ansi` for ANSI.
+    fn emphasis(&mut self, mark: &str, ansi: (u8, u8), children: &[Node], out: &mut String) {
+        let inner = self.inner(children);
+        if self.markdown() && !mark.is_empty() && !inner.trim().is_empty() {
+            out.push_str(mark);
+            out.push_str(&inner);
+            out.push_str(mark);
+        } else {
+            out.push_str(&self.style(ansi, &inner));
+        }
+    }
+
+    fn link(&self, url: &str, text: &str, out: &mut String) {
+        if self.markdown() {
+            out.push_str(&format!("[{}]({})", text, url.replace(' ', "%20")));
+        } else if text.is_empty() || text == url {
+            out.push_str(&self.style(UNDERLINE, url));
+        } else {
+            out.push_str(&format!("{} ({})", text, self.style(UNDERLINE, url)));
+        }
+    }
+
+    /// Rows of cells, each rendered on one line, lined up in columns.
+    fn table(&mut self, children: &[Node], out: &mut String) {
+        let mut rows = Vec::new();
+        for (_, row) in tags(children, &["tr"]) {
+            let cells: Vec<_> = tags(row, &["td", "th"]).map(|(name, cell)| {
+                let text = self.inner(cell).split_whitespace().collect::<Vec<_>>().join(" ");
+                match self.format {
+                    Format::Markdown => text.replace('|', "\\|"),
+                    _ if name == "th" => self.style(BOLD, &text),
+                    _ => text,
+                }
+            }).collect();
+            rows.push(cells);
+        }
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        let width = |x: &str| ESCAPE_CODE.replace_all(x, "").chars().count();
+        let widths: Vec<_> = (0..columns)
+            .map(|i| rows.iter().filter_map(|x| x.get(i)).map(|x| width(x)).max().unwrap_or(0))
+            .collect();
+        block(out);
+        out.push('\n');
+        for (i, row) in rows.iter().enumerate() {
+            let cells = (0..columns).map(|j| {
+                let cell = row.get(j).map_or("", String::as_str);
+                format!("{}{}", cell, " ".repeat(widths[j] - width(cell)))
+            });
+            if self.markdown() {
+                out.push_str(&format!("| {} |\n", cells.collect::<Vec<_>>().join(" | ")));
+                // Markdown tables need a header, so the first row is it.
+                if i == 0 {
+                    let rule = widths.iter().map(|x| "-".repeat((*x).max(3))).collect::<Vec<_>>();
+                    out.push_str(&format!("| {} |\n", rule.join(" | ")));
+                }
+            } else {
+                out.push_str(&cells.collect::<Vec<_>>().join("  "));
+                out.push('\n');
+            }
+        }
+        out.push('\n');
+    }
+
+    fn tag(&mut self, name: &str, arg: Option<&str>, children: &[Node], out: &mut String) {
+        match name {
+            "b" => self.emphasis("**", BOLD, children, out),
+            "i" => self.emphasis("*", ITALIC, children, out),
+            "u" => self.emphasis("", UNDERLINE, children, out),
+            "strike" | "s" => self.emphasis("~~", STRIKE, children, out),
+            "url" => {
+                let text = self.inner(children);
+                let url = arg.map(str::to_owned).unwrap_or_else(|| plain(children));
+                self.link(&url, &text, out);
+            }
+            "nation" | "region" => {
+                let id = arg.map(str::to_owned).unwrap_or_else(|| plain(children));
+                let url = format!("{}/{}={}", SITE, name, crate::canonical_name(&id));
+                let text = if children.is_empty() { id.replace('_', " ") } else { plain(children).replace('_', " ") };
+                if self.markdown() {
+                    out.push_str(&format!("[{}]({})", escape(&text), url));
+                } else {
+                    self.link(&url, &text, out);
+                }
+            }
+            "img" => {
+                let url = plain(children);
+                if self.markdown() {
+                    out.push_str(&format!("![]({})", url.trim()));
+                } else {
+                    out.push_str(&format!("[image: {}]", url.trim()));
+                }
+            }
+            "hr" => {
+                block(out);
+                out.push_str("\n---\n\n");
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = self.inner(children);
+                block(out);
+                out.push('\n');
+                if self.markdown() {
+                    out.push_str(&"#".repeat(name[1..].parse().unwrap()));
+                    out.push(' ');
+                }
+                out.push_str(&self.style(BOLD, text.trim()));
+                out.push_str("\n\n");
+            }
+            "quote" => {
+                let text = self.inner(children);
+                block(out);
+                out.push('\n');
+                if let Some(author) = arg.and_then(|x| x.split(';').next()).filter(|x| !x.is_empty()) {
+                    out.push_str(&format!("{} wrote:\n", author));
+                }
+                for line in text.trim_matches('\n').lines() {
+                    out.push_str("> ");
+                    out.push_str(line);
+                    out.push('\n');
+                }
+                out.push('\n');
+            }
+            "code" | "pre" => {
+                let text = plain(children);
+                block(out);
+                out.push('\n');
+                if self.markdown() {
+                    out.push_str(&format!("```\n{}\n```\n\n", text.trim_matches('\n')));
+                } else {
+                    for line in text.trim_matches('\n').lines() {
+                        out.push_str("    ");
+                        out.push_str(line);
+                        out.push('\n');
+                    }
+                    out.push('\n');
+                }
+            }
+            "table" => self.table(children, out),
+            "spoiler" => {
+                let text = self.inner(children);
+                let title = arg.filter(|x| !x.is_empty()).unwrap_or("Spoiler");
+                block(out);
+                out.push('\n');
+                if self.markdown() {
+                    out.push_str(&format!("<details><summary>{}</summary>\n\n{}\n\n</details>\n\n",
+                                          title, text.trim_matches('\n')));
+                } else {
+                    out.push_str(&self.style(BOLD, &format!("{}:", title)));
+                    out.push('\n');
+                    for line in text.trim_matches('\n').lines() {
+                        out.push_str("| ");
+                        out.push_str(line);
+                        out.push('\n');
+                    }
+                    out.push('\n');
+                }
+            }
+            "noparse" => {
+                let text = plain(children);
+                out.push_str(&if self.markdown() { escape(&text) } else { text });
+            }
+            "list" => {
+                self.lists.push(if arg.is_some() { Some(1) } else { None });
+                block(out);
+                // Only what's inside items is shown; a list's own text is whitespace.
+                for (_, item) in tags(children, &["*"]) {
+                    self.item(item, out);
+                }
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    out.push('\n');
+                }
+            }
+            "*" => self.item(children, out),
+            _ => self.nodes(children, out),
+        }
+    }
+
+    /// A list item, under the innermost list's marker.
+    fn item(&mut self, children: &[Node], out: &mut String) {
+        let marker = match self.lists.last_mut() {
+            Some(Some(n)) => {
+                *n += 1;
+                format!("{}.", *n - 1)
+            }
+            _ => "-".to_owned(),
+        };
+        let text = self.inner(children);
+        block(out);
+        // Nested lists end up indented under their item along with the rest of its lines.
+        let mut lines = text.trim().lines();
+        out.push_str(&format!("{} {}\n", marker, lines.next().unwrap_or("")));
+        for line in lines {
+            out.push_str(&format!("{}{}\n", " ".repeat(marker.len() + 1), line));
+        }
+    }
+}
+
+const BOLD: (u8, u8) = (1, 22);
+const ITALIC: (u8, u8) = (3, 23);
+const UNDERLINE: (u8, u8) = (4, 24);
+const STRIKE: (u8, u8) = (9, 29);
+
+/// The children of each tag in `nodes` named one of `names`, with the name.
+fn tags<'a>(nodes: &'a [Node], names: &'a [&str]) -> impl Iterator<Item = (&'a str, &'a [Node])> {
+    nodes.iter().filter_map(move |node| match node {
+        Node::Tag { name, children, .. } if names.contains(&name.as_str()) => Some((name.as_str(), &children[..])),
+        _ => None,
+    })
+}
+
+/// Start a new line, unless already at the start of one.
+fn block(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// The text inside `nodes`, without any markup.
+fn plain(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    Renderer { format: Format::Plain, lists: Vec::new() }.nodes(nodes, &mut out);
+    out
+}
+
+/// Escape the characters Markdown would read as markup.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '[' | ']' | '`') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,67 @@
+//! Sending requests without an async runtime of your own, for scripts
+//! and plugins. Like `reqwest::blocking`, this runs the async client on a
+//! runtime it keeps to itself, so don't call it from inside another one.
+//!
+//! ```no_run
+//! use nation::api::{Request, Shard, Target};
+//!
+//! let client = nation::blocking::Client::new()?;
+//! let request = Request {
+//!     target: Target::Nation("testlandia"),
+//!     auth: None,
+//!     shards: vec![Shard::Motto],
+//!     version: nation::API_VERSION,
+//! };
+//! let response = client.send(&request)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use serde::de::DeserializeOwned;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::api::{self, Command, Failure, Request, Response, RetryFailure, VersionError};
+use crate::{Nation, PinState};
+
+/// Blocking counterpart to `reqwest::Client` for the `api` functions.
+pub struct Client {
+    inner: reqwest::Client,
+    runtime: Runtime,
+}
+impl Client {
+    /// A client with the user agent the API rules ask for.
+    pub fn new() -> std::io::Result<Self> {
+        let inner = reqwest::Client::builder()
+            .user_agent(crate::USER_AGENT)
+            .build()
+            .map_err(std::io::Error::other)?;
+        Self::with_client(inner)
+    }
+    /// Send through an already configured client, say one with a proxy.
+    pub fn with_client(inner: reqwest::Client) -> std::io::Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self { inner, runtime })
+    }
+    /// See [`Request::send`].
+    pub fn send(&self, request: &Request) -> Result<Response, Failure> {
+        self.runtime.block_on(request.send(&self.inner))
+    }
+    /// See [`Request::send_at`].
+    pub fn send_at(&self, request: &Request, base: &str) -> Result<Response, Failure> {
+        self.runtime.block_on(request.send_at(&self.inner, base))
+    }
+    /// See [`Request::send_retry`].
+    pub fn send_retry(&self, request: &Request) -> Result<Response, RetryFailure> {
+        self.runtime.block_on(request.send_retry(&self.inner))
+    }
+    /// See [`Command::send`].
+    pub fn command<T: DeserializeOwned + Send + 'static>(&self, command: &Command) -> Result<Response<T>, RetryFailure> {
+        self.runtime.block_on(command.send(&self.inner))
+    }
+    /// See [`api::validate`].
+    pub fn validate(&self, nation: &mut Nation, version: u16) -> Result<PinState, Failure> {
+        self.runtime.block_on(api::validate(&self.inner, nation, version))
+    }
+    /// See [`api::current_version`].
+    pub fn current_version(&self) -> Result<u16, VersionError> {
+        self.runtime.block_on(api::current_version(&self.inner))
+    }
+}
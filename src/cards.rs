@@ -0,0 +1,221 @@
+//! Trading cards.
+//!
+//! Every season of cards has its own dump listing each card,
+//! published alongside the daily dumps.
+use chrono::{DateTime, Utc};
+use core::fmt;
+use core::str::FromStr;
+use serde::Deserialize;
+#[cfg(feature = "native")]
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[cfg(feature = "native")]
+use crate::dump::{self, DumpError, Fields};
+
+/// How rare a card is, from most to least common.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    UltraRare,
+    Epic,
+    Legendary,
+}
+impl Rarity {
+    pub const ALL: &'static [Rarity] = &[
+        Rarity::Common,
+        Rarity::Uncommon,
+        Rarity::Rare,
+        Rarity::UltraRare,
+        Rarity::Epic,
+        Rarity::Legendary,
+    ];
+    /// Bank a card of this rarity junks for, before any bonuses.
+    pub fn junk_value(self) -> f64 {
+        match self {
+            Rarity::Common => 0.01,
+            Rarity::Uncommon => 0.05,
+            Rarity::Rare => 0.1,
+            Rarity::UltraRare => 0.2,
+            Rarity::Epic => 0.5,
+            Rarity::Legendary => 1.0,
+        }
+    }
+}
+#[derive(Error, Debug)]
+#[error("unknown rarity {0:?}, expected common, uncommon, rare, ultra-rare, epic, or legendary")]
+pub struct ParseRarityError(String);
+impl FromStr for Rarity {
+    type Err = ParseRarityError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let wanted: String = input.chars().filter(char::is_ascii_alphabetic).collect::<String>().to_ascii_lowercase();
+        Rarity::ALL.iter().copied()
+            .find(|x| x.to_string().replace('-', "") == wanted)
+            .ok_or_else(|| ParseRarityError(input.to_owned()))
+    }
+}
+impl fmt::Display for Rarity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Rarity::Common => "common",
+            Rarity::Uncommon => "uncommon",
+            Rarity::Rare => "rare",
+            Rarity::UltraRare => "ultra-rare",
+            Rarity::Epic => "epic",
+            Rarity::Legendary => "legendary",
+        })
+    }
+}
+
+/// Where a season's card list is published.
+#[cfg(feature = "native")]
+pub fn dump_url(season: u8) -> String {
+    format!("{}/cardlist_S{}.xml.gz", dump::DUMP_BASE, season)
+}
+
+/// Download a season's card list into `dir`, returning where it was saved.
+#[cfg(feature = "native")]
+pub async fn download(client: &reqwest::Client, season: u8, dir: &Path) -> Result<PathBuf, DumpError> {
+    let path = dir.join(format!("cardlist_S{}.xml.gz", season));
+    dump::download_to(client, &dump_url(season), &path).await?;
+    Ok(path)
+}
+
+/// Open a saved card list.
+#[cfg(feature = "native")]
+pub fn open(path: &Path) -> Result<impl Iterator<Item = Result<Fields, DumpError>>, DumpError> {
+    dump::open_gzipped(path, b"NATION")
+}
+
+/// A single card, as listed in a card dump.
+#[derive(Debug, Clone)]
+pub struct Card {
+    /// Same as the ID of the nation on the card.
+    pub id: u64,
+    pub season: u8,
+    pub name: String,
+    pub region: String,
+    /// `None` for rarities this version doesn't know about.
+    pub rarity: Option<Rarity>,
+}
+#[cfg(feature = "native")]
+impl Card {
+    pub fn from_fields(mut fields: Fields, season: u8) -> Self {
+        Self {
+            id: fields.get("ID").and_then(|x| x.trim().parse().ok()).unwrap_or_default(),
+            season,
+            rarity: fields.get("CARDCATEGORY").and_then(|x| x.parse().ok()),
+            name: fields.remove("NAME").unwrap_or_default(),
+            region: fields.remove("REGION").unwrap_or_default(),
+        }
+    }
+}
+
+/// A card in a nation's deck.
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeckCard {
+    #[serde(rename = "CARDID")]
+    pub id: u64,
+    #[serde(rename = "SEASON")]
+    pub season: u8,
+    /// Rarity as the API spells it.
+    #[serde(rename = "CATEGORY")]
+    pub category: String,
+    #[serde(rename = "MARKET_VALUE", deserialize_with = "crate::api::optional_number", default)]
+    pub market_value: Option<f64>,
+}
+impl DeckCard {
+    pub fn rarity(&self) -> Option<Rarity> {
+        self.category.parse().ok()
+    }
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Deck {
+    #[serde(rename = "CARD", default)]
+    pub cards: Vec<DeckCard>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Trade {
+    #[serde(rename = "BUYER")]
+    pub buyer: String,
+    #[serde(rename = "SELLER")]
+    pub seller: String,
+    /// Empty for cards given away rather than sold.
+    #[serde(rename = "PRICE", deserialize_with = "crate::api::optional_number", default)]
+    pub price: Option<f64>,
+    #[serde(rename = "TIMESTAMP", with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Trades {
+    #[serde(rename = "TRADE", default)]
+    pub inner: Vec<Trade>,
+}
+impl Trades {
+    /// Price of the most recent sale, ignoring gifts.
+    pub fn last_price(&self) -> Option<f64> {
+        self.inner.iter()
+            .filter(|x| x.price.is_some())
+            .max_by_key(|x| x.timestamp)
+            .and_then(|x| x.price)
+    }
+}
+
+/// Whether an open offer is to buy or to sell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+#[derive(Error, Debug)]
+#[error("unknown market type {0:?}")]
+pub struct ParseSideError(String);
+impl FromStr for Side {
+    type Err = ParseSideError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim() {
+            "bid" => Ok(Side::Bid),
+            "ask" => Ok(Side::Ask),
+            _ => Err(ParseSideError(input.to_owned())),
+        }
+    }
+}
+/// An open bid or ask on a card.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Offer {
+    #[serde(rename = "NATION")]
+    pub nation: String,
+    #[serde(rename = "PRICE")]
+    pub price: f64,
+    #[serde(rename = "TIMESTAMP", with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+    #[serde(rename = "TYPE", deserialize_with = "crate::api::from_text")]
+    pub side: Side,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Markets {
+    #[serde(rename = "MARKET", default)]
+    pub inner: Vec<Offer>,
+}
+impl Markets {
+    /// The cheapest ask, if anyone is selling.
+    pub fn lowest_ask(&self) -> Option<&Offer> {
+        self.inner.iter()
+            .filter(|x| x.side == Side::Ask)
+            .min_by(|a, b| a.price.partial_cmp(&b.price).unwrap_or(core::cmp::Ordering::Equal))
+    }
+    /// The highest bid, if anyone is buying.
+    pub fn highest_bid(&self) -> Option<&Offer> {
+        self.inner.iter()
+            .filter(|x| x.side == Side::Bid)
+            .max_by(|a, b| a.price.partial_cmp(&b.price).unwrap_or(core::cmp::Ordering::Equal))
+    }
+}
+
+/// Page on the site where a card can be bought and sold.
+pub fn card_page(id: u64, season: u8) -> String {
+    format!("https://www.nationstates.net/page=deck/card={}/season={}", id, season)
+}
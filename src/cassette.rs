@@ -0,0 +1,161 @@
+//! Recording API responses to disk and playing them back.
+//!
+//! A cassette is a JSON file of requests and the responses they got.
+//! Recording one while reproducing a parsing problem lets it be replayed
+//! later, by anyone, without the credentials or the live API.
+//! Credentials never make it into a cassette: request headers aren't kept,
+//! and pins, autologins, cookies, and telegram keys are stripped.
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// Response headers that hand out credentials.
+const SECRET_HEADERS: &[&str] = &["x-pin", "x-autologin", "set-cookie"];
+/// Query parameters that carry credentials.
+const SECRET_PARAMETERS: &[&str] = &["client", "key"];
+const REDACTED: &str = "REDACTED";
+
+/// One request and the response it got.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    /// Request URL, with secrets redacted.
+    pub url: String,
+    pub status: u16,
+    /// Response headers by lowercase name, without secrets.
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+impl Interaction {
+    /// `headers` are by lowercase name, like [`crate::http::HttpResponse::headers`].
+    pub fn new(url: &str, status: u16, headers: &BTreeMap<String, String>, body: String) -> Self {
+        Self {
+            url: redact(url),
+            status,
+            headers: headers.iter()
+                .filter(|(name, _)| !SECRET_HEADERS.contains(&name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            body,
+        }
+    }
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+/// A URL with the values of secret query parameters replaced.
+pub fn redact(url: &str) -> String {
+    let mut parsed = match reqwest::Url::parse(url) {
+        Ok(x) => x,
+        Err(_) => return url.to_owned(),
+    };
+    let pairs: Vec<(String, String)> = parsed.query_pairs()
+        .map(|(key, value)| {
+            let value = if SECRET_PARAMETERS.contains(&key.as_ref()) { REDACTED.into() } else { value.into_owned() };
+            (key.into_owned(), value)
+        })
+        .collect();
+    if !pairs.is_empty() {
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    parsed.into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Send requests as usual, saving every response.
+    Record,
+    /// Answer requests from the cassette without touching the network.
+    Replay,
+}
+
+#[derive(Error, Debug)]
+pub enum CassetteError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Tape {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    tape: Tape,
+    /// Replayed interactions, so repeated requests play back in order.
+    played: Vec<bool>,
+}
+impl Cassette {
+    /// Start recording to `path`, replacing anything already there.
+    pub fn record(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            mode: Mode::Record,
+            tape: Tape::default(),
+            played: Vec::new(),
+        }
+    }
+    /// Load a recorded cassette for playback.
+    pub fn replay(path: &Path) -> Result<Self, CassetteError> {
+        let tape: Tape = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(Self {
+            path: path.to_owned(),
+            mode: Mode::Replay,
+            played: vec![false; tape.interactions.len()],
+            tape,
+        })
+    }
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+    fn next(&mut self, url: &str) -> Option<Interaction> {
+        let url = redact(url);
+        let (i, interaction) = self.tape.interactions.iter().enumerate()
+            .find(|(i, x)| !self.played[*i] && x.url == url)?;
+        self.played[i] = true;
+        Some(interaction.clone())
+    }
+    fn push(&mut self, interaction: Interaction) -> Result<(), CassetteError> {
+        self.tape.interactions.push(interaction);
+        // Saved after every request, so a crash still leaves a usable cassette.
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.tape)?)?;
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref CASSETTE: Mutex<Option<Cassette>> = Mutex::new(None);
+}
+
+/// Use a cassette for every request from now on.
+pub fn insert(cassette: Cassette) {
+    *CASSETTE.lock().unwrap() = Some(cassette);
+}
+/// Stop using a cassette, returning it.
+pub fn eject() -> Option<Cassette> {
+    CASSETTE.lock().unwrap().take()
+}
+pub(crate) fn mode() -> Option<Mode> {
+    CASSETTE.lock().unwrap().as_ref().map(Cassette::mode)
+}
+/// The next unplayed response recorded for `url`.
+pub(crate) fn play(url: &str) -> Option<Interaction> {
+    CASSETTE.lock().unwrap().as_mut()?.next(url)
+}
+pub(crate) fn record(interaction: Interaction) -> Result<(), CassetteError> {
+    match CASSETTE.lock().unwrap().as_mut() {
+        Some(cassette) => cassette.push(interaction),
+        None => Ok(()),
+    }
+}
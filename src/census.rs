@@ -0,0 +1,79 @@
+//! Census scales.
+//!
+//! The `CensusScale` enum itself is generated by `build.rs`
+//! from `data/census.xml`, so adding a scale doesn't involve
+//! touching any Rust code.
+use core::fmt;
+use core::str::FromStr;
+use serde::Deserialize;
+use thiserror::Error;
+
+include!(concat!(env!("OUT_DIR"), "/census.rs"));
+
+impl CensusScale {
+    /// Numeric ID used by the API.
+    pub fn id(self) -> u16 {
+        self as u16
+    }
+}
+
+/// Squash a scale name down to lowercase alphanumerics,
+/// so "Civil Rights", "civil-rights", and "civilrights" all match.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[derive(Error, Debug)]
+pub enum ParseScaleError {
+    #[error("no census scale with ID {0}")]
+    UnknownId(u16),
+    #[error("no census scale named {0:?}")]
+    UnknownName(String),
+}
+
+impl FromStr for CensusScale {
+    type Err = ParseScaleError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        if let Ok(id) = input.parse::<u16>() {
+            return Self::from_id(id).ok_or(ParseScaleError::UnknownId(id));
+        }
+        let wanted = normalize(input);
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|scale| normalize(scale.name()) == wanted)
+            .ok_or_else(|| ParseScaleError::UnknownName(input.to_owned()))
+    }
+}
+
+impl fmt::Display for CensusScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A nation's place in a census ranking.
+#[derive(Debug, Deserialize)]
+pub struct Rank {
+    #[serde(rename = "NAME")]
+    pub name: String,
+    #[serde(rename = "RANK")]
+    pub rank: u32,
+    #[serde(rename = "SCORE")]
+    pub score: f64,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct RankedNations {
+    #[serde(rename = "NATION", default)]
+    pub inner: Vec<Rank>,
+}
+/// A page of the `censusranks` shard, best first.
+#[derive(Debug, Deserialize, Default)]
+pub struct Ranks {
+    #[serde(rename = "NATIONS", default)]
+    pub nations: RankedNations,
+}
@@ -0,0 +1,120 @@
+//! User configuration, kept in a TOML file next to the profile.
+//!
+//! Unlike the profile, the config is mostly written by hand,
+//! so everything in it is optional. `nation init` starts one off.
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::issues::Policy;
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Issue answering policies by nation name,
+    /// with `default` applying to nations without their own.
+    pub policies: HashMap<String, Policy>,
+    /// API version to request instead of [`crate::API_VERSION`].
+    pub api_version: Option<u16>,
+    /// URL to send API requests to instead of [`crate::API_BASE`],
+    /// like a caching proxy or a test server.
+    pub api_base: Option<String>,
+    /// Proxy for every request, like `http://127.0.0.1:8118`.
+    /// Without one, the usual `HTTPS_PROXY` and `HTTP_PROXY` variables are respected.
+    pub proxy: Option<String>,
+    /// PEM file of extra root certificates to trust,
+    /// for proxies that intercept TLS.
+    pub ca_bundle: Option<PathBuf>,
+    /// Seconds to wait for a connection before giving up.
+    pub connect_timeout: Option<u64>,
+    /// Seconds a whole request may take, response included.
+    /// Unset by default, since data dumps take a while to download.
+    pub timeout: Option<u64>,
+    /// Don't use or keep pins for any nation, so nations also played in
+    /// a browser aren't logged out there. Costs a full login per request.
+    pub pin_friendly: bool,
+    /// Refuse to send anything that would change a nation, whichever it is.
+    /// Nations can be made read-only on their own in the profile instead.
+    pub read_only: bool,
+    /// Milliseconds to wait between requests at the least, on top of the
+    /// API's own limit, to leave room for other tools on the same address.
+    pub api_delay: Option<u64>,
+    /// How to reach whoever runs this tool, like a main nation or an email.
+    /// Sent along with the user agent, as the API rules ask.
+    pub contact: Option<String>,
+    /// Where to log every request that changes something,
+    /// instead of `audit.jsonl` next to the profile.
+    pub audit_log: Option<PathBuf>,
+    /// Name of whoever runs the tool, for profiles shared between people.
+    /// It's sent in the user agent, requests are logged under it instead of
+    /// the user running the tool, and nations it changes in the profile are
+    /// marked as changed by it.
+    pub operator: Option<String>,
+    /// Profile to use when a command isn't given one.
+    pub profile: Option<PathBuf>,
+    /// Commands to run one after another with `nation run`, by name. Each is
+    /// written as it would be after `nation`, like
+    /// `morning = ["ping --group main", "issues auto --group main"]`.
+    pub aliases: HashMap<String, Vec<String>>,
+    /// Shell commands to run around a command, by its name as typed,
+    /// like `ping` or `issues auto`.
+    pub hooks: HashMap<String, Hook>,
+}
+
+/// What to run around one command.
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Hook {
+    /// Runs first. If it fails, the command doesn't run.
+    pub pre: Option<String>,
+    /// Runs once the command is done, whether or not it worked,
+    /// with a JSON summary of how it went on standard input.
+    pub post: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("{0}")]
+    TomlWrite(#[from] toml::ser::Error),
+}
+
+impl Config {
+    /// Load the config, or the defaults if there isn't one.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(toml::from_str(&text)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+    /// Set `values` in the config file at `path`, keeping everything else in it.
+    /// Comments don't survive, since the file is rewritten from what was parsed.
+    pub fn update(path: &Path, values: Vec<(&str, toml::Value)>) -> Result<(), ConfigError> {
+        let mut table: toml::value::Table = match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => return Err(e.into()),
+        };
+        for (key, value) in values {
+            table.insert(key.to_owned(), value);
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string(&table)?)?;
+        Ok(())
+    }
+    /// Issue answering policy for a nation, if any.
+    pub fn policy(&self, nation: &str) -> Option<&Policy> {
+        let nation = crate::canonical_name(nation);
+        self.policies.iter()
+            .find(|(name, _)| crate::canonical_name(name) == nation)
+            .map(|(_, policy)| policy)
+            .or_else(|| self.policies.get("default"))
+    }
+}
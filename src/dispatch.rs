@@ -0,0 +1,42 @@
+//! Dispatches, the long-form posts nations publish: factbooks, bulletins,
+//! accounts, and meta posts.
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// A dispatch, from a nation's `dispatchlist` or the world `dispatch` shard.
+/// Only the latter has the text.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Dispatch {
+    pub id: u64,
+    #[serde(rename = "TITLE")]
+    pub title: String,
+    /// Nation that wrote it.
+    #[serde(rename = "AUTHOR")]
+    pub author: String,
+    /// Like "Factbook".
+    #[serde(rename = "CATEGORY")]
+    pub category: String,
+    /// Like "Overview".
+    #[serde(rename = "SUBCATEGORY")]
+    pub subcategory: String,
+    #[serde(rename = "CREATED", with = "chrono::serde::ts_seconds")]
+    pub created: DateTime<Utc>,
+    /// Last edit, if it has been edited.
+    #[serde(rename = "EDITED", default, deserialize_with = "crate::api::optional_timestamp")]
+    pub edited: Option<DateTime<Utc>>,
+    #[serde(rename = "VIEWS", default)]
+    pub views: u64,
+    /// Upvotes.
+    #[serde(rename = "SCORE", default)]
+    pub score: i64,
+    /// Body, in BBCode.
+    #[serde(rename = "TEXT", default)]
+    pub text: String,
+}
+
+/// A nation's dispatches.
+#[derive(Debug, Deserialize, Default)]
+pub struct DispatchList {
+    #[serde(rename = "DISPATCH", default)]
+    pub inner: Vec<Dispatch>,
+}
@@ -0,0 +1,326 @@
+//! Daily data dumps.
+//!
+//! Once a day the site publishes every nation and region as a gzipped XML file.
+//! The nations dump runs to hundreds of megabytes uncompressed,
+//! so dumps are read as a stream of records rather than deserialized whole.
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use core::fmt;
+use core::str::FromStr;
+use flate2::read::GzDecoder;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Where the current dumps are published.
+pub const DUMP_BASE: &str = "https://www.nationstates.net/pages";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Nations,
+    Regions,
+}
+impl Kind {
+    pub fn url(self) -> String {
+        format!("{}/{}.xml.gz", DUMP_BASE, self)
+    }
+    /// Name a dump is saved under, e.g. `2020-11-02-regions.xml.gz`.
+    pub fn file_name(self, day: NaiveDate) -> String {
+        format!("{}-{}.xml.gz", day, self)
+    }
+    /// Element each record in the dump is wrapped in.
+    pub(crate) fn record_tag(self) -> &'static [u8] {
+        match self {
+            Kind::Nations => b"NATION",
+            Kind::Regions => b"REGION",
+        }
+    }
+}
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Nations => "nations",
+            Kind::Regions => "regions",
+        })
+    }
+}
+#[derive(Error, Debug)]
+#[error("unknown dump {0:?}, expected nations or regions")]
+pub struct ParseKindError(String);
+impl FromStr for Kind {
+    type Err = ParseKindError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "nations" | "nation" => Ok(Kind::Nations),
+            "regions" | "region" => Ok(Kind::Regions),
+            _ => Err(ParseKindError(input.to_owned())),
+        }
+    }
+}
+
+/// Day a saved dump is from, going by its file name.
+pub fn day_of(path: &Path) -> Option<NaiveDate> {
+    let name = path.file_name()?.to_str()?;
+    NaiveDate::parse_from_str(name.get(..10)?, "%Y-%m-%d").ok()
+}
+
+#[derive(Error, Debug)]
+pub enum DumpError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("{0}")]
+    Request(#[from] reqwest::Error),
+    #[error("server responded with {0}")]
+    Status(StatusCode),
+}
+
+/// Where a downloaded dump ended up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fetched {
+    /// A new dump was saved here.
+    New(PathBuf),
+    /// The dump hasn't changed since it was last saved here.
+    Unchanged(PathBuf),
+}
+impl Fetched {
+    pub fn path(&self) -> &Path {
+        match self {
+            Fetched::New(x) | Fetched::Unchanged(x) => x,
+        }
+    }
+}
+
+/// File next to the dumps remembering when the last one was published,
+/// holding the `Last-Modified` header and the file it came with, one per line.
+fn modified_path(dir: &Path, kind: Kind) -> PathBuf {
+    dir.join(format!("{}.last-modified", kind))
+}
+
+/// Download today's dump into `dir`, unless the last one saved there is still current.
+pub async fn download(client: &reqwest::Client, kind: Kind, dir: &Path) -> Result<Fetched, DumpError> {
+    let modified = modified_path(dir, kind);
+    // Only worth asking if the file it refers to is still around.
+    let previous = std::fs::read_to_string(&modified).ok().and_then(|text| {
+        let mut lines = text.lines();
+        let since = lines.next()?.to_owned();
+        let path = dir.join(lines.next()?);
+        if path.exists() {
+            Some((since, path))
+        } else {
+            None
+        }
+    });
+    let path = dir.join(kind.file_name(Utc::now().naive_utc().date()));
+    let since = previous.as_ref().map(|(since, _)| since.as_str());
+    match fetch_to(client, &kind.url(), &path, since).await? {
+        Saved::NotModified => Ok(Fetched::Unchanged(previous.expect("only asked with a previous dump").1)),
+        Saved::Saved { last_modified } => {
+            match (last_modified, path.file_name().and_then(|x| x.to_str())) {
+                (Some(last_modified), Some(name)) => std::fs::write(&modified, format!("{}\n{}\n", last_modified, name))?,
+                // Without a date to ask with, the next download is unconditional.
+                _ => {
+                    let _ = std::fs::remove_file(&modified);
+                },
+            }
+            Ok(Fetched::New(path))
+        },
+    }
+}
+
+enum Saved {
+    Saved { last_modified: Option<String> },
+    NotModified,
+}
+
+/// Download a file to `path`, streaming it rather than holding it all in memory.
+pub async fn download_to(client: &reqwest::Client, url: &str, path: &Path) -> Result<(), DumpError> {
+    fetch_to(client, url, path, None).await.map(drop)
+}
+
+/// Download a file to `path`, unless it hasn't been modified since `since`, an HTTP date.
+async fn fetch_to(client: &reqwest::Client, url: &str, path: &Path, since: Option<&str>) -> Result<Saved, DumpError> {
+    use reqwest::header::{ACCEPT_ENCODING, IF_MODIFIED_SINCE, LAST_MODIFIED};
+    use tokio_compat_02::FutureExt;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Dumps are already gzipped, and asking for them compressed again
+    // could get them decompressed on the way in.
+    let mut request = client.get(url).header(ACCEPT_ENCODING, "identity");
+    if let Some(since) = since {
+        request = request.header(IF_MODIFIED_SINCE, since);
+    }
+    let mut response = request.send().compat().await?;
+    if since.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Saved::NotModified);
+    }
+    if response.status() != StatusCode::OK {
+        return Err(DumpError::Status(response.status()));
+    }
+    let last_modified = response.headers().get(LAST_MODIFIED)
+        .and_then(|x| x.to_str().ok())
+        .map(str::to_owned);
+    // Written under a temporary name so an interrupted download
+    // never looks like a complete dump.
+    let partial = path.with_extension("part");
+    let mut file = File::create(&partial)?;
+    while let Some(chunk) = response.chunk().compat().await? {
+        file.write_all(&chunk)?;
+    }
+    drop(file);
+    std::fs::rename(&partial, path)?;
+    Ok(Saved::Saved { last_modified })
+}
+
+/// The direct children of one record, by element name.
+/// Nested structure like officers and embassies is skipped.
+pub type Fields = HashMap<String, String>;
+
+/// Iterator over the records of a dump,
+/// which are the outermost elements named `tag`, however deep they're nested.
+pub struct Records<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    tag: &'static [u8],
+    depth: usize,
+}
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R, tag: &'static [u8]) -> Self {
+        let mut reader = Reader::from_reader(reader);
+        reader.trim_text(true);
+        Self {
+            reader,
+            buf: Vec::new(),
+            tag,
+            depth: 0,
+        }
+    }
+}
+/// Open a saved, still gzipped, dump.
+pub fn open(path: &Path, kind: Kind) -> Result<Records<BufReader<GzDecoder<File>>>, DumpError> {
+    open_gzipped(path, kind.record_tag())
+}
+pub(crate) fn open_gzipped(path: &Path, tag: &'static [u8]) -> Result<Records<BufReader<GzDecoder<File>>>, DumpError> {
+    let file = File::open(path)?;
+    Ok(Records::new(BufReader::new(GzDecoder::new(file)), tag))
+}
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = Result<Fields, DumpError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        // Depth of the record element once we're inside one.
+        // Its fields are one level below that.
+        let mut record: Option<(usize, Fields)> = None;
+        let mut field: Option<String> = None;
+        loop {
+            self.buf.clear();
+            let text = match self.reader.read_event(&mut self.buf) {
+                Ok(Event::Start(e)) => {
+                    self.depth += 1;
+                    match &record {
+                        None if e.name() == self.tag => record = Some((self.depth, Fields::new())),
+                        Some((depth, _)) if self.depth == depth + 1 => {
+                            field = Some(String::from_utf8_lossy(e.name()).into_owned());
+                        },
+                        _ => (),
+                    }
+                    continue;
+                },
+                Ok(Event::Empty(e)) => {
+                    if let Some((depth, fields)) = &mut record {
+                        if self.depth == *depth {
+                            fields.insert(String::from_utf8_lossy(e.name()).into_owned(), String::new());
+                        }
+                    }
+                    continue;
+                },
+                Ok(Event::End(_)) => {
+                    self.depth -= 1;
+                    match &mut record {
+                        Some((depth, _)) if self.depth + 1 == *depth => return record.map(|(_, x)| Ok(x)),
+                        Some((depth, fields)) if self.depth == *depth => {
+                            // Make sure fields with no text still show up.
+                            if let Some(name) = field.take() {
+                                fields.entry(name).or_default();
+                            }
+                        },
+                        _ => (),
+                    }
+                    continue;
+                },
+                Ok(Event::Text(e)) => match e.unescape_and_decode(&self.reader) {
+                    Ok(text) => text,
+                    Err(e) => return Some(Err(e.into())),
+                },
+                Ok(Event::CData(e)) => String::from_utf8_lossy(&e).into_owned(),
+                Ok(Event::Eof) => return None,
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e.into())),
+            };
+            // Only text directly inside a field counts, not text nested further down.
+            if let (Some((depth, fields)), Some(name)) = (&mut record, &field) {
+                if self.depth == *depth + 1 {
+                    fields.entry(name.clone()).or_default().push_str(&text);
+                }
+            }
+        }
+    }
+}
+
+fn number<T: FromStr + Default>(fields: &Fields, name: &str) -> T {
+    fields.get(name).and_then(|x| x.trim().parse().ok()).unwrap_or_default()
+}
+
+/// The parts of a region in the regions dump worth indexing.
+#[derive(Debug, Clone)]
+pub struct RegionRecord {
+    pub name: String,
+    pub nations: u32,
+    pub delegate: Option<String>,
+    pub delegate_votes: u32,
+    pub founder: Option<String>,
+    pub last_update: Option<DateTime<Utc>>,
+}
+impl From<Fields> for RegionRecord {
+    fn from(mut fields: Fields) -> Self {
+        let officeholder = |x: Option<String>| x.and_then(crate::region::officeholder);
+        Self {
+            nations: number(&fields, "NUMNATIONS"),
+            delegate_votes: number(&fields, "DELEGATEVOTES"),
+            last_update: fields.get("LASTUPDATE")
+                .and_then(|x| x.trim().parse().ok())
+                .and_then(|x| Utc.timestamp_opt(x, 0).single()),
+            delegate: officeholder(fields.remove("DELEGATE")),
+            founder: officeholder(fields.remove("FOUNDER")),
+            name: fields.remove("NAME").unwrap_or_default(),
+        }
+    }
+}
+
+/// The parts of a nation in the nations dump worth indexing.
+#[derive(Debug, Clone)]
+pub struct NationRecord {
+    pub name: String,
+    pub region: String,
+    pub wa_member: bool,
+    pub endorsements: u32,
+    /// In millions.
+    pub population: u64,
+}
+impl From<Fields> for NationRecord {
+    fn from(mut fields: Fields) -> Self {
+        Self {
+            wa_member: matches!(fields.get("UNSTATUS").map(String::as_str), Some(x) if x != "Non-member"),
+            endorsements: fields.get("ENDORSEMENTS")
+                .map_or(0, |x| x.split(',').filter(|x| !x.is_empty()).count() as u32),
+            population: number(&fields, "POPULATION"),
+            region: fields.remove("REGION").unwrap_or_default(),
+            name: fields.remove("NAME").unwrap_or_default(),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Economy shards, and summing them up over many nations.
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::api::{ResolvedShard, Shard};
+
+/// Shares of the economy, in percent, from the `sectors` shard.
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+pub struct Sectors {
+    #[serde(rename = "BLACKMARKET")]
+    pub black_market: f64,
+    #[serde(rename = "GOVERNMENT")]
+    pub government: f64,
+    #[serde(rename = "INDUSTRY")]
+    pub industry: f64,
+    #[serde(rename = "PUBLIC")]
+    pub public: f64,
+}
+
+/// The shards an [`Economy`] is made of.
+pub fn shards() -> Vec<Shard> {
+    vec![
+        Shard::Gdp,
+        Shard::Income,
+        Shard::Richest,
+        Shard::Poorest,
+        Shard::Tax,
+        Shard::MajorIndustry,
+        Shard::Sectors,
+    ]
+}
+
+/// A nation's economy. Amounts are in the nation's own currency.
+#[derive(Debug, Clone, Default)]
+pub struct Economy {
+    pub gdp: u64,
+    /// Average income.
+    pub income: u64,
+    /// Average income of the richest 10%.
+    pub richest: u64,
+    /// Average income of the poorest 10%.
+    pub poorest: u64,
+    /// Average income tax rate, in percent.
+    pub tax: f64,
+    pub major_industry: String,
+    pub sectors: Sectors,
+}
+impl Economy {
+    /// Pick the economy shards out of a response, ignoring anything else.
+    pub fn from_shards(shards: impl IntoIterator<Item = ResolvedShard>) -> Self {
+        let mut economy = Self::default();
+        for shard in shards {
+            match shard {
+                ResolvedShard::Gdp(x) => economy.gdp = x,
+                ResolvedShard::Income(x) => economy.income = x,
+                ResolvedShard::Richest(x) => economy.richest = x,
+                ResolvedShard::Poorest(x) => economy.poorest = x,
+                ResolvedShard::Tax(x) => economy.tax = x,
+                ResolvedShard::MajorIndustry(x) => economy.major_industry = x,
+                ResolvedShard::Sectors(x) => economy.sectors = x,
+                _ => (),
+            }
+        }
+        economy
+    }
+}
+
+/// Economies of many nations, summed up.
+/// Nations use their own currencies, so the totals are only a rough guide.
+#[derive(Debug, Default)]
+pub struct Aggregate {
+    pub nations: usize,
+    pub gdp: u64,
+    income: u64,
+    tax: f64,
+    sectors: Sectors,
+    /// Highest richest-10% income of any nation.
+    pub richest: u64,
+    /// Lowest poorest-10% income of any nation.
+    pub poorest: Option<u64>,
+    /// Number of nations with each major industry.
+    pub industries: BTreeMap<String, usize>,
+}
+impl Aggregate {
+    pub fn add(&mut self, economy: &Economy) {
+        self.nations += 1;
+        self.gdp = self.gdp.saturating_add(economy.gdp);
+        self.income = self.income.saturating_add(economy.income);
+        self.tax += economy.tax;
+        self.sectors.black_market += economy.sectors.black_market;
+        self.sectors.government += economy.sectors.government;
+        self.sectors.industry += economy.sectors.industry;
+        self.sectors.public += economy.sectors.public;
+        self.richest = self.richest.max(economy.richest);
+        self.poorest = Some(self.poorest.map_or(economy.poorest, |x| x.min(economy.poorest)));
+        *self.industries.entry(economy.major_industry.clone()).or_default() += 1;
+    }
+    /// Averages over every nation added, or `None` if there weren't any.
+    /// `gdp` is the total rather than the average.
+    pub fn summary(&self) -> Option<Economy> {
+        if self.nations == 0 {
+            return None;
+        }
+        let n = self.nations as f64;
+        Some(Economy {
+            gdp: self.gdp,
+            income: self.income / self.nations as u64,
+            richest: self.richest,
+            poorest: self.poorest.unwrap_or_default(),
+            tax: self.tax / n,
+            major_industry: self.industries.iter()
+                .max_by_key(|(_, &count)| count)
+                .map(|(name, _)| name.clone())
+                .unwrap_or_default(),
+            sectors: Sectors {
+                black_market: self.sectors.black_market / n,
+                government: self.sectors.government / n,
+                industry: self.sectors.industry / n,
+                public: self.sectors.public / n,
+            },
+        })
+    }
+}
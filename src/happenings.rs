@@ -0,0 +1,214 @@
+//! Happenings, parsed into typed events.
+//!
+//! The API only gives us a line of marked up text per event,
+//! like `@@testlandia@@ relocated from %%a%% to %%b%%.`,
+//! so the structure is recovered with regexes.
+//! Anything not recognized is kept as `EventKind::Other`.
+use chrono::{DateTime, Utc};
+use core::fmt;
+use core::str::FromStr;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Broad kind of event, used for filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Move,
+    Founding,
+    Cte,
+    Endo,
+    Delegate,
+    Rmb,
+    /// Changes to nations, like influence rising or falling at update.
+    Change,
+    Other,
+}
+impl Category {
+    /// Name of the matching `filter` value on the API side, if there is one.
+    pub fn api_filter(self) -> Option<&'static str> {
+        match self {
+            Category::Move => Some("move"),
+            Category::Founding => Some("founding"),
+            Category::Cte => Some("cte"),
+            Category::Endo => Some("endo"),
+            Category::Delegate => Some("member"),
+            Category::Rmb => Some("rmb"),
+            Category::Change => Some("change"),
+            Category::Other => None,
+        }
+    }
+}
+#[derive(Error, Debug)]
+#[error("unknown event category {0:?}, expected one of move, founding, cte, endo, delegate, rmb, change")]
+pub struct ParseCategoryError(String);
+impl FromStr for Category {
+    type Err = ParseCategoryError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "move" => Ok(Category::Move),
+            "founding" | "found" => Ok(Category::Founding),
+            "cte" => Ok(Category::Cte),
+            "endo" | "endorsement" => Ok(Category::Endo),
+            "delegate" => Ok(Category::Delegate),
+            "rmb" => Ok(Category::Rmb),
+            "change" | "influence" => Ok(Category::Change),
+            "other" => Ok(Category::Other),
+            _ => Err(ParseCategoryError(input.to_owned())),
+        }
+    }
+}
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Category::Move => "move",
+            Category::Founding => "founding",
+            Category::Cte => "cte",
+            Category::Endo => "endo",
+            Category::Delegate => "delegate",
+            Category::Rmb => "rmb",
+            Category::Change => "change",
+            Category::Other => "other",
+        })
+    }
+}
+
+/// What happened, with the nations and regions involved.
+/// Names are as the API sends them, in lowercase with underscores.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    Move { nation: String, from: String, to: String },
+    Founding { nation: String, region: String, refounded: bool },
+    Cte { nation: String, region: String },
+    Endorsed { nation: String, target: String },
+    EndorsementWithdrawn { nation: String, target: String },
+    /// A nation became delegate, possibly taking the seat from another.
+    DelegateGained { nation: String, region: String, previous: Option<String> },
+    DelegateLost { nation: String, region: String },
+    RmbPost { nation: String, region: String, post: Option<u64> },
+    /// A nation's influence in its region changed, which happens as the region updates.
+    InfluenceChanged { nation: String, region: String },
+    Other,
+}
+
+lazy_static! {
+    static ref MOVE: Regex = Regex::new(r"^@@([^@]+)@@ relocated from %%([^%]+)%% to %%([^%]+)%%").unwrap();
+    static ref FOUNDING: Regex = Regex::new(r"^@@([^@]+)@@ was (re)?founded in %%([^%]+)%%").unwrap();
+    static ref CTE: Regex = Regex::new(r"^@@([^@]+)@@ ceased to exist in %%([^%]+)%%").unwrap();
+    static ref ENDORSED: Regex = Regex::new(r"^@@([^@]+)@@ endorsed @@([^@]+)@@").unwrap();
+    static ref WITHDREW: Regex = Regex::new(r"^@@([^@]+)@@ withdrew its endorsement from @@([^@]+)@@").unwrap();
+    static ref DELEGATE_ELECTED: Regex = Regex::new(r"^@@([^@]+)@@ became WA Delegate of %%([^%]+)%%").unwrap();
+    static ref DELEGATE_SEIZED: Regex = Regex::new(r"^@@([^@]+)@@ seized the position of %%([^%]+)%% WA Delegate from @@([^@]+)@@").unwrap();
+    static ref DELEGATE_LOST: Regex = Regex::new(r"^@@([^@]+)@@ lost WA Delegate status in %%([^%]+)%%").unwrap();
+    static ref RMB: Regex = Regex::new(r#"^@@([^@]+)@@ lodged <a href="[^"]*?postid=(\d+)[^"]*"[^>]*>a message</a> on the %%([^%]+)%% Regional Message Board"#).unwrap();
+    static ref INFLUENCE: Regex = Regex::new(r"^@@([^@]+)@@'s influence in %%([^%]+)%% (rose|fell) from").unwrap();
+    static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+    static ref MARKER: Regex = Regex::new(r"@@([^@]+)@@|%%([^%]+)%%").unwrap();
+}
+
+impl EventKind {
+    /// Recover the structure of a happenings line.
+    pub fn parse(text: &str) -> Self {
+        let owned = |caps: &regex::Captures, i: usize| caps[i].to_owned();
+        if let Some(c) = MOVE.captures(text) {
+            EventKind::Move { nation: owned(&c, 1), from: owned(&c, 2), to: owned(&c, 3) }
+        } else if let Some(c) = FOUNDING.captures(text) {
+            EventKind::Founding { nation: owned(&c, 1), region: owned(&c, 3), refounded: c.get(2).is_some() }
+        } else if let Some(c) = CTE.captures(text) {
+            EventKind::Cte { nation: owned(&c, 1), region: owned(&c, 2) }
+        } else if let Some(c) = ENDORSED.captures(text) {
+            EventKind::Endorsed { nation: owned(&c, 1), target: owned(&c, 2) }
+        } else if let Some(c) = WITHDREW.captures(text) {
+            EventKind::EndorsementWithdrawn { nation: owned(&c, 1), target: owned(&c, 2) }
+        } else if let Some(c) = DELEGATE_ELECTED.captures(text) {
+            EventKind::DelegateGained { nation: owned(&c, 1), region: owned(&c, 2), previous: None }
+        } else if let Some(c) = DELEGATE_SEIZED.captures(text) {
+            EventKind::DelegateGained { nation: owned(&c, 1), region: owned(&c, 2), previous: Some(owned(&c, 3)) }
+        } else if let Some(c) = DELEGATE_LOST.captures(text) {
+            EventKind::DelegateLost { nation: owned(&c, 1), region: owned(&c, 2) }
+        } else if let Some(c) = RMB.captures(text) {
+            EventKind::RmbPost { nation: owned(&c, 1), region: owned(&c, 3), post: c[2].parse().ok() }
+        } else if let Some(c) = INFLUENCE.captures(text) {
+            EventKind::InfluenceChanged { nation: owned(&c, 1), region: owned(&c, 2) }
+        } else {
+            EventKind::Other
+        }
+    }
+    pub fn category(&self) -> Category {
+        match self {
+            EventKind::Move { .. } => Category::Move,
+            EventKind::Founding { .. } => Category::Founding,
+            EventKind::Cte { .. } => Category::Cte,
+            EventKind::Endorsed { .. } | EventKind::EndorsementWithdrawn { .. } => Category::Endo,
+            EventKind::DelegateGained { .. } | EventKind::DelegateLost { .. } => Category::Delegate,
+            EventKind::RmbPost { .. } => Category::Rmb,
+            EventKind::InfluenceChanged { .. } => Category::Change,
+            EventKind::Other => Category::Other,
+        }
+    }
+}
+
+/// A single line of happenings.
+#[derive(Debug, Deserialize)]
+pub struct Event {
+    pub id: u64,
+    #[serde(rename = "TIMESTAMP", with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+    /// Raw text, with `@@nation@@` and `%%region%%` markers and HTML links.
+    #[serde(rename = "TEXT")]
+    pub text: String,
+}
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        EventKind::parse(&self.text)
+    }
+    /// The text with markers and HTML stripped, for display.
+    pub fn plain_text(&self) -> String {
+        let text = TAG.replace_all(&self.text, "");
+        MARKER.replace_all(&text, |caps: &regex::Captures| {
+            caps.get(1).or_else(|| caps.get(2)).unwrap().as_str().replace('_', " ")
+        }).into_owned()
+    }
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Happenings {
+    #[serde(rename = "EVENT", default)]
+    pub events: Vec<Event>,
+}
+
+/// Times `region` was seen updating: the first influence change of each
+/// burst of them, since a region's nations all update within moments.
+pub fn update_times(events: &[Event], region: &str) -> Vec<DateTime<Utc>> {
+    let region = crate::canonical_name(region);
+    let mut times: Vec<_> = events.iter()
+        .filter(|x| matches!(x.kind(), EventKind::InfluenceChanged { region: r, .. } if crate::canonical_name(&r) == region))
+        .map(|x| x.timestamp)
+        .collect();
+    times.sort();
+    let mut updates: Vec<DateTime<Utc>> = Vec::new();
+    let mut last = None;
+    for time in times {
+        // Updates are hours apart, so a gap of minutes starts a new one.
+        if last.is_none_or(|x| time - x > chrono::Duration::minutes(10)) {
+            updates.push(time);
+        }
+        last = Some(time);
+    }
+    updates
+}
+
+/// Whose happenings to ask the world shard for.
+#[derive(Debug, Clone)]
+pub enum View {
+    Nation(String),
+    Region(String),
+}
+impl View {
+    pub(crate) fn to_parameter(&self) -> String {
+        match self {
+            View::Nation(name) => format!("nation.{}", name),
+            View::Region(name) => format!("region.{}", name),
+        }
+    }
+}
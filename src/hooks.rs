@@ -0,0 +1,58 @@
+//! Shell commands run before and after a command, as set in the config,
+//! for notifications and bookkeeping the command doesn't do itself.
+//!
+//! Both see the command's name in `NATION_COMMAND`. The one run after
+//! also gets a [`Summary`] as JSON on standard input. What they print
+//! goes to standard error, out of the way of the command's own output.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::process::Stdio;
+
+use crate::notify::shell;
+
+/// How a command went, for the hook run after it.
+#[derive(Debug, Serialize)]
+pub struct Summary<'a> {
+    /// Name as typed, like `issues auto`.
+    pub command: &'a str,
+    /// Everything after `nation` on the command line, with passwords and keys redacted.
+    pub args: &'a [String],
+    pub ok: bool,
+    /// What the process exits with, or would if this were the last command.
+    pub exit_code: i32,
+    pub error: Option<String>,
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+}
+
+fn check(status: std::process::ExitStatus) -> io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("hook failed with {}", status)))
+    }
+}
+
+/// Run `hook` before `command`.
+pub fn before(hook: &str, command: &str) -> io::Result<()> {
+    check(shell(hook).env("NATION_COMMAND", command).stdout(io::stderr()).status()?)
+}
+
+/// Run `hook` after a command, with `summary` on its standard input.
+pub fn after(hook: &str, summary: &Summary) -> io::Result<()> {
+    let mut child = shell(hook)
+        .env("NATION_COMMAND", summary.command)
+        .stdin(Stdio::piped())
+        .stdout(io::stderr())
+        .spawn()?;
+    let json = serde_json::to_vec(summary)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read its input has every right not to.
+        match stdin.write_all(&json) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => (),
+        }
+    }
+    check(child.wait()?)
+}
@@ -0,0 +1,124 @@
+//! The transport API requests go out over.
+//!
+//! Everything in [`crate::api`] sends through an [`HttpClient`], which is
+//! implemented for `reqwest::Client`. Another client, or a test double,
+//! only has to turn a URL and some headers into an [`HttpResponse`].
+use std::collections::BTreeMap;
+use std::future::Future;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// A request that didn't make it, or a response that broke off partway.
+#[derive(Error, Debug)]
+#[error(transparent)]
+pub struct HttpError(Box<dyn std::error::Error + Send + Sync>);
+impl HttpError {
+    pub fn new(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self(error.into())
+    }
+    /// The underlying error, to downcast to the client's own error type.
+    pub fn get_ref(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        &*self.0
+    }
+}
+
+/// A response body, either all there or still arriving.
+#[derive(Debug)]
+pub struct Body(Chunks);
+#[derive(Debug)]
+enum Chunks {
+    Whole(Option<Vec<u8>>),
+    Arriving(mpsc::Receiver<Result<Vec<u8>, HttpError>>),
+}
+impl Body {
+    pub fn whole(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(Chunks::Whole(Some(bytes.into())))
+    }
+    /// A body that arrives over a channel, ending when the sender is dropped.
+    pub fn arriving(chunks: mpsc::Receiver<Result<Vec<u8>, HttpError>>) -> Self {
+        Self(Chunks::Arriving(chunks))
+    }
+    /// The next piece of the body, or `None` once it's all been read.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, HttpError> {
+        match &mut self.0 {
+            Chunks::Whole(bytes) => Ok(bytes.take()),
+            Chunks::Arriving(chunks) => chunks.recv().await.transpose(),
+        }
+    }
+    /// Read the rest of the body as text.
+    pub async fn text(mut self) -> Result<String, HttpError> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            bytes.extend(chunk);
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    /// Headers by lowercase name.
+    pub headers: BTreeMap<String, String>,
+    pub body: Body,
+}
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+}
+
+/// Something that can send HTTP requests.
+/// The futures have to be `Send`, so requests can go out from spawned tasks.
+pub trait HttpClient {
+    fn get<'a>(&'a self, url: &'a str, headers: &'a [(&'a str, String)])
+               -> impl Future<Output = Result<HttpResponse, HttpError>> + Send + 'a;
+    fn post<'a>(&'a self, url: &'a str, headers: &'a [(&'a str, String)], body: Vec<u8>)
+                -> impl Future<Output = Result<HttpResponse, HttpError>> + Send + 'a;
+}
+
+#[cfg(feature = "native")]
+impl HttpClient for reqwest::Client {
+    fn get<'a>(&'a self, url: &'a str, headers: &'a [(&'a str, String)])
+               -> impl Future<Output = Result<HttpResponse, HttpError>> + Send + 'a {
+        send_reqwest(self.get(url), headers)
+    }
+    fn post<'a>(&'a self, url: &'a str, headers: &'a [(&'a str, String)], body: Vec<u8>)
+                -> impl Future<Output = Result<HttpResponse, HttpError>> + Send + 'a {
+        send_reqwest(self.post(url).body(body), headers)
+    }
+}
+
+#[cfg(feature = "native")]
+async fn send_reqwest(mut request: reqwest::RequestBuilder, headers: &[(&str, String)]) -> Result<HttpResponse, HttpError> {
+    // `reqwest` is on Tokio 0.2 still. We're on Tokio 0.3.
+    use tokio_compat_02::FutureExt;
+    for (name, value) in headers {
+        request = request.header(*name, value);
+    }
+    let mut response = request.send().compat().await.map_err(HttpError::new)?;
+    let status = response.status().as_u16();
+    let headers = response.headers().iter()
+        .filter_map(|(name, value)| Some((name.as_str().to_owned(), value.to_str().ok()?.to_owned())))
+        .collect();
+    // Passed along as it arrives, so large responses can be parsed without holding them whole.
+    let (sender, chunks) = mpsc::channel(16);
+    tokio::spawn(async move {
+        loop {
+            let chunk = match response.chunk().compat().await {
+                Ok(Some(chunk)) => Ok(chunk.to_vec()),
+                Ok(None) => break,
+                Err(e) => Err(HttpError::new(e)),
+            };
+            let failed = chunk.is_err();
+            // Nobody's listening once the body's been dropped.
+            if sender.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    Ok(HttpResponse {
+        status,
+        headers,
+        body: Body::arriving(chunks),
+    })
+}
@@ -0,0 +1,597 @@
+//! Local SQLite store, mostly an index of daily dumps.
+//!
+//! Every ingested dump keeps its own rows, tagged with the day it's from,
+//! so questions about the whole world can be answered without touching the API.
+//! Things worth remembering between runs, like card prices
+//! and what the issue autopilot did, go here too.
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use itertools::Itertools;
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use thiserror::Error;
+
+use crate::cards::{self, Card, Rarity};
+use crate::dump::{self, DumpError, Kind, NationRecord, RegionRecord};
+use crate::issues::{NationalPolicies, Outcome};
+use crate::region::Residency;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS regions (
+    day TEXT NOT NULL,
+    id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    nations INTEGER NOT NULL,
+    delegate TEXT,
+    delegate_votes INTEGER NOT NULL,
+    founder TEXT,
+    last_update INTEGER,
+    PRIMARY KEY (day, id)
+);
+CREATE TABLE IF NOT EXISTS nations (
+    day TEXT NOT NULL,
+    id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    region TEXT NOT NULL,
+    wa_member INTEGER NOT NULL,
+    endorsements INTEGER NOT NULL,
+    population INTEGER NOT NULL,
+    PRIMARY KEY (day, id)
+);
+CREATE INDEX IF NOT EXISTS nations_by_region ON nations (day, region);
+CREATE TABLE IF NOT EXISTS cards (
+    season INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    nation TEXT NOT NULL,
+    region TEXT NOT NULL,
+    rarity TEXT,
+    PRIMARY KEY (season, id)
+);
+CREATE INDEX IF NOT EXISTS cards_by_nation ON cards (nation);
+CREATE TABLE IF NOT EXISTS issue_decisions (
+    nation TEXT NOT NULL,
+    issue INTEGER NOT NULL,
+    option INTEGER,
+    reason TEXT NOT NULL,
+    decided INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS issue_effects (
+    nation TEXT NOT NULL,
+    issue INTEGER NOT NULL,
+    option INTEGER NOT NULL,
+    scale INTEGER NOT NULL,
+    change REAL NOT NULL,
+    answered INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS issue_effects_by_option ON issue_effects (issue, option, scale);
+CREATE TABLE IF NOT EXISTS issue_results (
+    nation TEXT NOT NULL,
+    issue INTEGER NOT NULL,
+    title TEXT,
+    option INTEGER NOT NULL,
+    answered INTEGER NOT NULL,
+    description TEXT,
+    headlines TEXT NOT NULL,
+    reclassifications TEXT NOT NULL,
+    banners TEXT NOT NULL,
+    new_policies TEXT NOT NULL,
+    removed_policies TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS card_prices (
+    season INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    fetched INTEGER NOT NULL,
+    price REAL,
+    PRIMARY KEY (season, id)
+);
+CREATE TABLE IF NOT EXISTS residency (
+    nation TEXT PRIMARY KEY,
+    fetched INTEGER NOT NULL,
+    founded INTEGER,
+    first_login INTEGER,
+    last_login INTEGER
+);
+CREATE TABLE IF NOT EXISTS influence (
+    nation TEXT NOT NULL,
+    recorded INTEGER NOT NULL,
+    level TEXT NOT NULL,
+    score REAL NOT NULL,
+    PRIMARY KEY (nation, recorded)
+);
+CREATE TABLE IF NOT EXISTS welcomed (
+    region TEXT NOT NULL,
+    nation TEXT NOT NULL,
+    sent INTEGER NOT NULL,
+    PRIMARY KEY (region, nation)
+);
+";
+
+#[derive(Error, Debug)]
+pub enum IndexError {
+    #[error("{0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("{0}")]
+    Dump(#[from] DumpError),
+}
+
+/// Result of an arbitrary query, with every value formatted as text.
+#[derive(Debug, Default)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Canned filters over the regions table.
+#[derive(Debug, Default, Clone)]
+pub struct RegionFilter {
+    pub founderless: bool,
+    pub min_nations: Option<u32>,
+}
+
+/// An issue answered through this tool, as recorded in the store.
+#[derive(Debug, Clone)]
+pub struct AnsweredIssue {
+    pub issue: u32,
+    pub title: Option<String>,
+    pub option: i32,
+    pub answered: DateTime<Utc>,
+    pub description: Option<String>,
+    pub headlines: Vec<String>,
+    /// Like "Economy: Strong -> Very Strong".
+    pub reclassifications: Vec<String>,
+    pub banners: Vec<String>,
+    pub new_policies: Vec<String>,
+    pub removed_policies: Vec<String>,
+    /// Census scale IDs and how much they changed, biggest change first.
+    pub effects: Vec<(u16, f64)>,
+}
+
+/// Canned filters over the cards table.
+#[derive(Debug, Default, Clone)]
+pub struct CardFilter {
+    /// Part of the nation's name.
+    pub name: Option<String>,
+    pub rarity: Option<Rarity>,
+    pub season: Option<u8>,
+}
+
+/// A region whose delegate seat changed hands between two days.
+#[derive(Debug, Clone)]
+pub struct DelegateChange {
+    pub region: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+/// A region whose nation count moved by a lot between two days.
+#[derive(Debug, Clone)]
+pub struct Swing {
+    pub region: String,
+    pub before: u32,
+    pub after: u32,
+}
+/// Changes to regions between two dumps.
+#[derive(Debug, Default)]
+pub struct RegionDiff {
+    pub delegates: Vec<DelegateChange>,
+    /// Regions and the founder that stopped existing.
+    pub founder_ctes: Vec<(String, String)>,
+    pub swings: Vec<Swing>,
+}
+/// Changes to nations between two dumps.
+#[derive(Debug, Default)]
+pub struct NationDiff {
+    /// Nations and the regions they moved from and to.
+    pub moves: Vec<(String, String, String)>,
+    /// Nations that are gone, with the region they were last in.
+    pub ceased: Vec<(String, String)>,
+}
+
+pub struct Index {
+    conn: Connection,
+}
+impl Index {
+    pub fn open(path: &Path) -> Result<Self, IndexError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(DumpError::from)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+    /// Load a saved dump, replacing anything already indexed for that day.
+    /// Returns the number of records ingested.
+    pub fn ingest(&mut self, path: &Path, kind: Kind, day: NaiveDate) -> Result<usize, IndexError> {
+        let day = day.to_string();
+        let tx = self.conn.transaction()?;
+        let mut count = 0;
+        match kind {
+            Kind::Regions => {
+                tx.execute("DELETE FROM regions WHERE day = ?", params![day])?;
+                let mut insert = tx.prepare(
+                    "INSERT INTO regions (day, id, name, nations, delegate, delegate_votes, founder, last_update)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)")?;
+                for record in dump::open(path, kind)? {
+                    let region = RegionRecord::from(record?);
+                    insert.execute(params![
+                        day,
+                        crate::canonical_name(&region.name),
+                        region.name,
+                        region.nations,
+                        region.delegate,
+                        region.delegate_votes,
+                        region.founder,
+                        region.last_update.map(|x| x.timestamp()),
+                    ])?;
+                    count += 1;
+                }
+            },
+            Kind::Nations => {
+                tx.execute("DELETE FROM nations WHERE day = ?", params![day])?;
+                let mut insert = tx.prepare(
+                    "INSERT INTO nations (day, id, name, region, wa_member, endorsements, population)
+                     VALUES (?, ?, ?, ?, ?, ?, ?)")?;
+                for record in dump::open(path, kind)? {
+                    let nation = NationRecord::from(record?);
+                    insert.execute(params![
+                        day,
+                        crate::canonical_name(&nation.name),
+                        nation.name,
+                        crate::canonical_name(&nation.region),
+                        nation.wa_member,
+                        nation.endorsements,
+                        nation.population as i64,
+                    ])?;
+                    count += 1;
+                }
+            },
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+    /// Load a season's card list, replacing the cards already indexed for it.
+    pub fn ingest_cards(&mut self, path: &Path, season: u8) -> Result<usize, IndexError> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM cards WHERE season = ?", params![season])?;
+        let mut count = 0;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO cards (season, id, name, nation, region, rarity) VALUES (?, ?, ?, ?, ?, ?)")?;
+            for record in cards::open(path)? {
+                let card = Card::from_fields(record?, season);
+                insert.execute(params![
+                    card.season,
+                    card.id as i64,
+                    card.name,
+                    crate::canonical_name(&card.name),
+                    card.region,
+                    card.rarity.map(|x| x.to_string()),
+                ])?;
+                count += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+    /// Cards matching a filter, rarest first.
+    pub fn cards(&self, filter: &CardFilter) -> Result<Table, IndexError> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if let Some(name) = &filter.name {
+            conditions.push("nation LIKE ?");
+            values.push(Box::new(format!("%{}%", crate::canonical_name(name))));
+        }
+        if let Some(rarity) = filter.rarity {
+            conditions.push("rarity = ?");
+            values.push(Box::new(rarity.to_string()));
+        }
+        if let Some(season) = filter.season {
+            conditions.push("season = ?");
+            values.push(Box::new(season));
+        }
+        let mut sql = "SELECT id, season, name, region, rarity FROM cards".to_owned();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        // Rarities sort the wrong way alphabetically, so order by their position in the list.
+        let rank = Rarity::ALL.iter().enumerate()
+            .map(|(i, x)| format!("WHEN '{}' THEN {}", x, i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        sql.push_str(&format!(" ORDER BY CASE rarity {} ELSE -1 END DESC, season, id", rank));
+        self.query_with(&sql, values.iter().map(|x| x.as_ref()))
+    }
+    /// Last trade price of a card, if it was looked up less than `max_age` ago.
+    /// The outer `None` means there's no fresh lookup cached,
+    /// the inner one that the card has never been sold.
+    pub fn cached_price(&self, id: u64, season: u8, max_age: chrono::Duration) -> Result<Option<Option<f64>>, IndexError> {
+        let oldest = (chrono::Utc::now() - max_age).timestamp();
+        let mut stmt = self.conn.prepare(
+            "SELECT price FROM card_prices WHERE season = ? AND id = ? AND fetched >= ?")?;
+        let mut rows = stmt.query(params![season, id as i64, oldest])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
+    pub fn cache_price(&self, id: u64, season: u8, price: Option<f64>) -> Result<(), IndexError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO card_prices (season, id, fetched, price) VALUES (?, ?, ?, ?)",
+            params![season, id as i64, chrono::Utc::now().timestamp(), price])?;
+        Ok(())
+    }
+    /// A nation's founding and logins, if fetched within `max_age`.
+    pub fn cached_residency(&self, nation: &str, max_age: chrono::Duration) -> Result<Option<Residency>, IndexError> {
+        let oldest = (Utc::now() - max_age).timestamp();
+        let mut stmt = self.conn.prepare(
+            "SELECT founded, first_login, last_login FROM residency WHERE nation = ? AND fetched >= ?")?;
+        let mut rows = stmt.query(params![crate::canonical_name(nation), oldest])?;
+        let time = |x: Option<i64>| x.and_then(|x| Utc.timestamp_opt(x, 0).single());
+        match rows.next()? {
+            Some(row) => Ok(Some(Residency {
+                nation: nation.to_owned(),
+                founded: time(row.get(0)?),
+                first_login: time(row.get(1)?),
+                last_login: time(row.get(2)?),
+            })),
+            None => Ok(None),
+        }
+    }
+    pub fn cache_residency(&self, residency: &Residency) -> Result<(), IndexError> {
+        let time = |x: Option<DateTime<Utc>>| x.map(|x| x.timestamp());
+        self.conn.execute(
+            "INSERT OR REPLACE INTO residency (nation, fetched, founded, first_login, last_login) VALUES (?, ?, ?, ?, ?)",
+            params![crate::canonical_name(&residency.nation), Utc::now().timestamp(),
+                    time(residency.founded), time(residency.first_login), time(residency.last_login)])?;
+        Ok(())
+    }
+    pub fn record_influence(&self, nation: &str, level: &str, score: f64) -> Result<(), IndexError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO influence (nation, recorded, level, score) VALUES (?, ?, ?, ?)",
+            params![crate::canonical_name(nation), Utc::now().timestamp(), level, score])?;
+        Ok(())
+    }
+    /// Influence scores recorded for a nation since `since`, oldest first.
+    pub fn influence_history(&self, nation: &str, since: DateTime<Utc>) -> Result<Vec<(DateTime<Utc>, f64)>, IndexError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT recorded, score FROM influence WHERE nation = ? AND recorded >= ? ORDER BY recorded")?;
+        let rows = stmt.query_map(params![crate::canonical_name(nation), since.timestamp()], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?))
+        })?;
+        let mut history = Vec::new();
+        for row in rows {
+            let (recorded, score) = row?;
+            if let Some(recorded) = Utc.timestamp_opt(recorded, 0).single() {
+                history.push((recorded, score));
+            }
+        }
+        Ok(history)
+    }
+    /// Times `region` last updated, as recorded in each indexed regions dump, oldest first.
+    pub fn region_updates(&self, region: &str) -> Result<Vec<DateTime<Utc>>, IndexError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT last_update FROM regions WHERE id = ? AND last_update IS NOT NULL ORDER BY last_update")?;
+        let rows = stmt.query_map(params![crate::canonical_name(region)], |row| row.get::<_, i64>(0))?;
+        let mut updates = Vec::new();
+        for row in rows {
+            updates.extend(Utc.timestamp_opt(row?, 0).single());
+        }
+        Ok(updates)
+    }
+    /// Whether a welcome telegram for `region` has gone to `nation`.
+    pub fn welcomed(&self, region: &str, nation: &str) -> Result<bool, IndexError> {
+        let mut stmt = self.conn.prepare("SELECT 1 FROM welcomed WHERE region = ? AND nation = ?")?;
+        Ok(stmt.exists(params![crate::canonical_name(region), crate::canonical_name(nation)])?)
+    }
+    pub fn record_welcome(&self, region: &str, nation: &str) -> Result<(), IndexError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO welcomed (region, nation, sent) VALUES (?, ?, ?)",
+            params![crate::canonical_name(region), crate::canonical_name(nation), chrono::Utc::now().timestamp()])?;
+        Ok(())
+    }
+    /// Log what the issue autopilot did, with `None` for issues it skipped.
+    pub fn record_decision(&self, nation: &str, issue: u32, option: Option<i32>, reason: &str) -> Result<(), IndexError> {
+        self.conn.execute(
+            "INSERT INTO issue_decisions (nation, issue, option, reason, decided) VALUES (?, ?, ?, ?, ?)",
+            params![crate::canonical_name(nation), issue, option, reason, chrono::Utc::now().timestamp()])?;
+        Ok(())
+    }
+    /// Keep everything that came of answering an issue.
+    /// The census changes are kept separately, so later decisions can go by them.
+    pub fn record_outcome(&mut self, nation: &str, title: Option<&str>, outcome: &Outcome) -> Result<(), IndexError> {
+        let tx = self.conn.transaction()?;
+        let now = chrono::Utc::now().timestamp();
+        // Lists are kept one item per line; nothing here has newlines of its own.
+        let policies = |x: &NationalPolicies| x.inner.iter().map(|x| format!("{} ({})", x.name, x.category)).join("\n");
+        tx.execute(
+            "INSERT INTO issue_results (nation, issue, title, option, answered, description, headlines,
+                                        reclassifications, banners, new_policies, removed_policies)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                crate::canonical_name(nation),
+                outcome.id,
+                title,
+                outcome.choice,
+                now,
+                outcome.description,
+                outcome.headlines.inner.join("\n"),
+                outcome.reclassifications.inner.iter()
+                    .map(|x| format!("{}: {} -> {}", x.subject(), x.from, x.to))
+                    .join("\n"),
+                outcome.unlocks.banners.join("\n"),
+                policies(&outcome.new_policies),
+                policies(&outcome.removed_policies),
+            ])?;
+        for rank in &outcome.rankings.inner {
+            tx.execute(
+                "INSERT INTO issue_effects (nation, issue, option, scale, change, answered) VALUES (?, ?, ?, ?, ?, ?)",
+                params![crate::canonical_name(nation), outcome.id, outcome.choice, rank.id, rank.change, now])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    /// Issues a nation has answered through this tool, newest first.
+    pub fn issue_history(&self, nation: &str, limit: u32) -> Result<Vec<AnsweredIssue>, IndexError> {
+        let nation = crate::canonical_name(nation);
+        let mut stmt = self.conn.prepare(
+            "SELECT issue, title, option, answered, description, headlines, reclassifications,
+                    banners, new_policies, removed_policies
+             FROM issue_results WHERE nation = ? ORDER BY answered DESC LIMIT ?")?;
+        let lines = |x: String| x.lines().map(String::from).collect::<Vec<_>>();
+        let rows = stmt.query_map(params![nation, limit], |row| Ok(AnsweredIssue {
+            issue: row.get(0)?,
+            title: row.get(1)?,
+            option: row.get(2)?,
+            answered: Utc.timestamp_opt(row.get(3)?, 0).single().unwrap_or_else(Utc::now),
+            description: row.get(4)?,
+            headlines: lines(row.get(5)?),
+            reclassifications: lines(row.get(6)?),
+            banners: lines(row.get(7)?),
+            new_policies: lines(row.get(8)?),
+            removed_policies: lines(row.get(9)?),
+            effects: Vec::new(),
+        }))?;
+        let mut history = Vec::new();
+        for row in rows {
+            history.push(row?);
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT scale, change FROM issue_effects
+             WHERE nation = ? AND issue = ? AND option = ? AND answered = ?
+             ORDER BY ABS(change) DESC")?;
+        for answered in &mut history {
+            let rows = stmt.query_map(
+                params![nation, answered.issue, answered.option, answered.answered.timestamp()],
+                |row| Ok((row.get(0)?, row.get(1)?)))?;
+            for row in rows {
+                answered.effects.push(row?);
+            }
+        }
+        Ok(history)
+    }
+    /// Average change to a scale recorded for an issue option, across all nations.
+    pub fn average_effect(&self, issue: u32, option: i32, scale: u16) -> Result<Option<f64>, IndexError> {
+        Ok(self.conn.query_row(
+            "SELECT AVG(change) FROM issue_effects WHERE issue = ? AND option = ? AND scale = ?",
+            params![issue, option, scale], |row| row.get(0))?)
+    }
+    /// Most recent day with a dump of this kind indexed.
+    pub fn latest_day(&self, kind: Kind) -> Result<Option<NaiveDate>, IndexError> {
+        let day: Option<String> = self.conn.query_row(
+            &format!("SELECT MAX(day) FROM {}", kind), params![], |row| row.get(0))?;
+        Ok(day.and_then(|x| x.parse().ok()))
+    }
+    /// Run any SQL against the index.
+    pub fn query(&self, sql: &str) -> Result<Table, IndexError> {
+        self.query_with(sql, core::iter::empty())
+    }
+    fn query_with<'a, P>(&self, sql: &str, values: P) -> Result<Table, IndexError>
+    where
+        P: IntoIterator<Item = &'a dyn rusqlite::ToSql>,
+    {
+        let mut stmt = self.conn.prepare(sql)?;
+        let columns: Vec<String> = stmt.column_names().into_iter().map(str::to_owned).collect();
+        let width = columns.len();
+        let mut rows = stmt.query(values)?;
+        let mut table = Table { columns, rows: Vec::new() };
+        while let Some(row) = rows.next()? {
+            table.rows.push((0..width).map(|i| match row.get_raw(i) {
+                ValueRef::Null => String::new(),
+                ValueRef::Integer(x) => x.to_string(),
+                ValueRef::Real(x) => x.to_string(),
+                ValueRef::Text(x) | ValueRef::Blob(x) => String::from_utf8_lossy(x).into_owned(),
+            }).collect());
+        }
+        Ok(table)
+    }
+    /// Regions on a given day matching a filter, biggest first.
+    pub fn regions(&self, day: NaiveDate, filter: &RegionFilter) -> Result<Table, IndexError> {
+        let mut sql = format!(
+            "SELECT name, nations, delegate, delegate_votes, founder FROM regions WHERE day = '{}'", day);
+        if filter.founderless {
+            sql.push_str(" AND founder IS NULL");
+        }
+        if let Some(min) = filter.min_nations {
+            sql.push_str(&format!(" AND nations >= {}", min));
+        }
+        sql.push_str(" ORDER BY nations DESC");
+        self.query(&sql)
+    }
+    /// Compare regions across two indexed days.
+    /// Swings smaller than `min_swing` nations are left out.
+    pub fn region_diff(&self, from: NaiveDate, to: NaiveDate, min_swing: u32) -> Result<RegionDiff, IndexError> {
+        let (from, to) = (from.to_string(), to.to_string());
+        let mut diff = RegionDiff::default();
+        let mut stmt = self.conn.prepare(
+            "SELECT a.name, a.delegate, b.delegate FROM regions a JOIN regions b ON a.id = b.id
+             WHERE a.day = ?1 AND b.day = ?2 AND a.delegate IS NOT b.delegate
+             ORDER BY b.nations DESC")?;
+        let rows = stmt.query_map(params![from, to], |row| Ok(DelegateChange {
+            region: row.get(0)?,
+            before: row.get(1)?,
+            after: row.get(2)?,
+        }))?;
+        for row in rows {
+            diff.delegates.push(row?);
+        }
+        // A founder only shows as ceased to exist if we have that day's nations to check against.
+        let mut stmt = self.conn.prepare(
+            "SELECT b.name, b.founder FROM regions a JOIN regions b ON a.id = b.id
+             WHERE a.day = ?1 AND b.day = ?2 AND b.founder IS NOT NULL
+               AND EXISTS (SELECT 1 FROM nations n WHERE n.day = ?1 AND n.id = b.founder)
+               AND EXISTS (SELECT 1 FROM nations n WHERE n.day = ?2)
+               AND NOT EXISTS (SELECT 1 FROM nations n WHERE n.day = ?2 AND n.id = b.founder)
+             ORDER BY b.nations DESC")?;
+        let rows = stmt.query_map(params![from, to], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for row in rows {
+            diff.founder_ctes.push(row?);
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT a.name, a.nations, b.nations FROM regions a JOIN regions b ON a.id = b.id
+             WHERE a.day = ?1 AND b.day = ?2 AND ABS(a.nations - b.nations) >= ?3
+             ORDER BY ABS(a.nations - b.nations) DESC")?;
+        let rows = stmt.query_map(params![from, to, min_swing], |row| Ok(Swing {
+            region: row.get(0)?,
+            before: row.get(1)?,
+            after: row.get(2)?,
+        }))?;
+        for row in rows {
+            diff.swings.push(row?);
+        }
+        Ok(diff)
+    }
+    /// Compare nations across two indexed days.
+    pub fn nation_diff(&self, from: NaiveDate, to: NaiveDate) -> Result<NationDiff, IndexError> {
+        let (from, to) = (from.to_string(), to.to_string());
+        let mut diff = NationDiff::default();
+        let mut stmt = self.conn.prepare(
+            "SELECT b.name, a.region, b.region FROM nations a JOIN nations b ON a.id = b.id
+             WHERE a.day = ?1 AND b.day = ?2 AND a.region != b.region
+             ORDER BY b.region, b.id")?;
+        let rows = stmt.query_map(params![from, to], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        for row in rows {
+            diff.moves.push(row?);
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT a.name, a.region FROM nations a
+             WHERE a.day = ?1 AND NOT EXISTS (SELECT 1 FROM nations b WHERE b.day = ?2 AND b.id = a.id)
+             ORDER BY a.region, a.id")?;
+        let rows = stmt.query_map(params![from, to], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for row in rows {
+            diff.ceased.push(row?);
+        }
+        Ok(diff)
+    }
+    /// Whether a dump of this kind is indexed for the given day.
+    /// Whether the nations dump for `day` has `name` in it.
+    pub fn has_nation(&self, day: NaiveDate, name: &str) -> Result<bool, IndexError> {
+        let mut stmt = self.conn.prepare("SELECT 1 FROM nations WHERE day = ? AND id = ?")?;
+        Ok(stmt.exists(params![day.to_string(), crate::canonical_name(name)])?)
+    }
+    pub fn has_day(&self, kind: Kind, day: NaiveDate) -> Result<bool, IndexError> {
+        Ok(self.conn.query_row(
+            &format!("SELECT EXISTS (SELECT 1 FROM {} WHERE day = ?)", kind),
+            params![day.to_string()], |row| row.get(0))?)
+    }
+}
@@ -0,0 +1,317 @@
+//! Issues, and answering them automatically.
+//!
+//! An answering policy is a short list of rules set per nation in the config:
+//! options to avoid or prefer by their text, a census scale to push up,
+//! and what to do when none of that settles it.
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+use crate::census::CensusScale;
+
+/// Option ID that dismisses an issue instead of answering it.
+pub const DISMISS: i32 = -1;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct IssueOption {
+    pub id: i32,
+    #[serde(rename = "$value")]
+    pub text: String,
+}
+/// A pending issue, from the private `issues` shard.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Issue {
+    pub id: u32,
+    #[serde(rename = "TITLE")]
+    pub title: String,
+    #[serde(rename = "TEXT")]
+    pub text: String,
+    #[serde(rename = "OPTION", default)]
+    pub options: Vec<IssueOption>,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Issues {
+    #[serde(rename = "ISSUE", default)]
+    pub inner: Vec<Issue>,
+}
+
+/// Change to one census scale after answering an issue.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RankChange {
+    /// Census scale ID.
+    pub id: u16,
+    #[serde(rename = "SCORE")]
+    pub score: f64,
+    #[serde(rename = "CHANGE")]
+    pub change: f64,
+    /// Change as a percentage of the old score.
+    #[serde(rename = "PCHANGE")]
+    pub percent_change: f64,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Rankings {
+    #[serde(rename = "RANK", default)]
+    pub inner: Vec<RankChange>,
+}
+/// A change of government category or freedom description.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Reclassification {
+    /// `govt` for the government category,
+    /// or `0`, `1`, `2` for civil rights, economy, and political freedom.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(rename = "FROM")]
+    pub from: String,
+    #[serde(rename = "TO")]
+    pub to: String,
+}
+impl Reclassification {
+    /// What was reclassified, in words.
+    pub fn subject(&self) -> &str {
+        match self.kind.as_str() {
+            "govt" => "Government",
+            "0" => "Civil Rights",
+            "1" => "Economy",
+            "2" => "Political Freedom",
+            other => other,
+        }
+    }
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Reclassifications {
+    #[serde(rename = "RECLASSIFY", default)]
+    pub inner: Vec<Reclassification>,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Headlines {
+    #[serde(rename = "HEADLINE", default)]
+    pub inner: Vec<String>,
+}
+/// Banners unlocked by an answer.
+#[derive(Debug, Deserialize, Default)]
+pub struct Unlocks {
+    #[serde(rename = "BANNER", default)]
+    pub banners: Vec<String>,
+}
+/// A national policy, like "Gun Control".
+/// Sent by the `policies` shard and when an answer adopts or drops one.
+#[derive(Debug, Deserialize, Clone)]
+pub struct NationalPolicy {
+    #[serde(rename = "NAME")]
+    pub name: String,
+    #[serde(rename = "CAT")]
+    pub category: String,
+    #[serde(rename = "DESC", default)]
+    pub description: String,
+    /// Image code the site uses for the policy.
+    #[serde(rename = "PIC", default)]
+    pub picture: String,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct NationalPolicies {
+    #[serde(rename = "POLICY", default)]
+    pub inner: Vec<NationalPolicy>,
+}
+
+/// What happened after answering an issue.
+#[derive(Debug, Deserialize)]
+pub struct Outcome {
+    pub id: u32,
+    pub choice: i32,
+    /// `1` when the answer went through.
+    #[serde(rename = "OK")]
+    pub ok: Option<u8>,
+    #[serde(rename = "ERROR")]
+    pub error: Option<String>,
+    /// Summary of the effect, like "The nation's prisons are now empty."
+    #[serde(rename = "DESC")]
+    pub description: Option<String>,
+    #[serde(rename = "RANKINGS", default)]
+    pub rankings: Rankings,
+    #[serde(rename = "HEADLINES", default)]
+    pub headlines: Headlines,
+    #[serde(rename = "RECLASSIFICATIONS", default)]
+    pub reclassifications: Reclassifications,
+    #[serde(rename = "UNLOCKS", default)]
+    pub unlocks: Unlocks,
+    #[serde(rename = "NEW_POLICIES", default)]
+    pub new_policies: NationalPolicies,
+    #[serde(rename = "REMOVED_POLICIES", default)]
+    pub removed_policies: NationalPolicies,
+}
+/// Response to the issue answering command.
+#[derive(Debug, Deserialize)]
+pub struct AnswerResponse {
+    #[serde(rename = "ISSUE")]
+    pub issue: Outcome,
+}
+
+/// What to do when no rule picks an option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Fallback {
+    /// Leave the issue for a human.
+    #[default]
+    Skip,
+    Dismiss,
+    /// Pick the first option not ruled out, leaving the issue if they all are.
+    First,
+}
+
+fn regexes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|x| Regex::new(&format!("(?i){}", x)).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// Rules for answering one nation's issues.
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Never pick options whose text matches any of these, case insensitively.
+    #[serde(deserialize_with = "regexes")]
+    pub avoid: Vec<Regex>,
+    /// Pick the first option whose text matches any of these.
+    #[serde(deserialize_with = "regexes")]
+    pub prefer: Vec<Regex>,
+    /// Pick the option that raised this scale the most when it was picked before.
+    #[serde(deserialize_with = "crate::api::from_text_optional")]
+    pub maximize: Option<CensusScale>,
+    pub fallback: Fallback,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    Answer { option: i32, reason: String },
+    Skip { reason: String },
+}
+
+impl Policy {
+    /// Choose an option for an issue.
+    /// `effect` gives the average change to a census scale
+    /// recorded for an option of an issue, if it's been picked before.
+    pub fn decide<F>(&self, issue: &Issue, effect: F) -> Decision
+    where
+        F: Fn(u32, i32, CensusScale) -> Option<f64>,
+    {
+        let allowed: Vec<&IssueOption> = issue.options.iter()
+            .filter(|x| !self.avoid.iter().any(|re| re.is_match(&x.text)))
+            .collect();
+        for re in &self.prefer {
+            if let Some(option) = allowed.iter().find(|x| re.is_match(&x.text)) {
+                return Decision::Answer { option: option.id, reason: format!("matches /{}/", strip_flags(re)) };
+            }
+        }
+        if let Some(scale) = self.maximize {
+            let best = allowed.iter()
+                .filter_map(|x| Some((x.id, effect(issue.id, x.id, scale)?)))
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(core::cmp::Ordering::Equal));
+            if let Some((option, change)) = best {
+                return Decision::Answer { option, reason: format!("{} changed by {:+.2} on average before", scale, change) };
+            }
+        }
+        match self.fallback {
+            Fallback::Skip => Decision::Skip { reason: "no rule applies".into() },
+            Fallback::Dismiss => Decision::Answer { option: DISMISS, reason: "fallback: dismiss".into() },
+            Fallback::First => match allowed.first() {
+                Some(option) => Decision::Answer { option: option.id, reason: "fallback: first allowed option".into() },
+                None => Decision::Skip { reason: "every option is avoided".into() },
+            },
+        }
+    }
+}
+
+/// The pattern as written in the config, without the flags added when compiling it.
+fn strip_flags(re: &Regex) -> &str {
+    re.as_str().trim_start_matches("(?i)")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue() -> Issue {
+        let option = |id, text: &str| IssueOption { id, text: text.into() };
+        Issue {
+            id: 42,
+            title: "Taxing Times".into(),
+            text: "The treasury is empty.".into(),
+            options: vec![
+                option(0, "Raise taxes on the rich."),
+                option(1, "Cut spending on the military."),
+                option(2, "Print more money."),
+            ],
+        }
+    }
+
+    fn policy(toml: &str) -> Policy {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn none(_: u32, _: i32, _: CensusScale) -> Option<f64> {
+        None
+    }
+
+    fn answer(decision: Decision) -> i32 {
+        match decision {
+            Decision::Answer { option, .. } => option,
+            Decision::Skip { reason } => panic!("skipped: {}", reason),
+        }
+    }
+
+    #[test]
+    fn avoided_options_are_never_picked() {
+        let policy = policy(r#"avoid = ["TAXES", "military"]
+                               fallback = "first""#);
+        assert_eq!(answer(policy.decide(&issue(), none)), 2);
+    }
+
+    #[test]
+    fn preferences_go_in_order() {
+        let policy = policy(r#"prefer = ["money", "taxes"]"#);
+        let decision = policy.decide(&issue(), none);
+        assert_eq!(decision, Decision::Answer { option: 2, reason: "matches /money/".into() });
+        let policy = self::policy(r#"prefer = ["money", "taxes"]
+                                     avoid = ["print"]"#);
+        assert_eq!(answer(policy.decide(&issue(), none)), 0);
+    }
+
+    #[test]
+    fn maximize_picks_the_best_recorded_effect() {
+        let policy = policy(r#"maximize = "Economy""#);
+        let effect = |issue, option, scale| {
+            assert_eq!((issue, scale), (42, CensusScale::Economy));
+            match option {
+                0 => Some(1.5),
+                1 => Some(-2.0),
+                _ => None,
+            }
+        };
+        assert_eq!(answer(policy.decide(&issue(), effect)), 0);
+    }
+
+    #[test]
+    fn maximize_without_effects_falls_back() {
+        let policy = policy(r#"maximize = "Economy""#);
+        assert!(matches!(policy.decide(&issue(), none), Decision::Skip { .. }));
+        let policy = self::policy(r#"maximize = "Economy"
+                                     fallback = "first""#);
+        assert_eq!(answer(policy.decide(&issue(), none)), 0);
+    }
+
+    #[test]
+    fn fallbacks() {
+        assert_eq!(policy("").decide(&issue(), none), Decision::Skip { reason: "no rule applies".into() });
+        assert_eq!(answer(policy(r#"fallback = "dismiss""#).decide(&issue(), none)), DISMISS);
+        assert_eq!(answer(policy(r#"fallback = "first""#).decide(&issue(), none)), 0);
+        assert_eq!(answer(policy(r#"fallback = "first"
+                                    avoid = ["raise"]"#).decide(&issue(), none)), 1);
+    }
+
+    #[test]
+    fn first_leaves_issues_with_every_option_avoided() {
+        let policy = policy(r#"avoid = ["."]
+                               fallback = "first""#);
+        assert_eq!(policy.decide(&issue(), none), Decision::Skip { reason: "every option is avoided".into() });
+    }
+}
@@ -0,0 +1,76 @@
+//! Client for the NationStates API.
+//!
+//! The `nation` binary is a thin command line layer over this library.
+//!
+//! With the default `native` feature off, only building requests and
+//! parsing responses is left, along with the shard types. That much also
+//! builds for `wasm32-unknown-unknown`, sending through whatever
+//! [`http::HttpClient`] the page provides.
+pub mod api;
+pub mod audit;
+pub mod banners;
+pub mod bbcode;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cards;
+pub mod cassette;
+pub mod census;
+pub mod config;
+pub mod dispatch;
+#[cfg(feature = "native")]
+pub mod dump;
+pub mod economy;
+pub mod happenings;
+#[cfg(feature = "native")]
+pub mod hooks;
+pub mod http;
+#[cfg(feature = "native")]
+pub mod index;
+pub mod issues;
+pub mod metrics;
+pub mod names;
+#[cfg(feature = "native")]
+pub mod notify;
+pub mod poll;
+pub mod profile;
+pub mod ratelimit;
+pub mod region;
+pub mod rmb;
+pub mod session;
+pub mod settings;
+pub mod telegram;
+#[cfg(feature = "native")]
+pub mod term;
+#[cfg(feature = "self-update")]
+pub mod update;
+pub mod wa;
+pub mod world;
+pub mod zday;
+
+pub use profile::{Auth, Login, Nation, Pin, PinState};
+
+/// Base URL of the NationStates API.
+pub const API_BASE: &str = "https://www.nationstates.net/cgi-bin/api.cgi";
+/// The NationStates API version this library is written against,
+/// and the one requests ask for unless told otherwise.
+pub const API_VERSION: u16 = 11;
+/// User agent sent with every request, as the API rules require.
+pub const USER_AGENT: &str = "nation-rs/0.1.0 https://github.com/green-narofsky/nation-rs";
+
+/// [`USER_AGENT`] with the name of whoever runs it and how to reach them,
+/// as far as they're given.
+pub fn user_agent(operator: Option<&str>, contact: Option<&str>) -> String {
+    let operator = operator.map(str::trim).filter(|x| !x.is_empty());
+    let contact = contact.map(str::trim).filter(|x| !x.is_empty());
+    match (operator, contact) {
+        (Some(operator), Some(contact)) => format!("{} (run by {}, {})", USER_AGENT, operator, contact),
+        (Some(x), None) | (None, Some(x)) => format!("{} (run by {})", USER_AGENT, x),
+        (None, None) => USER_AGENT.to_owned(),
+    }
+}
+
+/// Normalize a nation or region name the way the API does,
+/// so "Testlandia Two" and "testlandia_two" compare equal.
+pub fn canonical_name(name: &str) -> String {
+    name.trim().to_lowercase().replace(' ', "_")
+}
@@ -1,384 +1,4828 @@
 use structopt::StructOpt;
 // use std::time::{Instant, Duration};
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use core::convert::Infallible;
 use core::str::FromStr;
-use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::fmt::Debug;
-use thiserror::Error;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::Mutex;
+use tokio::sync::watch;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use nation::{api, audit, wa, zday};
+use nation::api::ShardSet;
+use nation::banners;
+use nation::bbcode;
+use nation::cards::{self, Rarity};
+use nation::cassette::{self, Cassette};
+use nation::census::CensusScale;
+use nation::dump;
+use nation::economy::{self, Aggregate, Economy};
+use nation::config::Config;
+use nation::index::{CardFilter, Index, RegionFilter, Table};
+use nation::issues::{self, AnswerResponse, Decision, Issue, Policy};
+use nation::metrics;
+use nation::names;
+use nation::ratelimit;
+use nation::rmb;
+use nation::session::{RetryPolicy, Session};
+use nation::settings::{self, Appearance, Change};
+use nation::notify::{Notification, Notifier};
+use nation::poll;
+use nation::telegram::{Telegram, Template, Templates};
+use nation::term::{self, ColorChoice};
+use nation::happenings::{self, EventKind, View};
+use nation::hooks;
+use nation::profile::{self, Details, Profile};
+use nation::PinState;
+use nation::region::{self, Authority, EmbassyAction, Power, Residency, Snapshot, TagFilter, UpdateWindow};
+use nation::wa::{Council, ResolutionId};
 
-/// Base URL of the NationStates API.
-const API_BASE: &'static str = "https://www.nationstates.net/cgi-bin/api.cgi";
-/// The NationStates API version this library is written against.
-const API_VERSION: u16 = 11;
-
-/// Session pin for the NationStates API.
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Pin {
-    value: u64,
-    timestamp: DateTime<Utc>,
-}
-impl Pin {
-    /// Check validity based on timestamp.
-    /// Note that pins are also invalidated by additional logins.
-    fn valid(&self) -> bool {
-        Utc::now().signed_duration_since(self.timestamp) < Duration::hours(2)
-    }
-}
-
-/// Authentication information for the NationStates API.
-// A usable `Auth` will have at least one `Some` in its fields.
-#[derive(Serialize, Deserialize)]
-struct Auth {
-    // Storage should prefer storing autologin tokens over passwords.
-    password: Option<String>,
-    autologin: Option<String>,
-    pin: Option<Pin>,
+#[derive(StructOpt)]
+struct ProfilePath {
+    path: PathBuf,
 }
-impl Default for Auth {
+impl Default for ProfilePath {
     fn default() -> Self {
+        // The config is read again later, but defaults are settled while parsing arguments.
+        let configured = Config::load(&config_path()).ok().and_then(|x| x.profile);
         Self {
-            password: None,
-            autologin: None,
-            pin: None,
+            path: configured.unwrap_or_else(default_profile_path),
         }
     }
 }
-impl Debug for Auth {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        write!(f, "Auth")
-    }
+
+/// Where the profile goes unless the config says otherwise.
+fn default_profile_path() -> PathBuf {
+    // Separated out so I can do platform specific stuff if I want.
+    data_dir().join("nation.xml")
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Nation {
-    name: String,
-    auth: Auth,
+/// Where the hand written config file lives.
+fn config_path() -> PathBuf {
+    use directories::ProjectDirs;
+    let proj_dirs = ProjectDirs::from("", "", "Nation").unwrap();
+    proj_dirs.config_dir().join("config.toml")
+}
+
+/// Where the profile and everything we keep between runs goes.
+fn data_dir() -> PathBuf {
+    use directories::ProjectDirs;
+    let proj_dirs = ProjectDirs::from("", "", "Nation").unwrap();
+    proj_dirs.data_dir().to_owned()
 }
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename = "nations")]
-struct Nations {
-    #[serde(rename(deserialize = "$value", serialize = "nation"))]
-    inner: Vec<Nation>,
+impl FromStr for ProfilePath {
+    type Err = Infallible;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            path: PathBuf::from(input),
+        })
+    }
 }
-impl Nations {
-    /// Make a new collection of `Nation`s.
-    fn new() -> Self {
-        Self { inner: Vec::new() }
+impl core::fmt::Display for ProfilePath {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.path.to_string_lossy())
     }
 }
 
+/// Where to get a password from. Prompts on the terminal unless told otherwise.
 #[derive(StructOpt)]
-struct ProfilePath {
-    path: PathBuf,
+struct PasswordInput {
+    /// Deprecated: visible in shell history and `ps`. Prefer the prompt,
+    /// --password-stdin, or --password-file.
+    #[structopt(conflicts_with_all = &["password-stdin", "password-file"])]
+    password: Option<String>,
+    /// Read the password from the first line of standard input
+    #[structopt(long, conflicts_with = "password-file")]
+    password_stdin: bool,
+    /// Read the password from the first line of a file
+    #[structopt(long)]
+    password_file: Option<PathBuf>,
 }
-impl Default for ProfilePath {
-    fn default() -> Self {
-        // Separated out so I can do platform specific stuff if I want.
-        use directories::ProjectDirs;
-        let proj_dirs = ProjectDirs::from("", "", "Nation").unwrap();
-        Self {
-            path: proj_dirs.data_dir().join("nation.xml"),
+impl PasswordInput {
+    fn read(self) -> anyhow::Result<String> {
+        let password = if let Some(password) = self.password {
+            eprintln!("Warning: passwords given as arguments end up in shell history. \
+                       Leave it out to be prompted instead.");
+            password
+        } else if self.password_stdin {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line
+        } else if let Some(path) = self.password_file {
+            std::fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("Couldn't read {}: {}", path.display(), e))?
+                .lines().next().unwrap_or_default().to_owned()
+        } else {
+            rpassword::read_password_from_tty(Some("Password: ")).map_err(|e| {
+                anyhow::anyhow!("Couldn't prompt for a password ({}). Use --password-stdin or --password-file.", e)
+            })?
+        };
+        let password = password.trim_end_matches(&['\r', '\n'][..]).to_owned();
+        if password.is_empty() {
+            anyhow::bail!("The password is empty.");
         }
+        Ok(password)
     }
 }
-impl FromStr for ProfilePath {
-    type Err = Infallible;
-    fn from_str(input: &str) -> Result<Self, Self::Err> {
-        Ok(Self {
-            path: PathBuf::from(input),
-        })
+
+/// API version requests ask for, settled once at startup.
+static API_VERSION: AtomicU16 = AtomicU16::new(nation::API_VERSION);
+
+fn api_version() -> u16 {
+    API_VERSION.load(Ordering::Relaxed)
+}
+
+/// Whether to only print what would be sent or written.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Whether to leave pins alone, for nations also played elsewhere.
+static PIN_FRIENDLY: AtomicBool = AtomicBool::new(false);
+
+/// Load a profile, warning if other users can read it.
+fn load_profile(path: &Path) -> anyhow::Result<Profile> {
+    if let Ok(Some(mode)) = profile::loose_permissions(path) {
+        eprintln!("Warning: {} is readable by other users (mode {:o}). Run `nation doctor --fix-perms` to fix it.",
+                  path.display(), mode);
+    }
+    let mut profile = Profile::load(path)?;
+    profile.apply_env();
+    if PIN_FRIENDLY.load(Ordering::Relaxed) {
+        for nation in &mut profile.nations.inner {
+            nation.auth.pin_friendly = true;
+        }
     }
+    Ok(profile)
 }
-impl ToString for ProfilePath {
-    fn to_string(&self) -> String {
-        self.path.to_string_lossy().into_owned()
+
+/// Point out a pin that stopped working early, since that's
+/// usually another login elsewhere rather than expiry.
+fn warn_cut_short(nation: &nation::Nation) {
+    if let (Some(age), false) = (nation.auth.pin_cut_short, nation.auth.pin_friendly) {
+        eprintln!("Warning: {}'s pin was refused after {} minutes, long before it would expire. \
+                   Something else, like a browser, probably logged in as this nation. \
+                   If that keeps happening, --pin-friendly stops this tool relying on pins.",
+                  nation.name, age.num_minutes());
     }
 }
 
-// TODO: Consider separating the manually authored
-// profile and cached data retrieved from the API
-// into two separate files.
-// This is low priority because no
-// customization options come to mind.
-#[derive(Serialize, Debug)]
-struct Profile {
-    nations: Nations,
+lazy_static! {
+    /// Flipped by the first Ctrl-C.
+    static ref STOP: (watch::Sender<bool>, watch::Receiver<bool>) = watch::channel(false);
 }
-#[derive(Error, Debug)]
-enum ProfileError {
-    #[error("io error: {0}")]
-    Io(#[from] std::io::Error),
-    #[error("xml error: {0}")]
-    XmlError(#[from] quick_xml::DeError),
+/// Exit code after stopping for Ctrl-C, the one shells use for SIGINT.
+const INTERRUPTED_EXIT: i32 = 130;
+
+/// Whether Ctrl-C was pressed. Long-running commands check this between requests
+/// and wind down, saving the profile, instead of being cut off partway.
+fn interrupted() -> bool {
+    *STOP.1.borrow()
 }
-impl Profile {
-    fn load(path: &Path) -> Result<Self, ProfileError> {
-        let file = match std::fs::File::open(&path).map_err(|e| (e.kind(), e)) {
-            Ok(f) => f,
-            Err((std::io::ErrorKind::NotFound, _)) => return Ok(Self::default()),
-            Err((_, e)) => Err(e)?,
-        };
-        let reader = std::io::BufReader::new(file);
-        let nations = quick_xml::de::from_reader(reader)?;
-        Ok(Self { nations })
+
+/// Sleep between requests, waking early on Ctrl-C.
+async fn idle(duration: std::time::Duration) {
+    let mut stop = STOP.1.clone();
+    if interrupted() {
+        return;
     }
-    fn save(&self, path: &Path) -> Result<(), ProfileError> {
-        let writer = std::fs::File::create(&path)?;
-        Ok(quick_xml::se::to_writer(writer, &self.nations)?)
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => (),
+        _ = stop.changed() => (),
     }
 }
-impl Default for Profile {
-    fn default() -> Self {
-        Self {
-            nations: Nations::new(),
+
+/// Held while the profile is saved, so a second Ctrl-C waits for the save.
+static SAVING: Mutex<()> = Mutex::new(());
+
+/// Save the profile, unless this is a dry run.
+fn save_profile(profile: &mut Profile, path: &Path) -> anyhow::Result<()> {
+    if dry_run() {
+        println!("Would save profile to {}", path.display());
+        return Ok(());
+    }
+    let _saving = SAVING.lock();
+    if let Some(operator) = OPERATOR.lock().unwrap().as_deref() {
+        profile.attribute(path, operator)?;
+    }
+    Ok(profile.save(path)?)
+}
+
+/// A length of time given on the command line, like "25d", "12h", or "2w".
+/// A bare number is taken as days.
+#[derive(Debug, Clone, Copy)]
+struct Age(chrono::Duration);
+impl FromStr for Age {
+    type Err = String;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+        let (number, unit) = input.split_at(split);
+        let number: i64 = number.parse().map_err(|_| format!("expected a length of time like 25d, got {:?}", input))?;
+        Ok(Age(match unit {
+            "" | "d" => chrono::Duration::days(number),
+            "w" => chrono::Duration::weeks(number),
+            "h" => chrono::Duration::hours(number),
+            "m" => chrono::Duration::minutes(number),
+            _ => return Err(format!("unknown unit {:?}, expected w, d, h, or m", unit)),
+        }))
+    }
+}
+
+/// Exit statuses, so scripts can tell failures apart.
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_AUTH: i32 = 3;
+const EXIT_RATE_LIMITED: i32 = 4;
+const EXIT_NOT_FOUND: i32 = 5;
+const EXIT_NETWORK: i32 = 6;
+const EXIT_PARTIAL: i32 = 7;
+const EXIT_STATUS_HELP: &str = "EXIT STATUS:
+    0    success
+    1    any other error
+    2    bad command line arguments
+    3    credentials missing or rejected
+    4    rate limited by the API
+    5    nation or region not found by the API
+    6    network error
+    7    some nations in a batch failed, the rest succeeded
+    130  stopped by Ctrl-C";
+
+/// Some of a batch of nations failed.
+#[derive(Debug)]
+struct PartialFailure(String);
+impl core::fmt::Display for PartialFailure {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl std::error::Error for PartialFailure {}
+
+/// Exit status for an error, going by the first cause that has one.
+fn exit_code(error: &anyhow::Error) -> i32 {
+    let by_status = |status: reqwest::StatusCode| match status {
+        reqwest::StatusCode::NOT_FOUND => Some(EXIT_NOT_FOUND),
+        reqwest::StatusCode::FORBIDDEN => Some(EXIT_AUTH),
+        _ => None,
+    };
+    for cause in error.chain() {
+        let code = if let Some(failure) = cause.downcast_ref::<api::Failure>() {
+            match failure {
+                api::Failure::NoAuth | api::Failure::BadAuth | api::Failure::BadPin => Some(EXIT_AUTH),
+                api::Failure::RateLimited(_) => Some(EXIT_RATE_LIMITED),
+                api::Failure::Network(_) => Some(EXIT_NETWORK),
+                api::Failure::Other(status, _) => by_status(*status),
+                api::Failure::Malformed(_) | api::Failure::NotRecorded(_) | api::Failure::ReadOnly(_) => None,
+            }
+        } else if let Some(failure) = cause.downcast_ref::<api::RetryFailure>() {
+            match failure {
+                api::RetryFailure::NoAuth | api::RetryFailure::BadAuth => Some(EXIT_AUTH),
+                api::RetryFailure::RateLimited(_) => Some(EXIT_RATE_LIMITED),
+                api::RetryFailure::Network(_) => Some(EXIT_NETWORK),
+                api::RetryFailure::Other(status, _) => by_status(*status),
+                api::RetryFailure::Malformed(_) | api::RetryFailure::NotRecorded(_) | api::RetryFailure::ReadOnly(_) => None,
+            }
+        } else if cause.is::<reqwest::Error>() {
+            Some(EXIT_NETWORK)
+        } else if cause.is::<PartialFailure>() {
+            Some(EXIT_PARTIAL)
+        } else {
+            None
+        };
+        if let Some(code) = code {
+            return code;
         }
     }
+    EXIT_ERROR
+}
+
+#[derive(StructOpt)]
+#[structopt(after_help = EXIT_STATUS_HELP)]
+struct Cli {
+    /// API version to request, overriding the config
+    #[structopt(long, global = true)]
+    api_version: Option<u16>,
+    /// Send API requests here instead of to NationStates, overriding the config,
+    /// e.g. http://127.0.0.1:8080/cgi-bin/api.cgi for a caching proxy or test server
+    #[structopt(long, global = true, env = "NATION_API_BASE", value_name = "URL")]
+    api_base: Option<String>,
+    /// Record every API response to this cassette file, without credentials
+    #[structopt(long, global = true, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Answer API requests from a recorded cassette instead of the network
+    #[structopt(long, global = true)]
+    replay: Option<PathBuf>,
+    /// Print what telegrams, issue answers, and profile writes would do instead of doing them
+    #[structopt(long, global = true)]
+    dry_run: bool,
+    /// Refuse to send anything that would change a nation, like issue answers or telegrams,
+    /// for credentials shared only to keep an eye on nations
+    #[structopt(long, global = true)]
+    read_only: bool,
+    /// Send requests through this proxy, overriding the config and HTTPS_PROXY
+    #[structopt(long, global = true, value_name = "URL")]
+    proxy: Option<String>,
+    /// Describe every API request and response on stderr, with the rate limit headers
+    #[structopt(long, global = true)]
+    verbose: bool,
+    /// Log in with the autologin or password every time instead of using and keeping pins,
+    /// so nations also played in a browser don't keep getting logged out there
+    #[structopt(long, global = true)]
+    pin_friendly: bool,
+    /// Wait at least this many milliseconds between API requests, overriding the config
+    #[structopt(long, global = true, value_name = "MS")]
+    api_delay: Option<u64>,
+    /// Serve Prometheus metrics at http://ADDR/metrics while the command runs, e.g. 127.0.0.1:9184
+    #[structopt(long, global = true, value_name = "ADDR")]
+    metrics: Option<SocketAddr>,
+    /// When to color output: auto, always, or never. Auto leaves it off when NO_COLOR is set
+    /// or output isn't a terminal
+    #[structopt(long, global = true, default_value, value_name = "WHEN")]
+    color: ColorChoice,
+    #[structopt(subcommand)]
+    command: Opt,
 }
 
+// Changes the API has no commands for, like suppressing posts or moving
+// regions, are checked as far as they can be and then linked to on the site.
 #[derive(StructOpt)]
 enum Opt {
+    /// Set up the config and profile, and add a first nation
+    Init,
+    /// Run the commands an alias in the config stands for, one after another
+    Run {
+        /// Name of the alias
+        alias: String,
+    },
+    /// Replace this binary with the latest release, if there's a newer one
+    #[cfg(feature = "self-update")]
+    SelfUpdate {
+        /// Only say whether there's a newer release
+        #[structopt(long)]
+        check: bool,
+    },
     /// Ping nation(s)
     Ping {
         #[structopt(short, long, default_value)]
         profile: ProfilePath,
-        /// Retry with autologin or password if pin authentication fails
+        /// Retry with autologin or password if pin authentication fails.
+        /// Always on with `--group`.
         #[structopt(short, long = "retry")]
         retry_pin: bool,
+        /// Ping every nation in this group instead
+        #[structopt(short, long, conflicts_with = "nation")]
+        group: Option<String>,
         /// Name of the nation to ping
-        nation: String,
+        #[structopt(required_unless = "group")]
+        nation: Option<String>,
+    },
+    /// List the nations in the profile and the state of their credentials
+    List {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        /// Flag nations with no activity on record for this long, like "25d" or "12h".
+        /// Nations cease to exist after 28 days without logging in.
+        #[structopt(long)]
+        stale: Option<Age>,
+        /// Also show the region, WA status, population, and flag from the last ping or
+        /// `nation show`, without asking the API
+        #[structopt(long)]
+        details: bool,
     },
     /// Add a nation to profile
     Add {
         #[structopt(short, long, default_value)]
         profile: ProfilePath,
         name: String,
-        password: String,
+        #[structopt(flatten)]
+        password: PasswordInput,
         /// Verify the account against the NationStates API
         #[structopt(short, long)]
         verify: bool,
     },
-    #[allow(dead_code)]
     /// Save new password for a nation
     NewPassword {
         #[structopt(short, long, default_value)]
         profile: ProfilePath,
         /// Name of the nation whose password has changed
         nation: String,
-        /// New password for this nation
-        password: String,
-    }
-}
-
-mod api {
-    use std::borrow::Cow;
-    use itertools::Itertools;
-    use super::{Auth, Pin};
-    use chrono::Utc;
-    use serde::Deserialize;
-    use reqwest::StatusCode;
-    #[derive(Debug)]
-    pub enum Shard {
-        Ping,
-    }
-    impl Shard {
-        fn to_query_segment(&self) -> Cow<'_, str> {
-            // This may end up generated.
-            match self {
-                Shard::Ping => "ping".into(),
-            }
-        }
-    }
-    fn query_string(shards: &[Shard]) -> String {
-        shards.into_iter().map(Shard::to_query_segment).join("+")
-    }
-    #[derive(Debug, Deserialize)]
-    enum ResolvedShard {
-        #[serde(rename(deserialize = "PING"))]
-        Ping,
-    }
-    #[derive(Debug)]
-    pub struct Request<'a> {
-        pub(crate) nation: &'a super::Nation,
-        pub(crate) shards: Vec<Shard>,
-    }
-    impl Request<'_> {
-        // There are a bunch of copies and allocations
-        // involved in building this string,
-        // but it's not an optimization priority.
-        // LLVM probably sees through them anyway.
-        pub fn url(&self) -> String {
-            let mut res = String::from(crate::API_BASE);
-            res.push_str("?nation=");
-            res.push_str(&self.nation.name);
-            res.push_str("&q=");
-            res.push_str(&query_string(&self.shards));
-            res.push_str("&v=");
-            res.push_str(&crate::API_VERSION.to_string());
-            res
-        }
-    }
-    #[derive(Debug, Deserialize)]
-    pub struct NationData {
-        #[serde(rename(deserialize = "$value"))]
-        inner: Vec<ResolvedShard>,
-    }
-    #[derive(Debug)]
-    #[non_exhaustive]
-    pub struct Response {
-        pub data: NationData,
-        pub autologin: Option<String>,
-        pub pin: Option<Pin>,
-    }
-    #[derive(Debug)]
-    pub enum Failure {
-        NoAuth,
-        BadAuth,
-        // Bad pins are special because pins expire,
-        // so this is potentially recoverable.
-        // Also, pins can be invalidated by logging in separately.
-        // The `.valid()` method on pins is likely to
-        // handle pin expiration, but not arbitrary pin invalidation.
-        BadPin,
-        Other(StatusCode),
-    }
-    impl From<RetryFailure> for Failure {
-        fn from(f: RetryFailure) -> Self {
-            match f {
-                RetryFailure::NoAuth => Self::NoAuth,
-                RetryFailure::BadAuth => Self::BadAuth,
-                RetryFailure::Other(code) => Self::Other(code),
-            }
-        }
-    }
-    #[derive(Debug)]
-    pub enum RetryFailure {
-        NoAuth,
-        BadAuth,
-        Other(StatusCode),
-    }
-    impl Request<'_> {
-        async fn send_internal(&self, client: &reqwest::Client, use_pin: bool) -> Result<Response, Failure> {
-            // `reqwest` is on Tokio 0.2 still. We're on Tokio 0.3.
-            use tokio_compat_02::FutureExt;
-            let mut request = client.get(&self.url());
-            let mut using_pin = false;
-            match &self.nation.auth {
-                // Note that pins fail more easily than autologins or passwords.
-                // If a pin fails and we have another credential on hand,
-                // we should retry and save the pin we get next.
-                // This method won't control that behavior, though.
-                // It will simply return a distinct error code for that case.
-                Auth { pin: Some(pin), .. } if pin.valid() && use_pin => {
-                    request = request.header("X-Pin", pin.value);
-                    using_pin = true;
-                },
-                Auth { autologin: Some(autologin), .. } => {
-                    request = request.header("X-Autologin", autologin);
-                },
-                Auth { password: Some(password), .. } => {
-                    request = request.header("X-Password", password);
-                },
-                _ => return Err(Failure::NoAuth),
-            };
-            let response = request.send().compat().await.unwrap();
-            let timestamp = Utc::now();
-            let headers = response.headers();
-            let (pin_value, autologin) = (headers.get("X-Pin")
-                                          .and_then(|x| x.to_str().ok()?.parse().ok()),
-                                          headers.get("X-Autologin")
-                                          .and_then(|x| x.to_str().ok().map(String::from)));
-            let pin = pin_value.map(|value| Pin {
-                value, timestamp,
-            });
-            let status = response.status();
-            if status == StatusCode::OK {
-                let text = response.text().await.unwrap();
-                // println!("Response text: {}", text);
-                let data = quick_xml::de::from_str(&text).unwrap();
-                println!("Using pin: {}", using_pin);
-                Ok(Response { data, autologin, pin })
-            } else {
-                Err(if status == StatusCode::FORBIDDEN {
-                    if using_pin { Failure::BadPin } else { Failure::BadAuth }
-                } else {
-                    Failure::Other(status)
-                })
-            }
-        }
-        pub async fn send(&self, client: &reqwest::Client) -> Result<Response, Failure> {
-            self.send_internal(client, true).await
-        }
-        /// Send request, and retry if the pin on hand has been invalidated.
-        // This will never return BadPin.
-        pub async fn send_retry(&self, client: &reqwest::Client) -> Result<Response, RetryFailure> {
-            match self.send(client).await {
-                Ok(x) => Ok(x),
-                Err(Failure::BadPin) => match self.send_internal(client, false).await {
-                    Ok(x) => Ok(x),
-                    Err(Failure::BadPin) => unreachable!("bad pin on retry"),
-                    Err(Failure::NoAuth) => Err(RetryFailure::NoAuth),
-                    Err(Failure::BadAuth) => Err(RetryFailure::BadAuth),
-                    Err(Failure::Other(code)) => Err(RetryFailure::Other(code)),
-                },
-                Err(Failure::NoAuth) => Err(RetryFailure::NoAuth),
-                Err(Failure::BadAuth) => Err(RetryFailure::BadAuth),
-                Err(Failure::Other(code)) => Err(RetryFailure::Other(code))
-            }
-        }
-    }
+        #[structopt(flatten)]
+        password: PasswordInput,
+    },
+    /// Check the profile for problems: missing nations, bad credentials,
+    /// duplicate names, and loose file permissions
+    Doctor {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        /// Also ping every nation to check its credentials
+        #[structopt(long)]
+        ping: bool,
+        /// Make the profile readable by its owner only, if it isn't. Unix only
+        #[structopt(long)]
+        fix_perms: bool,
+    },
+    /// Check on a nation that ceased to exist, and re-verify it once restored
+    Restore {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        /// Name of the nation to restore
+        nation: String,
+    },
+    /// Show a summary of a nation
+    Show {
+        /// Name of the nation to show
+        nation: String,
+    },
+    /// Show a nation's economy, or the combined economy of a region
+    Economy {
+        /// Sum up every nation in this region instead, one request per nation
+        #[structopt(long, conflicts_with = "nation")]
+        region: Option<String>,
+        /// Name of the nation to show
+        #[structopt(required_unless = "region")]
+        nation: Option<String>,
+    },
+    /// Compare census scores and classifications of two nations
+    Compare {
+        /// First nation
+        a: String,
+        /// Second nation
+        b: String,
+        /// Comma separated scales to compare, by ID or name.
+        /// Defaults to the three freedoms.
+        #[structopt(long, use_delimiter = true)]
+        scales: Vec<CensusScale>,
+        /// Print the comparison as JSON
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Show census scores for a nation
+    Census {
+        /// Scale to show, by ID or name (e.g. "1" or "Economy").
+        /// May be repeated. Shows all scales if omitted.
+        #[structopt(short, long = "scale", number_of_values = 1)]
+        scales: Vec<CensusScale>,
+        /// List the known census scales and exit
+        #[structopt(long)]
+        list_scales: bool,
+        /// Name of the nation to query
+        #[structopt(required_unless = "list-scales")]
+        nation: Option<String>,
+        /// Show how scores changed over time instead
+        #[structopt(long)]
+        history: bool,
+        /// Start the history at this date (YYYY-MM-DD)
+        #[structopt(long, requires = "history")]
+        from: Option<NaiveDate>,
+        /// End the history at this date (YYYY-MM-DD)
+        #[structopt(long, requires = "history")]
+        to: Option<NaiveDate>,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+    /// Show the nations ranked highest on a census scale
+    Top {
+        /// Scale to rank by, by ID or name
+        #[structopt(short, long)]
+        scale: CensusScale,
+        /// Rank nations in this region instead of the whole world
+        #[structopt(long)]
+        region: Option<String>,
+        /// Number of nations to show
+        #[structopt(long, default_value = "100")]
+        limit: u32,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+    /// Check which nation names are taken
+    Exists {
+        /// Nations to check
+        names: Vec<String>,
+        /// File of nations to check, one per line
+        #[structopt(long)]
+        file: Option<PathBuf>,
+        /// Check against the latest nations dump in the index instead of the API
+        #[structopt(long)]
+        offline: bool,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+    /// Record regional influence for nations in the profile, and show how fast it's growing.
+    /// Run it regularly, say daily, to build up a history to go by.
+    Influence {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        /// Only this group of nations in the profile
+        #[structopt(short, long, conflicts_with = "nation")]
+        group: Option<String>,
+        /// Only this nation, which doesn't have to be in the profile
+        nation: Option<String>,
+        /// Estimate when influence reaches each --target at the current rate
+        #[structopt(long)]
+        project: bool,
+        /// Influence score to project to, like what ejecting a nation or
+        /// setting a password would cost, from the region's control page.
+        /// May be repeated
+        #[structopt(long = "target", number_of_values = 1, requires = "project")]
+        targets: Vec<f64>,
+    },
+    /// Make up nation names from a pattern like "Frontier {adjective} {noun}",
+    /// printing one per line
+    Names {
+        /// Text with {list} slots, filled from the built in adjective and noun lists
+        /// or from --list
+        #[structopt(long)]
+        pattern: names::Pattern,
+        /// Number of names to make
+        #[structopt(long, default_value = "50")]
+        count: usize,
+        /// Extra word lists for the pattern, as name=file with one word per line
+        #[structopt(long, value_name = "name=file")]
+        list: Vec<String>,
+        /// Only print names that aren't taken
+        #[structopt(long)]
+        check: bool,
+        /// With --check, go by the latest nations dump in the index instead of the API
+        #[structopt(long, requires = "check")]
+        offline: bool,
+    },
+    /// World Assembly commands
+    Wa(WaCommand),
+    /// Show recent happenings
+    Happenings {
+        /// Only show happenings in this region
+        #[structopt(long, conflicts_with = "nation")]
+        region: Option<String>,
+        /// Only show happenings involving this nation
+        #[structopt(long)]
+        nation: Option<String>,
+        /// Comma separated event categories to show:
+        /// move, founding, cte, endo, delegate, rmb, change, other
+        #[structopt(long, use_delimiter = true)]
+        only: Vec<happenings::Category>,
+        /// Maximum number of events to request
+        #[structopt(long)]
+        limit: Option<u32>,
+        /// Keep polling and print events as they happen
+        #[structopt(long)]
+        follow: bool,
+        /// Seconds between polls with --follow
+        #[structopt(long, default_value = "30")]
+        interval: u64,
+        /// Output format: text, or ndjson for a JSON object per event
+        #[structopt(short, long, default_value)]
+        output: Stream,
+    },
+    /// Region commands
+    Region(RegionCommand),
+    /// Endorsement commands
+    Endo(EndoCommand),
+    /// Regional message board commands
+    Rmb(RmbCommand),
+    /// Read dispatches
+    Dispatch(DispatchCommand),
+    /// Regional poll commands
+    Poll(PollCommand),
+    /// Z-Day, the yearly zombie event
+    Zday(ZdayCommand),
+    /// Check a nation can move to a region and link to the page to move it from
+    Move {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        nation: String,
+        region: String,
+        /// Once the nation has moved, record its new region in the profile
+        #[structopt(long)]
+        update: bool,
+    },
+    /// Search for regions by tag
+    Regions {
+        /// Comma separated tags, prefixed with "-" to exclude regions with that tag,
+        /// e.g. "defender,-password,-founderless"
+        #[structopt(long, use_delimiter = true, allow_hyphen_values = true, required = true)]
+        tags: Vec<TagFilter>,
+        /// Page of results to show, starting from 1
+        #[structopt(long, default_value = "1")]
+        page: usize,
+        /// Number of regions per page
+        #[structopt(long, default_value = "50")]
+        per_page: usize,
+        /// Output format: text, csv, or tsv. Csv and tsv list every matching region,
+        /// ignoring pagination
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+    /// Download daily dumps and query them offline
+    Dump(DumpCommand),
+    /// Trading card commands
+    Cards(CardsCommand),
+    /// Issue commands
+    Issues(IssuesCommand),
+    /// Check the API version in use against the live API
+    ApiVersion,
+    /// Download a nation's flag
+    Flag {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        /// Where to save the flag. For `--all`, the directory to save flags in.
+        /// Defaults to the nation's name in the current directory.
+        #[structopt(short, long)]
+        out: Option<PathBuf>,
+        /// Save the flags of every nation in the profile
+        #[structopt(long, conflicts_with = "nation")]
+        all: bool,
+        /// Name of the nation whose flag to download
+        #[structopt(required_unless = "all")]
+        nation: Option<String>,
+    },
+    /// List the banners a nation can display
+    Banners {
+        nation: String,
+    },
+    /// Check a change to a nation's motto, pretitle, flag, or banner, and link to
+    /// the page to make it on
+    Set(SetCommand),
+    /// Appearance themes across many nations
+    Theme(ThemeCommand),
+    /// List a nation's national policies
+    Policies {
+        nation: String,
+    },
+    /// Telegram commands
+    Tg(TgCommand),
+    /// World-wide feeds
+    World(WorldCommand),
+    /// Reports across the nations in the profile
+    Report(ReportCommand),
+    /// Group nations in the profile, for commands that take `--group`
+    Tag(TagCommand),
+    /// Send recruitment telegrams to newly founded nations
+    Recruit {
+        /// Region to recruit for
+        #[structopt(long)]
+        region: String,
+        /// Recruitment telegram template: the name of a saved one, or tgid:key
+        #[structopt(long)]
+        template: String,
+        /// API client key to send telegrams with
+        #[structopt(long)]
+        client_key: String,
+        /// File of nations to skip, one per line.
+        /// Nations telegrammed are appended to it.
+        #[structopt(long)]
+        exclude_file: Option<PathBuf>,
+        /// Seconds to wait before checking for new nations again
+        #[structopt(long, default_value = "60")]
+        poll: u64,
+    },
+    /// Telegram nations founded in or moving to a region, once each
+    Welcome {
+        /// Region to welcome nations to
+        #[structopt(long)]
+        region: String,
+        /// Telegram template: the name of a saved one, or tgid:key
+        #[structopt(long)]
+        template: String,
+        /// API client key to send telegrams with
+        #[structopt(long)]
+        client_key: String,
+        /// Keep checking for new arrivals until interrupted
+        #[structopt(long)]
+        daemon: bool,
+        /// Seconds to wait before checking for new arrivals again
+        #[structopt(long, default_value = "60")]
+        poll: u64,
+    },
+    /// Show the log of issue answers, telegrams, and other requests that
+    /// changed something, newest first
+    Audit {
+        /// Only show requests by this nation
+        #[structopt(long)]
+        nation: Option<String>,
+        /// Only show requests by this operator
+        #[structopt(long)]
+        operator: Option<String>,
+        /// How many entries to show
+        #[structopt(long, default_value = "50")]
+        limit: usize,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let opt = Opt::from_args();
-    // println!("timestamp: {}", quick_xml::se::to_string(&Utc::now()).unwrap());
-    match opt {
-        Opt::Ping { profile: profile_path, nation, retry_pin } => {
-            let mut profile = Profile::load(&profile_path.path)?;
-            // println!("Profile: {:#?}", profile);
-            // println!("XML Profile: {}", quick_xml::se::to_string(&profile.nations).unwrap());
-            let nation = match profile.nations.inner.iter_mut().find(|x| x.name == nation) {
-                Some(x) => x,
-                None => anyhow::bail!("Nation {} not found.", nation),
-            };
-            let req = api::Request {
-                shards: vec![api::Shard::Ping],
-                nation,
-            };
-            println!("Request: {:?}", req);
-            println!("Request URL: {}", req.url());
-            let client = reqwest::Client::builder()
-                .user_agent("nation-rs/0.1.0 https://github.com/green-narofsky/nation-rs").build().unwrap();
-            let res = if retry_pin {
-                req.send_retry(&client).await.map_err(From::from)
-            } else { req.send(&client).await };
-            match res {
-                Ok(api::Response { data, autologin, pin }) => {
-                    println!("Ok: {:?}", data);
-                    if let Some(autologin) = autologin {
-                        nation.auth.autologin = Some(autologin);
-                        // Since autologins last as long as passwords do,
-                        // we can delete our stored password.
-                        nation.auth.password = None;
-                    }
-                    if let Some(pin) = pin {
-                        nation.auth.pin = Some(pin);
-                    }
-                    profile.save(&profile_path.path)?;
-                },
-                Err(e) => anyhow::bail!("Failure: {:?}", e),
-            }
-        }
-        #[allow(unused_variables)]
-        Opt::Add {
-            profile,
-            name,
-            password,
-            verify,
-        } => todo!("adding nations to profile on command line"),
-        Opt::NewPassword { .. } => todo!("password changes"),
+#[derive(StructOpt)]
+enum WaCommand {
+    /// List proposals in a council's queue
+    Proposals {
+        /// Council to list proposals for: "ga" or "sc"
+        #[structopt(default_value = "ga")]
+        council: Council,
+        /// Keep polling a single proposal and report approval changes
+        #[structopt(long, value_name = "proposal-id")]
+        watch: Option<String>,
+        /// Seconds between polls in watch mode
+        #[structopt(long, default_value = "300")]
+        interval: u64,
+    },
+    /// Show votes on a resolution
+    Votes {
+        /// Council the resolution is in: "ga" or "sc"
+        #[structopt(default_value = "ga")]
+        council: Council,
+        /// "current" for the resolution at vote, or the number of a passed resolution.
+        /// Vote tracking is only available for the current resolution.
+        #[structopt(long, default_value = "current")]
+        resolution: ResolutionId,
+        /// Draw the hourly vote totals as a chart
+        #[structopt(long)]
+        chart: bool,
+        /// Output format: text, csv, or tsv. Csv and tsv print the hourly vote totals instead
+        #[structopt(short, long, default_value)]
+        output: Output,
+        /// Print the delegate vote log
+        #[structopt(long)]
+        log: bool,
+    },
+    /// Check a nation isn't in the World Assembly yet and link to where it applies
+    Join {
+        nation: String,
+    },
+    /// Check a nation is in the World Assembly, say what resigning costs,
+    /// and link to where it resigns
+    Leave {
+        nation: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum RegionCommand {
+    /// Report nations arriving in, leaving, or ceasing to exist in a region
+    /// since the last time it was watched
+    Watch {
+        region: String,
+        /// Keep watching, checking again every this many seconds
+        #[structopt(long)]
+        interval: Option<u64>,
+    },
+    /// Report who holds power in a region and how secure the delegacy is
+    Audit {
+        region: String,
+    },
+    /// Show a region's leaders, size, tags, WA badges, and any liberation
+    /// or injunction in force or on the way
+    Info {
+        region: String,
+    },
+    /// Estimate when a region updates, from influence changes in its recent
+    /// happenings and the update times in indexed regions dumps
+    UpdateTime {
+        region: String,
+    },
+    /// Print a region's factbook
+    Factbook {
+        region: String,
+        /// Print the BBCode as written, for editing
+        #[structopt(long)]
+        raw: bool,
+    },
+    /// World Factbook Entry commands
+    Wfe(WfeCommand),
+    /// Embassy commands
+    Embassy(EmbassyCommand),
+    /// Regional password commands
+    Password(RegionPasswordCommand),
+    /// Check that a nation can eject another from a region, preview the
+    /// influence involved, and link to the page to eject it from
+    Eject(BorderArgs),
+    /// Check that a nation can eject and ban another from a region, preview
+    /// the influence involved, and link to the page to ban it from
+    Ban(BorderArgs),
+    /// Show how long each resident has been around and when they last logged in,
+    /// least recently active first
+    Residency {
+        region: String,
+        /// Only show nations that haven't logged in for this long, like "7d"
+        #[structopt(long)]
+        inactive: Option<Age>,
+        /// Reuse details fetched within this long instead of asking again
+        #[structopt(long, default_value = "1d")]
+        max_age: Age,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+    /// Check tag changes against the known tags and a nation's authority,
+    /// and link to the page to make them on
+    Tag {
+        /// Officer changing the tags
+        nation: String,
+        region: String,
+        /// Comma separated tags to add
+        #[structopt(long, use_delimiter = true)]
+        add: Vec<String>,
+        /// Comma separated tags to remove
+        #[structopt(long, use_delimiter = true)]
+        remove: Vec<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum WfeCommand {
+    /// Show how a new World Factbook Entry differs from the current one,
+    /// check that a nation can change it, and link to the page to change it on
+    Set {
+        /// Officer changing the entry
+        nation: String,
+        region: String,
+        /// File holding the new entry, in BBCode
+        #[structopt(long)]
+        file: PathBuf,
+    },
+}
+
+/// Check that an embassy change can be made, and link to the page to make it on.
+#[derive(StructOpt)]
+enum EmbassyCommand {
+    /// Ask another region for an embassy
+    Request(EmbassyArgs),
+    /// Accept another region's request for an embassy
+    Accept(EmbassyArgs),
+    /// Turn down another region's request for an embassy
+    Reject(EmbassyArgs),
+    /// Close an embassy, or withdraw a request for one
+    Close(EmbassyArgs),
+}
+
+#[derive(StructOpt)]
+struct EmbassyArgs {
+    /// Officer with Embassies authority
+    nation: String,
+    region: String,
+    /// Region on the other end of the embassy
+    target: String,
+}
+
+#[derive(StructOpt)]
+enum RegionPasswordCommand {
+    /// Check that a nation can set a region's password, and link to the page to set it on
+    Set {
+        /// Founder or delegate with Executive authority
+        nation: String,
+        region: String,
+    },
+    /// Check that a nation can take a region's password off, and link to the page
+    /// to do it on
+    Clear {
+        /// Founder or delegate with Executive authority
+        nation: String,
+        region: String,
+    },
+}
+
+#[derive(StructOpt)]
+struct BorderArgs {
+    /// Officer with Border Control authority
+    nation: String,
+    region: String,
+    /// Nation to remove from the region
+    target: String,
+}
+
+#[derive(StructOpt)]
+enum EndoCommand {
+    /// List WA members in a region that a nation hasn't endorsed yet,
+    /// and link to where to endorse them
+    Sweep {
+        /// Nation doing the endorsing
+        nation: String,
+        /// Region to sweep
+        #[structopt(long)]
+        region: String,
+    },
+    /// Check a nation's endorsement of another, or of everyone in its region but a list,
+    /// can be withdrawn and link to the pages to withdraw them on
+    Withdraw {
+        /// Nation withdrawing its endorsements
+        nation: String,
+        #[structopt(required_unless = "all-except-file")]
+        target: Option<String>,
+        /// Withdraw from every nation the nation has endorsed except those listed in this file,
+        /// one per line
+        #[structopt(long, conflicts_with = "target", value_name = "FILE")]
+        all_except_file: Option<PathBuf>,
+    },
+}
+
+#[derive(StructOpt)]
+enum ZdayCommand {
+    /// Show how a nation is faring and check an action for it, then link to the page
+    /// to take it on
+    Act {
+        nation: String,
+        #[structopt(long, possible_values = &zday::ACTIONS)]
+        action: String,
+        /// Nation to fire a cure missile at
+        #[structopt(long, required_if("action", "cure"))]
+        target: Option<String>,
+    },
+    /// Show a region's survivors, zombies, and dead
+    Region {
+        region: String,
+        /// Keep polling and print the counts whenever they change
+        #[structopt(long)]
+        monitor: bool,
+        /// Seconds between polls with --monitor
+        #[structopt(long, default_value = "60")]
+        interval: u64,
+    },
+}
+
+#[derive(StructOpt)]
+enum RmbCommand {
+    /// Print the latest posts on a region's message board
+    Read {
+        region: String,
+        /// Number of posts to show
+        #[structopt(long, default_value = "50")]
+        limit: u32,
+        /// Keep polling and print posts as they're made
+        #[structopt(long)]
+        follow: bool,
+        /// Seconds between polls with --follow
+        #[structopt(long, default_value = "30")]
+        interval: u64,
+        /// Output format: text, or ndjson for a JSON object per post
+        #[structopt(short, long, default_value)]
+        output: Stream,
+    },
+    /// Check that a nation can suppress a post, and link to it on the site
+    Suppress {
+        /// Officer doing the suppressing
+        nation: String,
+        region: String,
+        post: u64,
+        /// Unsuppress the post instead
+        #[structopt(long)]
+        undo: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum DispatchCommand {
+    /// List a nation's dispatches
+    List {
+        nation: String,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+    /// Print a dispatch, with its BBCode rendered for the terminal
+    Read {
+        id: u64,
+        /// Render as Markdown instead
+        #[structopt(long)]
+        markdown: bool,
+    },
+}
+
+#[derive(StructOpt)]
+enum PollCommand {
+    /// Check a poll against the limits on polls and a nation's authority,
+    /// and link to the region to open it from
+    Create {
+        /// Officer opening the poll
+        nation: String,
+        region: String,
+        #[structopt(long)]
+        question: String,
+        /// An answer to offer, given once per option
+        #[structopt(long = "option", number_of_values = 1)]
+        options: Vec<String>,
+        /// How long the poll stays open, like "3d" or "12h"
+        #[structopt(long, default_value = "3d")]
+        duration: Age,
+    },
+}
+
+#[derive(StructOpt)]
+enum SetCommand {
+    Motto {
+        nation: String,
+        motto: String,
+    },
+    /// Only for nations with a population of 250 million or more
+    Pretitle {
+        nation: String,
+        pretitle: String,
+    },
+    Flag {
+        nation: String,
+        /// PNG, JPEG, GIF, or SVG image to upload
+        #[structopt(long)]
+        file: PathBuf,
+    },
+    Banner {
+        nation: String,
+        /// Banner code, like "v1", from `nation banners`
+        banner: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum ThemeCommand {
+    /// Show how a theme would change each nation in the profile, and link to
+    /// the page to make the changes on
+    Apply {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        /// Only this group of nations in the profile
+        #[structopt(short, long)]
+        group: Option<String>,
+        /// TOML file with motto, pretitle, flag, banner, capital, leader, religion,
+        /// currency, and animal settings, and [nations.<name>] tables overriding them
+        #[structopt(long)]
+        template: PathBuf,
+    },
+}
+
+#[derive(StructOpt)]
+enum DumpCommand {
+    /// Download today's dumps and add them to the local index
+    Download {
+        /// Which dumps to download, nations or regions
+        #[structopt(long, use_delimiter = true, default_value = "regions,nations")]
+        kinds: Vec<dump::Kind>,
+    },
+    /// Add a dump that's already on disk to the local index
+    Ingest {
+        file: PathBuf,
+        /// Whether the file is a nations or regions dump
+        #[structopt(long)]
+        kind: dump::Kind,
+        /// Day the dump is from, if it isn't at the start of the file name
+        #[structopt(long)]
+        date: Option<NaiveDate>,
+    },
+    /// Run an SQL query against the local index
+    Sql {
+        query: String,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+    /// Show what changed between two indexed dumps
+    Diff {
+        #[structopt(long)]
+        from: NaiveDate,
+        #[structopt(long)]
+        to: NaiveDate,
+        /// Compare regions or nations
+        #[structopt(long, default_value = "regions")]
+        what: dump::Kind,
+        /// Smallest change in a region's nation count worth reporting
+        #[structopt(long, default_value = "10")]
+        min_swing: u32,
+    },
+    /// List regions from the local index
+    Regions {
+        /// Only regions without a founder
+        #[structopt(long)]
+        founderless: bool,
+        /// Only regions with at least this many nations
+        #[structopt(long)]
+        min_nations: Option<u32>,
+        /// Day to look at, defaulting to the newest indexed dump
+        #[structopt(long)]
+        date: Option<NaiveDate>,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+}
+
+#[derive(StructOpt)]
+enum CardsCommand {
+    /// Download card lists and add them to the local index
+    Download {
+        /// Seasons to download
+        #[structopt(long, use_delimiter = true, required = true)]
+        season: Vec<u8>,
+    },
+    /// Estimate what a nation's deck is worth
+    Value {
+        nation: String,
+        /// Look up recent trades for this many of the most valuable cards
+        #[structopt(long, default_value = "10")]
+        lookups: usize,
+        /// Output format for the cards: text, csv, or tsv.
+        /// The totals are left out of csv and tsv.
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+    /// Watch a card's market and notify when it can be bought cheaply enough
+    Watch {
+        #[structopt(long)]
+        card: u64,
+        #[structopt(long)]
+        season: u8,
+        /// Notify when the lowest ask is at or below this much bank
+        #[structopt(long)]
+        max_bid: f64,
+        /// Seconds between checks
+        #[structopt(long, default_value = "60")]
+        interval: u64,
+        /// Shell command to run for notifications instead of printing them,
+        /// given NATION_TITLE and NATION_BODY in its environment
+        #[structopt(long)]
+        notify_command: Option<String>,
+    },
+    /// Search the indexed card lists
+    Search {
+        /// Part of a nation's name
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(long)]
+        rarity: Option<Rarity>,
+        #[structopt(long)]
+        season: Option<u8>,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+}
+
+#[derive(StructOpt)]
+enum IssuesCommand {
+    /// Show a nation's pending issues
+    List {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        nation: String,
+    },
+    /// Answer one of a nation's issues
+    Answer {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        nation: String,
+        issue: u32,
+        /// Option to pick, or -1 to dismiss the issue
+        #[structopt(allow_hyphen_values = true)]
+        option: i32,
+        /// Shell command to run when the answer reclassifies the nation,
+        /// given NATION_TITLE and NATION_BODY in its environment
+        #[structopt(long)]
+        notify_command: Option<String>,
+    },
+    /// Review issues answered through this tool and what came of them
+    History {
+        nation: String,
+        /// How many issues to show
+        #[structopt(long, default_value = "20")]
+        limit: u32,
+    },
+    /// Answer a nation's pending issues according to its policy in the config
+    Auto {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        /// Answer issues for every nation in this group instead
+        #[structopt(short, long, conflicts_with = "nation")]
+        group: Option<String>,
+        #[structopt(required_unless = "group")]
+        nation: Option<String>,
+        /// Shell command to run when an answer reclassifies a nation,
+        /// given NATION_TITLE and NATION_BODY in its environment
+        #[structopt(long)]
+        notify_command: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum TagCommand {
+    /// Add a nation to a group
+    Add {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        nation: String,
+        group: String,
+    },
+    /// Take a nation out of a group
+    Remove {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        nation: String,
+        group: String,
+    },
+    /// List groups and their nations
+    List {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+    },
+}
+
+#[derive(StructOpt)]
+enum ReportCommand {
+    /// Rank the profile's nations on a census scale, with their world percentile
+    Census {
+        #[structopt(short, long, default_value)]
+        profile: ProfilePath,
+        /// Only this group of nations in the profile
+        #[structopt(short, long)]
+        group: Option<String>,
+        /// Scale to rank by, by ID or name
+        #[structopt(short, long)]
+        scale: CensusScale,
+        /// Output format: text, csv, or tsv
+        #[structopt(short, long, default_value)]
+        output: Output,
+    },
+}
+
+#[derive(StructOpt)]
+enum WorldCommand {
+    /// List the most recently founded nations
+    Newnations {
+        /// Only nations founded in this region
+        #[structopt(long, value_name = "region")]
+        founded_in: Option<String>,
+        /// Keep polling and print nations as they're founded
+        #[structopt(long)]
+        follow: bool,
+        /// Seconds between polls with --follow
+        #[structopt(long, default_value = "30")]
+        interval: u64,
+        /// Output format: text, or ndjson for a JSON object per nation
+        #[structopt(short, long, default_value)]
+        output: Stream,
+    },
+}
+
+#[derive(StructOpt)]
+enum TgCommand {
+    /// Show how many telegrams are waiting for delivery
+    Queue,
+    /// Save telegram templates under names
+    Template(TemplateCommand),
+    /// Send a telegram to some nations, keeping to the template's rate limit
+    Send {
+        /// Template: the name of a saved one, or tgid:key
+        #[structopt(long)]
+        template: String,
+        /// API client key to send telegrams with
+        #[structopt(long)]
+        client_key: String,
+        /// Nations to send to
+        #[structopt(required = true)]
+        to: Vec<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum TemplateCommand {
+    /// Save a template, replacing any with the same name. Its key is kept in
+    /// plain text in templates.json, readable by its owner only, not in a keyring
+    Add {
+        name: String,
+        /// Template ID, the number in %TEMPLATE-1234%
+        #[structopt(long)]
+        tgid: u64,
+        /// Secret key the site shows for the template
+        #[structopt(long)]
+        key: String,
+        /// The template is marked for recruitment on the site
+        #[structopt(long)]
+        recruitment: bool,
+    },
+    /// Forget a saved template
+    Remove {
+        name: String,
+    },
+    /// List saved templates, without their keys
+    List,
+}
+
+/// Where named telegram templates are kept.
+fn templates_path() -> PathBuf {
+    data_dir().join("templates.json")
+}
+
+/// A saved template by name.
+fn saved_template(name: &str) -> anyhow::Result<Template> {
+    match Templates::load(&templates_path())?.get(name) {
+        Some(x) => Ok(x.clone()),
+        None => anyhow::bail!("No template named {:?}; see `nation tg template list`, or give it as tgid:key.", name),
+    }
+}
+
+/// Nations a command applies to: one by name, or every nation in a group.
+fn select<'a>(profile: &'a mut Profile, nation: Option<&str>, group: Option<&str>)
+              -> anyhow::Result<Vec<&'a mut nation::Nation>> {
+    let selected: Vec<_> = profile.nations.inner.iter_mut()
+        .filter(|x| match (nation, group) {
+            (Some(name), _) => x.name == name,
+            (None, Some(group)) => x.in_group(group),
+            (None, None) => false,
+        })
+        .collect();
+    if selected.is_empty() {
+        match (nation, group) {
+            (Some(name), _) => anyhow::bail!("Nation {} not found.", name),
+            (None, Some(group)) => anyhow::bail!("No nations in group {}.", group),
+            (None, None) => anyhow::bail!("No nation or group given."),
+        }
+    }
+    Ok(selected)
+}
+
+/// Ping a nation with its stored credentials,
+/// keeping any new credentials the API hands back.
+async fn ping(client: &reqwest::Client, nation: &mut nation::Nation) -> Result<PinState, api::Failure> {
+    let result = api::validate(client, nation, api_version()).await;
+    warn_cut_short(nation);
+    result
+}
+
+/// Send as `nation`, through the API version asked for.
+fn session<'a>(client: &'a reqwest::Client, nation: &'a mut nation::Nation) -> Session<'a, reqwest::Client> {
+    Session::new(client, nation).version(api_version())
+}
+
+/// Pull the value of a single shard out of response data.
+macro_rules! shard {
+    ($data:expr, $variant:ident) => {
+        $data.inner.into_iter().find_map(|shard| match shard {
+            api::ResolvedShard::$variant(x) => Some(x),
+            _ => None,
+        })
+    };
+}
+
+/// Seconds to wait for a connection when the config doesn't say.
+const CONNECT_TIMEOUT: u64 = 30;
+
+lazy_static! {
+    /// Client for every request, set up at startup once the proxy and certificates are known.
+    static ref CLIENT: Mutex<Option<reqwest::Client>> = Mutex::new(None);
+    /// Who's running the tool, if the config says, to mark profile changes with.
+    static ref OPERATOR: Mutex<Option<String>> = Mutex::new(None);
+}
+
+fn client() -> reqwest::Client {
+    CLIENT.lock().unwrap()
+        .get_or_insert_with(|| build_client(&Config::default(), None).unwrap())
+        .clone()
+}
+
+/// A client set up as the config says, going through `proxy` instead of the configured one if given.
+fn build_client(config: &Config, proxy: Option<&str>) -> anyhow::Result<reqwest::Client> {
+    use std::time::Duration;
+    let mut builder = reqwest::Client::builder()
+        .user_agent(nation::user_agent(config.operator.as_deref(), config.contact.as_deref()))
+        .connect_timeout(Duration::from_secs(config.connect_timeout.unwrap_or(CONNECT_TIMEOUT)));
+    if let Some(seconds) = config.timeout {
+        builder = builder.timeout(Duration::from_secs(seconds));
+    }
+    if let Some(proxy) = proxy.or(config.proxy.as_deref()) {
+        // This replaces the proxies from the environment.
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| anyhow::anyhow!("Bad proxy {}: {}", proxy, e))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &config.ca_bundle {
+        let pem = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Couldn't read CA bundle {}: {}", path.display(), e))?;
+        let certificate = reqwest::Certificate::from_pem(&pem)
+            .map_err(|e| anyhow::anyhow!("Bad CA bundle {}: {}", path.display(), e))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    builder.build().map_err(|e| anyhow::anyhow!("Couldn't set up the HTTP client: {}", e))
+}
+
+/// Download a nation's flag to `out`, or under its canonical name in `dir`,
+/// keeping the extension the site serves it with.
+async fn download_flag(client: &reqwest::Client, nation: &str, out: Option<PathBuf>, dir: &Path) -> anyhow::Result<PathBuf> {
+    let data = fetch(client, api::Target::Nation(nation), vec![api::Shard::Flag]).await?;
+    let url = match shard!(data, Flag) {
+        Some(x) => x,
+        None => anyhow::bail!("No flag in response for {}.", nation),
+    };
+    let path = out.unwrap_or_else(|| {
+        let extension = Path::new(&url).extension().and_then(|x| x.to_str()).unwrap_or("png");
+        dir.join(format!("{}.{}", nation::canonical_name(nation), extension))
+    });
+    dump::download_to(client, &url, &path).await?;
+    Ok(path)
+}
+
+/// Fetch public shards.
+async fn fetch(client: &reqwest::Client, target: api::Target<'_>, shards: Vec<api::Shard>) -> anyhow::Result<api::Data> {
+    let req = api::Request {
+        target,
+        auth: None,
+        shards,
+        version: api_version(),
+    };
+    Ok(req.send(client).await?.data)
+}
+
+/// Whether a request failed because the nation or region doesn't exist.
+fn is_not_found(e: &anyhow::Error) -> bool {
+    matches!(e.downcast_ref(), Some(api::Failure::Other(reqwest::StatusCode::NOT_FOUND, _)))
+}
+
+/// Split a command line into arguments at whitespace, keeping
+/// anything in single or double quotes together.
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            },
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("unclosed {}", q));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Ask a question on the terminal, taking `default` if the answer is empty.
+fn prompt(question: &str, default: Option<&str>) -> anyhow::Result<String> {
+    use std::io::Write;
+    match default {
+        Some(default) => print!("{} [{}]: ", question, default),
+        None => print!("{}: ", question),
+    }
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line)? == 0 {
+        anyhow::bail!("Standard input ended before setup was done.");
+    }
+    let answer = line.trim();
+    Ok(if answer.is_empty() { default.unwrap_or_default() } else { answer }.to_owned())
+}
+
+/// Walk through the config and profile, add a first nation, and ping it.
+async fn init() -> anyhow::Result<()> {
+    let path = config_path();
+    let config = Config::load(&path)?;
+    println!("Setting up nation. Press Enter to keep what's in brackets.");
+    println!("The API rules ask scripts to say who runs them, so NationStates can get in touch about problems.");
+    let contact = prompt("Your main nation or an email", config.contact.as_deref())?;
+    let default_profile = config.profile.clone().unwrap_or_else(default_profile_path);
+    let profile_path = PathBuf::from(prompt("Where to keep the profile", Some(&default_profile.to_string_lossy()))?);
+    let mut values = vec![("profile", toml::Value::String(profile_path.to_string_lossy().into_owned()))];
+    if !contact.is_empty() {
+        values.push(("contact", toml::Value::String(contact)));
+    }
+    if dry_run() {
+        println!("Would write the config to {}", path.display());
+    } else {
+        Config::update(&path, values)
+            .map_err(|e| anyhow::anyhow!("Couldn't write {}: {}", path.display(), e))?;
+        println!("Wrote the config to {}.", path.display());
+    }
+
+    let mut profile = load_profile(&profile_path)?;
+    let name = prompt("First nation to add, or nothing to skip", None)?;
+    if name.is_empty() {
+        save_profile(&mut profile, &profile_path)?;
+        println!("Add nations later with `nation add`.");
+        return Ok(());
+    }
+    let canonical = nation::canonical_name(&name);
+    let client = client();
+    let nation = match profile.nations.inner.iter().position(|x| nation::canonical_name(&x.name) == canonical) {
+        Some(i) => {
+            println!("{} is already in the profile, checking it still works.", name);
+            let nation = &mut profile.nations.inner[i];
+            ping(&client, nation).await?;
+            nation
+        },
+        None => {
+            let password = PasswordInput { password: None, password_stdin: false, password_file: None }.read()?;
+            let mut new = nation::Nation {
+                name,
+                auth: nation::Auth {
+                    password: Some(password),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            if let Err(e) = ping(&client, &mut new).await {
+                return Err(anyhow::Error::new(e).context(format!("Verification failed, not adding {}", new.name)));
+            }
+            let data = fetch(&client, api::Target::Nation(&new.name), vec![api::Shard::Region]).await?;
+            new.founding_region = shard!(data, Region);
+            profile.nations.inner.push(new);
+            profile.nations.inner.last_mut().unwrap()
+        },
+    };
+    println!("Pinged {}. Everything's set up.", nation.name);
+    save_profile(&mut profile, &profile_path)?;
+    Ok(())
+}
+
+/// Check a profile over, printing what's wrong and returning how many problems there are.
+/// Expired pins and names stored differently from the API's are noted without counting,
+/// since neither stops anything from working.
+async fn doctor(client: &reqwest::Client, path: &Path, ping_nations: bool, fix_perms: bool) -> anyhow::Result<usize> {
+    use std::collections::HashMap;
+    let mut problems = 0;
+    let mut problem = |subject: &str, message: String| {
+        println!("{}: {}: {}", term::bad("problem"), subject, message);
+        problems += 1;
+    };
+    let note = |subject: &str, message: String| println!("{}: {}: {}", term::warn("note"), subject, message);
+    let mut profile = Profile::load(path)?;
+    profile.apply_env();
+    if !path.exists() {
+        // Nations can come from the environment alone, but otherwise there's nothing to check.
+        if profile.nations.inner.is_empty() {
+            problem(&path.display().to_string(), "no profile here".into());
+            return Ok(problems);
+        }
+        note(&path.display().to_string(), "no profile here; using nations from the environment".into());
+    }
+    match profile::loose_permissions(path).unwrap_or(None) {
+        Some(mode) if fix_perms && !dry_run() => {
+            profile::restrict_permissions(path)?;
+            println!("{}: {}: mode {:o} -> 600", term::good("fixed"), path.display(), mode);
+        },
+        Some(mode) => problem(&path.display().to_string(),
+                              format!("readable by other users (mode {:o}), but it holds passwords; \
+                                       fix it with --fix-perms", mode)),
+        None if fix_perms && !cfg!(unix) => note(&path.display().to_string(),
+                                                 "permissions aren't checked or fixed on this platform; \
+                                                  limit who can read the profile through its ACL".into()),
+        None => (),
+    }
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    for nation in &profile.nations.inner {
+        let canonical = nation::canonical_name(&nation.name);
+        match seen.get(&canonical) {
+            Some(first) => problem(&nation.name, format!("same nation as {}, listed twice", first)),
+            None => { seen.insert(canonical, &nation.name); },
+        }
+    }
+    let mut changed = false;
+    for nation in &mut profile.nations.inner {
+        let name = nation.name.clone();
+        let auth = &nation.auth;
+        if auth.login().is_none() {
+            problem(&name, "no password or autologin stored".into());
+        }
+        match auth.pin_state() {
+            PinState::Expired => note(&name, "pin expired; the next request will log in again".into()),
+            PinState::Rejected => note(&name, "pin was rejected; the next request will log in again".into()),
+            PinState::Missing | PinState::Valid => (),
+        }
+        match fetch(client, api::Target::Nation(&name), vec![api::Shard::Name]).await {
+            Ok(data) => {
+                let actual = shard!(data, Name).unwrap_or_default();
+                if !actual.is_empty() && actual != name {
+                    note(&name, format!("stored under a different name than the API's {:?}", actual));
+                }
+            },
+            Err(e) if is_not_found(&e) => {
+                problem(&name, "doesn't exist; see `nation restore`".into());
+                continue;
+            },
+            Err(e) => {
+                problem(&name, format!("couldn't look it up: {}", e));
+                continue;
+            },
+        }
+        if ping_nations {
+            match ping(client, nation).await {
+                Ok(_) => changed = true,
+                Err(e) => problem(&name, format!("ping failed: {}", e)),
+            }
+        }
+    }
+    if changed {
+        save_profile(&mut profile, path)?;
+    }
+    Ok(problems)
+}
+
+async fn fetch_proposals(client: &reqwest::Client, council: Council) -> anyhow::Result<wa::Proposals> {
+    let data = fetch(client, api::Target::Wa(council), vec![api::Shard::Proposals]).await?;
+    shard!(data, Proposals).ok_or_else(|| anyhow::anyhow!("response did not contain proposals"))
+}
+
+/// Print newly founded nations, oldest first, and keep polling for more if `follow` is set.
+async fn new_nations(client: &reqwest::Client, founded_in: Option<&str>, follow: bool, interval: u64,
+                     output: Stream) -> anyhow::Result<()> {
+    use std::collections::HashSet;
+    let mut seen = HashSet::new();
+    loop {
+        let data = fetch(client, api::Target::World, vec![api::Shard::NewNationDetails]).await?;
+        let nations = shard!(data, NewNationDetails).unwrap_or_default().inner;
+        for nation in nations.iter().rev() {
+            if !seen.insert(nation.name.clone()) || matches!(founded_in, Some(x) if !nation.founded_in(x)) {
+                continue;
+            }
+            match output {
+                Stream::Text => println!("[{}] {} in {}", nation.founded.format("%Y-%m-%d %H:%M:%S"), nation.name, nation.region),
+                Stream::Ndjson => StreamEvent::NewNation {
+                    nation: &nation.name,
+                    region: &nation.region,
+                    time: nation.founded,
+                }.print(),
+            }
+        }
+        // The feed only holds the last fifty or so, so forget anything older.
+        seen.retain(|x| nations.iter().any(|n| &n.name == x));
+        if !follow {
+            return Ok(());
+        }
+        idle(std::time::Duration::from_secs(interval.max(10))).await;
+        if interrupted() {
+            return Ok(());
+        }
+    }
+}
+
+/// Long-form BBCode text, like dispatches and issues, rendered for the terminal.
+fn render_text(text: &str) -> String {
+    bbcode::render(text, if term::color() { bbcode::Format::Ansi } else { bbcode::Format::Plain })
+}
+
+fn print_post(post: &rmb::Post, region: &str, output: Stream) {
+    if output == Stream::Ndjson {
+        return StreamEvent::RmbPost {
+            id: post.id,
+            region,
+            nation: &post.nation,
+            time: post.timestamp,
+            status: post.status.to_string(),
+            likes: post.likes,
+            suppressor: post.suppressor.as_deref(),
+            message: &post.message,
+        }.print();
+    }
+    let time = post.timestamp.format("%Y-%m-%d %H:%M:%S");
+    let note = match (post.status, &post.suppressor) {
+        (rmb::PostStatus::Visible, _) if post.likes > 0 => format!(" ({} likes)", post.likes),
+        (rmb::PostStatus::Visible, _) => String::new(),
+        (rmb::PostStatus::Suppressed, Some(by)) => format!(" (suppressed by {})", by),
+        (status, _) => format!(" ({})", status),
+    };
+    match post.status {
+        rmb::PostStatus::Visible | rmb::PostStatus::Suppressed => println!("[{}] {}{}: {}", time, post.nation, note, render_text(&post.message)),
+        _ => println!("[{}] {}{}", time, post.nation, note),
+    }
+}
+
+/// Print recent happenings, newest first. With `follow`, print them oldest first instead
+/// and keep polling for more.
+async fn show_happenings(client: &reqwest::Client, view: Option<View>, only: &[happenings::Category], limit: Option<u32>,
+                         follow: bool, interval: u64, output: Stream) -> anyhow::Result<()> {
+    // The API can't filter for unrecognized events,
+    // so leave filtering entirely to us in that case.
+    let filter = if only.contains(&happenings::Category::Other) { Vec::new() } else { only.to_vec() };
+    let mut last = None;
+    loop {
+        let shards = vec![api::Shard::Happenings { view: view.clone(), filter: filter.clone(), limit }];
+        let data = fetch(client, api::Target::World, shards).await?;
+        let mut events = shard!(data, Happenings).unwrap_or_default().events;
+        if follow {
+            events.sort_by_key(|x| x.id);
+        }
+        for event in &events {
+            let category = event.kind().category();
+            if last.is_some_and(|id| event.id <= id) || (!only.is_empty() && !only.contains(&category)) {
+                continue;
+            }
+            match output {
+                Stream::Text => println!("{} [{}] {}", event.timestamp.format("%Y-%m-%d %H:%M:%S"), category, event.plain_text()),
+                Stream::Ndjson => StreamEvent::Happening {
+                    id: event.id,
+                    time: event.timestamp,
+                    category: category.to_string(),
+                    text: event.plain_text(),
+                    raw: &event.text,
+                }.print(),
+            }
+        }
+        last = events.iter().map(|x| x.id).chain(last).max();
+        if !follow {
+            return Ok(());
+        }
+        idle(std::time::Duration::from_secs(interval.max(10))).await;
+        if interrupted() {
+            return Ok(());
+        }
+    }
+}
+
+/// Print the latest `limit` posts on a region's message board, oldest first,
+/// and keep polling for more if `follow` is set.
+async fn rmb_read(client: &reqwest::Client, region: &str, limit: u32, follow: bool, interval: u64,
+                  output: Stream) -> anyhow::Result<()> {
+    let mut posts = Vec::new();
+    let mut pages = api::Paginated::messages(client, region, api_version());
+    while posts.len() < limit as usize && !interrupted() {
+        match pages.next().await {
+            Some(post) => posts.push(post?),
+            None => break,
+        }
+    }
+    posts.reverse();
+    for post in &posts {
+        print_post(post, region, output);
+    }
+    if !follow {
+        return Ok(());
+    }
+    let mut last = posts.last().map(|post| post.id);
+    loop {
+        idle(std::time::Duration::from_secs(interval.max(10))).await;
+        if interrupted() {
+            return Ok(());
+        }
+        let data = fetch(client, api::Target::Region(region), vec![api::Shard::Messages {
+            limit: Some(100),
+            offset: None,
+            from_id: last.map(|id| id + 1),
+        }]).await?;
+        for post in shard!(data, Messages).unwrap_or_default().posts {
+            if last.is_none_or(|id| post.id > id) {
+                print_post(&post, region, output);
+                last = Some(post.id);
+            }
+        }
+    }
+}
+
+/// Split `names` into the ones nobody has, and the ones taken by a nation.
+/// With `offline` this goes by the latest nations dump, which is up to a day old.
+/// Nations that ceased to exist recently can still be restored by their owners,
+/// so a name counted as available may not stay that way.
+async fn split_taken(client: &reqwest::Client, names: &[String], offline: bool)
+                     -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let mut available = Vec::new();
+    let mut taken = Vec::new();
+    if offline {
+        let index = Index::open(&index_path())?;
+        let day = match index.latest_day(dump::Kind::Nations)? {
+            Some(x) => x,
+            None => anyhow::bail!("No nations dump in the index; see `nation dump download`."),
+        };
+        for name in names {
+            if index.has_nation(day, name)? {
+                taken.push(name.clone());
+            } else {
+                available.push(name.clone());
+            }
+        }
+        return Ok((available, taken));
+    }
+    for (i, name) in names.iter().enumerate() {
+        if interrupted() {
+            break;
+        }
+        eprint!("\r{}/{} names checked", i + 1, names.len());
+        match fetch(client, api::Target::Nation(name), vec![api::Shard::Name]).await {
+            Ok(_) => taken.push(name.clone()),
+            Err(e) if is_not_found(&e) => available.push(name.clone()),
+            Err(e) => return Err(e),
+        }
+    }
+    eprintln!();
+    Ok((available, taken))
+}
+
+/// Fail unless `nation` holds `power` in `region`.
+async fn require_power(client: &reqwest::Client, nation: &str, region: &str, power: Power) -> anyhow::Result<()> {
+    let data = fetch(client, api::Target::Region(region), region::Leadership::shards()).await?;
+    let authority = region::Leadership::from_shards(data.inner).authority(nation);
+    if !authority.has(power) {
+        anyhow::bail!("{} doesn't have {} authority in {} (has {})", nation, power, region, authority);
+    }
+    Ok(())
+}
+
+/// Check that `nation` can make these tag changes in `region`,
+/// returning the ones that would change anything.
+async fn check_tags(client: &reqwest::Client, nation: &str, region: &str, add: &[String], remove: &[String])
+                    -> anyhow::Result<(Vec<&'static str>, Vec<&'static str>)> {
+    let known = |tags: &[String]| tags.iter()
+        .map(|tag| match region::known_tag(tag) {
+            Some(x) if region::AUTOMATIC_TAGS.contains(&x) => anyhow::bail!("{} is set automatically, not by officers", x),
+            Some(x) => Ok(x),
+            None => anyhow::bail!("unknown tag {:?}", tag),
+        })
+        .collect::<anyhow::Result<Vec<_>>>();
+    let (add, remove) = (known(add)?, known(remove)?);
+    if let Some(tag) = add.iter().find(|x| remove.contains(x)) {
+        anyhow::bail!("{} is both added and removed", tag);
+    }
+    let mut shards = region::Leadership::shards();
+    shards.push(api::Shard::Tags);
+    let data = fetch(client, api::Target::Region(region), shards).await?;
+    let mut tags = region::Tags::default();
+    let leadership = region::Leadership::from_shards(data.inner.into_iter().filter_map(|shard| match shard {
+        api::ResolvedShard::Tags(x) => {
+            tags = x;
+            None
+        },
+        shard => Some(shard),
+    }));
+    let authority = leadership.authority(nation);
+    if !authority.has(Power::Appearance) {
+        anyhow::bail!("{} doesn't have {} authority in {} (has {})", nation, Power::Appearance, region, authority);
+    }
+    for tag in add.iter().filter(|x| tags.contains(x)) {
+        eprintln!("{} already has {}", region, tag);
+    }
+    for tag in remove.iter().filter(|x| !tags.contains(x)) {
+        eprintln!("{} doesn't have {}", region, tag);
+    }
+    Ok((add.into_iter().filter(|x| !tags.contains(x)).collect(),
+        remove.into_iter().filter(|x| tags.contains(x)).collect()))
+}
+
+/// Check that `nation` can take `action` on the embassy between `region` and `target`.
+async fn check_embassy(client: &reqwest::Client, action: EmbassyAction, nation: &str, region: &str, target: &str)
+                       -> anyhow::Result<()> {
+    if nation::canonical_name(region) == nation::canonical_name(target) {
+        anyhow::bail!("a region can't have an embassy with itself");
+    }
+    let mut shards = region::Leadership::shards();
+    shards.push(api::Shard::Embassies);
+    let data = fetch(client, api::Target::Region(region), shards).await?;
+    let mut embassies = region::Embassies::default();
+    let leadership = region::Leadership::from_shards(data.inner.into_iter().filter_map(|shard| match shard {
+        api::ResolvedShard::Embassies(x) => {
+            embassies = x;
+            None
+        },
+        shard => Some(shard),
+    }));
+    let authority = leadership.authority(nation);
+    if !authority.has(Power::Embassies) {
+        anyhow::bail!("{} doesn't have {} authority in {} (has {})", nation, Power::Embassies, region, authority);
+    }
+    let status = embassies.status(target);
+    if !action.allowed(status) {
+        match status {
+            Some(status) => anyhow::bail!("can't {} the embassy between {} and {}: it's {}", action, region, target, status),
+            None => anyhow::bail!("can't {} an embassy between {} and {}: there isn't one", action, region, target),
+        }
+    }
+    Ok(())
+}
+
+/// Check that `nation` can change the password of `region`, and return whether it has one.
+async fn check_region_password(client: &reqwest::Client, nation: &str, region: &str) -> anyhow::Result<bool> {
+    let mut shards = region::Leadership::shards();
+    shards.push(api::Shard::Tags);
+    let data = fetch(client, api::Target::Region(region), shards).await?;
+    let mut tags = region::Tags::default();
+    let leadership = region::Leadership::from_shards(data.inner.into_iter().filter_map(|shard| match shard {
+        api::ResolvedShard::Tags(x) => {
+            tags = x;
+            None
+        },
+        shard => Some(shard),
+    }));
+    let authority = leadership.authority(nation);
+    if !authority.has(Power::Executive) {
+        anyhow::bail!("{} doesn't have {} authority in {} (has {})", nation, Power::Executive, region, authority);
+    }
+    // The site tags passworded regions itself.
+    Ok(tags.contains("Password"))
+}
+
+/// A nation's region, influence level, and influence score.
+async fn fetch_influence(client: &reqwest::Client, nation: &str) -> anyhow::Result<(String, String, f64)> {
+    let shards = ShardSet::new().region().influence().census([CensusScale::Influence]).build()?;
+    let data = fetch(client, api::Target::Nation(nation), shards).await?;
+    let (mut region, mut level, mut score) = (None, None, None);
+    for shard in data.inner {
+        match shard {
+            api::ResolvedShard::Region(x) => region = Some(x),
+            api::ResolvedShard::Influence(x) => level = Some(x),
+            api::ResolvedShard::Census(x) => score = x.scales.into_iter().find_map(|x| x.score),
+            _ => (),
+        }
+    }
+    match (region, level, score) {
+        (Some(region), Some(level), Some(score)) => Ok((region, level, score)),
+        _ => anyhow::bail!("No influence in the response for {}.", nation),
+    }
+}
+
+/// Check that an officer can `action` ("eject" or "ban") a nation, and print both nations' influence.
+/// Ejecting and banning cost the officer influence, more for targets with more of it;
+/// the site shows the exact cost before going ahead.
+async fn remove_nation(client: &reqwest::Client, action: &str, args: BorderArgs) -> anyhow::Result<()> {
+    let BorderArgs { nation, region, target } = args;
+    let (nation, region, target) = (nation.as_str(), region.as_str(), target.as_str());
+    if nation::canonical_name(nation) == nation::canonical_name(target) {
+        anyhow::bail!("{} can't remove itself from {}", nation, region);
+    }
+    let data = fetch(client, api::Target::Region(region), region::Leadership::shards()).await?;
+    let leadership = region::Leadership::from_shards(data.inner);
+    let authority = leadership.authority(nation);
+    if !authority.has(Power::BorderControl) {
+        anyhow::bail!("{} doesn't have {} authority in {} (has {})", nation, Power::BorderControl, region, authority);
+    }
+    if leadership.founder.as_deref().is_some_and(|x| nation::canonical_name(x) == nation::canonical_name(target)) {
+        anyhow::bail!("{} founded {} and can't be removed from it", target, region);
+    }
+    let (home, target_level, target_score) = fetch_influence(client, target).await?;
+    if nation::canonical_name(&home) != nation::canonical_name(region) {
+        anyhow::bail!("{} lives in {}, not {}", target, home, region);
+    }
+    let (_, level, score) = fetch_influence(client, nation).await?;
+    println!("{}: {}, {:.0} influence", target, target_level, target_score);
+    println!("{}: {}, {:.0} influence", nation, level, score);
+    if target_score > score {
+        println!("{}", term::warn(format!("{} has more influence than {}; check the cost on the site first.", target, nation)));
+    }
+    println!("To {} {}, do it from its page on the site: https://www.nationstates.net/nation={}",
+             action, target, nation::canonical_name(target));
+    Ok(())
+}
+
+/// The lines of `old` and `new`, marked `-` if only in `old`, `+` if only in `new`,
+/// and ` ` if in both.
+fn line_diff<'a>(old: &'a str, new: &'a str) -> Vec<(char, &'a str)> {
+    let (old, new): (Vec<_>, Vec<_>) = (old.lines().collect(), new.lines().collect());
+    // Length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            diff.push(('+', new[j]));
+            j += 1;
+        } else {
+            diff.push(('-', old[i]));
+            i += 1;
+        }
+    }
+    diff
+}
+
+/// Check that `nation` can change the World Factbook Entry of `region`,
+/// and print how `new` differs from the current one. Returns whether it does.
+async fn preview_wfe(client: &reqwest::Client, nation: &str, region: &str, new: &str) -> anyhow::Result<bool> {
+    let mut shards = region::Leadership::shards();
+    shards.push(api::Shard::Factbook);
+    let data = fetch(client, api::Target::Region(region), shards).await?;
+    let mut current = String::new();
+    let leadership = region::Leadership::from_shards(data.inner.into_iter().filter_map(|shard| match shard {
+        api::ResolvedShard::Factbook(x) => {
+            current = x;
+            None
+        },
+        shard => Some(shard),
+    }));
+    let authority = leadership.authority(nation);
+    if !authority.has(Power::Appearance) {
+        anyhow::bail!("{} doesn't have {} authority in {} (has {})", nation, Power::Appearance, region, authority);
+    }
+    let current = current.replace("\r\n", "\n");
+    if current.trim_end() == new.trim_end() {
+        return Ok(false);
+    }
+    println!("{}", term::bad(format!("--- current WFE of {}", region)));
+    println!("{}", term::good("+++ new WFE"));
+    for (mark, line) in line_diff(current.trim_end(), new.trim_end()) {
+        match mark {
+            '-' => println!("{}", term::bad(format!("-{}", line))),
+            '+' => println!("{}", term::good(format!("+{}", line))),
+            _ => println!(" {}", line),
+        }
+    }
+    Ok(true)
+}
+
+/// The top `limit` nations on `scale`, fetching `censusranks` a page at a time.
+async fn top(client: &reqwest::Client, scale: CensusScale, region: Option<&str>, limit: u32) -> anyhow::Result<Table> {
+    let target = match region {
+        Some(region) => api::Target::Region(region),
+        None => api::Target::World,
+    };
+    let mut table = Table {
+        columns: ["rank", "nation", "score"].iter().map(|&x| x.into()).collect(),
+        rows: Vec::new(),
+    };
+    let mut ranks = api::Paginated::census_ranks(client, target, scale, api_version());
+    while table.rows.len() < limit as usize && !interrupted() {
+        let rank = match ranks.next().await {
+            Some(x) => x?,
+            None => break,
+        };
+        table.rows.push(vec![rank.rank.to_string(), rank.name, rank.score.to_string()]);
+    }
+    Ok(table)
+}
+
+/// Send recruitment telegrams to new nations until interrupted.
+async fn recruit(client: &reqwest::Client, region: &str, template: &Template, client_key: &str,
+                 exclude_file: Option<&std::path::Path>, poll: u64) -> anyhow::Result<()> {
+    use std::collections::HashSet;
+    use std::io::Write;
+    use std::time::Instant;
+    let mut excluded: HashSet<String> = match exclude_file {
+        Some(path) => match std::fs::read_to_string(path) {
+            Ok(text) => text.lines().filter(|x| !x.trim().is_empty()).map(nation::canonical_name).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e.into()),
+        },
+        None => HashSet::new(),
+    };
+    let mut record = match exclude_file {
+        Some(path) => Some(std::fs::OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+    let mut last_sent: Option<Instant> = None;
+    'poll: loop {
+        let data = fetch(client, api::Target::World, vec![api::Shard::NewNations]).await?;
+        let new_nations = shard!(data, NewNations).unwrap_or_default();
+        for recipient in new_nations {
+            if !excluded.insert(recipient.clone()) {
+                continue;
+            }
+            let shards = vec![api::Shard::TgCanRecruit { from: Some(region.to_owned()) }];
+            let data = fetch(client, api::Target::Nation(&recipient), shards).await?;
+            let can_recruit = data.inner.iter().any(|shard| matches!(shard, api::ResolvedShard::TgCanRecruit(1)));
+            if !can_recruit {
+                println!("Skipping {}: not accepting recruitment telegrams", recipient);
+                continue;
+            }
+            if let Some(last) = last_sent {
+                let elapsed = last.elapsed();
+                if elapsed < template.interval() {
+                    metrics::throttle(template.interval() - elapsed).await;
+                }
+            }
+            if interrupted() {
+                return Ok(());
+            }
+            let tg = Telegram { client_key, template, to: &recipient };
+            last_sent = Some(Instant::now());
+            if dry_run() {
+                println!("Would send:\n{}", tg.preview());
+                continue 'poll;
+            }
+            if let Err(e) = tg.send(client).await {
+                return Err(anyhow::Error::new(e).context(format!("Sending to {} failed", recipient)));
+            }
+            if let Some(file) = &mut record {
+                writeln!(file, "{}", recipient)?;
+            }
+            println!("[{}] Sent {} to {}", Utc::now().format("%Y-%m-%d %H:%M:%S"), template, recipient);
+            // Nations founded while we waited out the rate limit
+            // are better targets than the rest of this batch.
+            continue 'poll;
+        }
+        idle(std::time::Duration::from_secs(poll)).await;
+        if interrupted() {
+            return Ok(());
+        }
+    }
+}
+
+/// Telegram nations recently founded in or moved to `region` that haven't
+/// been welcomed yet, going by the happenings. Keeps at it if `daemon` is set.
+async fn welcome(client: &reqwest::Client, region: &str, template: &Template, client_key: &str,
+                 daemon: bool, poll: u64) -> anyhow::Result<()> {
+    use std::time::Instant;
+    let index = Index::open(&index_path())?;
+    let here = nation::canonical_name(region);
+    let mut last_sent: Option<Instant> = None;
+    loop {
+        let shards = vec![api::Shard::Happenings {
+            view: Some(View::Region(here.clone())),
+            filter: vec![happenings::Category::Move, happenings::Category::Founding],
+            limit: None,
+        }];
+        let data = fetch(client, api::Target::World, shards).await?;
+        let mut events = shard!(data, Happenings).unwrap_or_default().events;
+        // Oldest first, so nations are welcomed in the order they arrived.
+        events.sort_by_key(|x| x.id);
+        let arrivals = events.iter().filter_map(|event| match event.kind() {
+            EventKind::Move { nation, to, .. } if to == here => Some(nation),
+            EventKind::Founding { nation, region, .. } if region == here => Some(nation),
+            _ => None,
+        });
+        for recipient in arrivals.unique() {
+            if index.welcomed(region, &recipient)? {
+                continue;
+            }
+            if let Some(last) = last_sent {
+                let elapsed = last.elapsed();
+                if elapsed < template.interval() {
+                    metrics::throttle(template.interval() - elapsed).await;
+                }
+            }
+            if interrupted() {
+                return Ok(());
+            }
+            let tg = Telegram { client_key, template, to: &recipient };
+            last_sent = Some(Instant::now());
+            if dry_run() {
+                println!("Would send:\n{}", tg.preview());
+                continue;
+            }
+            if let Err(e) = tg.send(client).await {
+                return Err(anyhow::Error::new(e).context(format!("Sending to {} failed", recipient)));
+            }
+            index.record_welcome(region, &recipient)?;
+            println!("[{}] Sent {} to {}", Utc::now().format("%Y-%m-%d %H:%M:%S"), template, recipient);
+        }
+        if !daemon {
+            return Ok(());
+        }
+        idle(std::time::Duration::from_secs(poll)).await;
+        if interrupted() {
+            return Ok(());
+        }
+    }
+}
+
+/// WA members residing in `region`, other than `nation`, each with
+/// the endorsements it has, which `nation` may or may not be among.
+async fn endorsements_in(client: &reqwest::Client, nation: &str, region: &str) -> anyhow::Result<Vec<(String, Vec<String>)>> {
+    use std::collections::HashSet;
+    let data = fetch(client, api::Target::Region(region), vec![api::Shard::Nations]).await?;
+    let residents = shard!(data, Nations).unwrap_or_default();
+    let data = fetch(client, api::Target::Wa(Council::General), vec![api::Shard::Members]).await?;
+    let members: HashSet<String> = shard!(data, Members).unwrap_or_default().into_iter().collect();
+    let me = nation::canonical_name(nation);
+    if !members.contains(&me) {
+        anyhow::bail!("{} isn't in the World Assembly", nation);
+    }
+    let candidates: Vec<_> = residents.into_iter().filter(|x| x != &me && members.contains(x)).collect();
+    let mut endorsements = Vec::new();
+    for (i, candidate) in candidates.iter().enumerate() {
+        if interrupted() {
+            break;
+        }
+        eprint!("\r{}/{} members checked", i + 1, candidates.len());
+        match fetch(client, api::Target::Nation(candidate), vec![api::Shard::Endorsements]).await {
+            Ok(data) => endorsements.push((candidate.clone(), shard!(data, Endorsements).unwrap_or_default())),
+            // Nations can move out or cease to exist partway through.
+            Err(e) if is_not_found(&e) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    eprintln!();
+    if endorsements.len() < candidates.len() && !interrupted() {
+        eprintln!("Skipped {} of {} members", candidates.len() - endorsements.len(), candidates.len());
+    }
+    Ok(endorsements)
+}
+
+/// Show what withdrawing `nation`'s endorsements of `targets` would do,
+/// given the endorsements each has now.
+fn print_withdrawals(nation: &str, targets: &[(String, Vec<String>)], delegate: Option<&str>) {
+    for (target, endorsements) in targets {
+        let note = if delegate == Some(target.as_str()) { ", the delegate, so one WA vote fewer" } else { "" };
+        println!("{}: {} -> {} endorsements{}", target, endorsements.len(), endorsements.len() - 1, note);
+    }
+    // Influence comes from endorsements received, not given.
+    println!("Withdrawing doesn't cost {} influence, but each of these nations gains influence \
+              more slowly with one endorsement fewer.", nation);
+}
+
+/// Compare a region's residents against the last snapshot taken,
+/// then save a new snapshot.
+async fn watch_region(client: &reqwest::Client, region: &str) -> anyhow::Result<()> {
+    let path = data_dir().join("regions").join(format!("{}.xml", nation::canonical_name(region)));
+    let data = fetch(client, api::Target::Region(region), vec![api::Shard::Nations]).await?;
+    let current = Snapshot::new(region, shard!(data, Nations).unwrap_or_default());
+    let now = current.timestamp.format("%Y-%m-%d %H:%M:%S");
+    match Snapshot::load(&path)? {
+        None => println!("[{}] {}: first snapshot, {} nations", now, region, current.nations.len()),
+        Some(previous) => {
+            let changes = previous.changes(&current);
+            if changes.is_empty() {
+                println!("[{}] {}: no changes, {} nations", now, region, current.nations.len());
+            }
+            for nation in &changes.arrivals {
+                println!("[{}] {}: {} arrived", now, region, nation);
+            }
+            for nation in &changes.departures {
+                // Nations that ceased to exist are gone from the API entirely.
+                let departure = match fetch(client, api::Target::Nation(nation), vec![api::Shard::Name]).await {
+                    Ok(_) => "departed",
+                    Err(e) if is_not_found(&e) => "ceased to exist",
+                    Err(e) => return Err(e),
+                };
+                println!("[{}] {}: {} {}", now, region, nation, departure);
+            }
+        },
+    }
+    current.save(&path)?;
+    Ok(())
+}
+
+/// Print a security report for a region:
+/// who holds which powers, and how far ahead of the pack the delegate is.
+async fn audit_region(client: &reqwest::Client, region: &str) -> anyhow::Result<()> {
+    let shards = vec![
+        api::Shard::Founder,
+        api::Shard::FounderAuth,
+        api::Shard::Delegate,
+        api::Shard::DelegateAuth,
+        api::Shard::DelegateVoteCount,
+        api::Shard::Officers,
+        api::Shard::Tags,
+        // The first page of the ranking is enough to find the runner-up.
+        api::Shard::CensusRanks { scale: CensusScale::WorldAssemblyEndorsements, start: None },
+    ];
+    let data = fetch(client, api::Target::Region(region), shards).await?;
+    let mut founder = None;
+    let mut founder_auth = Authority::default();
+    let mut delegate = None;
+    let mut delegate_auth = Authority::default();
+    let mut delegate_votes = 0;
+    let mut officers = Vec::new();
+    let mut password = false;
+    let mut ranks = Vec::new();
+    for shard in data.inner {
+        match shard {
+            api::ResolvedShard::Founder(x) => founder = region::officeholder(x),
+            api::ResolvedShard::FounderAuth(x) => founder_auth = x,
+            api::ResolvedShard::Delegate(x) => delegate = region::officeholder(x),
+            api::ResolvedShard::DelegateAuth(x) => delegate_auth = x,
+            api::ResolvedShard::DelegateVoteCount(x) => delegate_votes = x,
+            api::ResolvedShard::Officers(x) => officers = x.inner,
+            api::ResolvedShard::Tags(x) => password = x.contains("password"),
+            api::ResolvedShard::CensusRanks(x) => ranks = x.nations.inner,
+            _ => (),
+        }
+    }
+
+    let mut findings = Vec::new();
+    println!("{}", region);
+    match &founder {
+        Some(name) => println!("    founder:   {} ({})", name, founder_auth),
+        None => {
+            println!("    founder:   none");
+            findings.push("region is founderless".to_owned());
+        },
+    }
+    match &delegate {
+        Some(name) => {
+            // Delegate votes count the delegate's own vote on top of their endorsements.
+            let endorsements = delegate_votes.saturating_sub(1);
+            println!("    delegate:  {} ({}), {} endorsements", name, delegate_auth, endorsements);
+            let runner_up = ranks.iter().find(|x| nation::canonical_name(&x.name) != nation::canonical_name(name));
+            let power = if delegate_auth.has(Power::Executive) { "has" } else { "does not have" };
+            match runner_up {
+                Some(next) => {
+                    let lead = endorsements as i64 - next.score as i64;
+                    println!("    runner-up: {}, {} endorsements", next.name, next.score as u64);
+                    findings.push(format!("delegate {} executive power and is {} endorsements {} the next nation",
+                                          power, lead.abs(), if lead >= 0 { "above" } else { "below" }));
+                },
+                None => findings.push(format!("delegate {} executive power and has no competition", power)),
+            }
+        },
+        None => {
+            println!("    delegate:  none");
+            findings.push("region has no delegate".to_owned());
+        },
+    }
+    if !officers.is_empty() {
+        println!("    officers:");
+    }
+    for officer in &officers {
+        println!("        {} \"{}\" ({})", officer.nation, officer.office, officer.authority);
+    }
+    let border_control: Vec<&str> = officers.iter()
+        .filter(|x| x.authority.has(Power::BorderControl))
+        .map(|x| x.nation.as_str())
+        .collect();
+    if !border_control.is_empty() {
+        findings.push(format!("{} officer(s) can eject and ban: {}", border_control.len(), border_control.join(", ")));
+    }
+    println!("    password:  {}", if password { "yes" } else { "no" });
+    if !password {
+        findings.push("region has no password".to_owned());
+    }
+    println!("findings:");
+    for finding in findings {
+        println!("    - {}", finding);
+    }
+    Ok(())
+}
+
+/// Fetch one scale for each nation and print them ranked, best first.
+/// Nations that can't be fetched are left out and counted as failures.
+async fn census_report(client: &reqwest::Client, names: &[String], scale: CensusScale, output: Output) -> anyhow::Result<()> {
+    let mut scores = Vec::new();
+    let mut failed = 0;
+    for name in names {
+        if interrupted() {
+            break;
+        }
+        let shards = ShardSet::new().census([scale])
+            .mode(api::CensusMode::Score).mode(api::CensusMode::Rank).mode(api::CensusMode::PercentRank)
+            .build()?;
+        match fetch(client, api::Target::Nation(name), shards).await {
+            Ok(data) => match shard!(data, Census).and_then(|x| x.scales.into_iter().next()) {
+                Some(score) => scores.push((name, score)),
+                None => {
+                    eprintln!("{}: no {} score in the response", name, scale);
+                    failed += 1;
+                },
+            },
+            Err(e) => {
+                eprintln!("{}: {}", name, e);
+                failed += 1;
+            },
+        }
+    }
+    // Highest first, the way the world ranks go.
+    scores.sort_by(|a, b| b.1.score.partial_cmp(&a.1.score).unwrap_or(std::cmp::Ordering::Equal));
+    let show = |x: Option<String>| x.unwrap_or_default();
+    let table = Table {
+        columns: ["#", "Nation", "Score", "World", "Percentile"].iter().map(|&x| x.into()).collect(),
+        rows: scores.iter().enumerate()
+            .map(|(i, (name, score))| vec![
+                (i + 1).to_string(),
+                name.to_string(),
+                show(score.score.map(|x| x.to_string())),
+                show(score.rank.map(|x| x.to_string())),
+                show(score.percent_rank.map(|x| format!("top {:.2}%", x))),
+            ])
+            .collect(),
+    };
+    print_table(&table, output);
+    if failed > 0 {
+        return Err(PartialFailure(format!("{} of {} nations couldn't be fetched.", failed, names.len())).into());
+    }
+    Ok(())
+}
+
+/// Record each nation's influence and print it, with how it's been
+/// growing over the last month and, if asked, when it reaches each target.
+async fn track_influence(client: &reqwest::Client, names: &[String], project: bool, targets: &[f64]) -> anyhow::Result<()> {
+    let index = Index::open(&index_path())?;
+    for name in names {
+        if interrupted() {
+            break;
+        }
+        let shards = ShardSet::new().influence().census([CensusScale::Influence]).build()?;
+        let data = fetch(client, api::Target::Nation(name), shards).await?;
+        let mut level = None;
+        let mut score = None;
+        for shard in data.inner {
+            match shard {
+                api::ResolvedShard::Influence(x) => level = Some(x),
+                api::ResolvedShard::Census(x) => score = x.scales.into_iter().find_map(|x| x.score),
+                _ => (),
+            }
+        }
+        let (level, score) = match (level, score) {
+            (Some(level), Some(score)) => (level, score),
+            _ => anyhow::bail!("No influence in the response for {}.", name),
+        };
+        if !dry_run() {
+            index.record_influence(name, &level, score)?;
+        }
+        let history = index.influence_history(name, Utc::now() - chrono::Duration::days(30))?;
+        let trend = region::InfluenceTrend::from_history(&history);
+        match trend {
+            Some(trend) => println!("{}: {}, {:.0} influence ({:+.1} a day)", name, level, score, trend.per_day),
+            None => println!("{}: {}, {:.0} influence (not enough history for a trend yet)", name, level, score),
+        }
+        if !project {
+            continue;
+        }
+        for &target in targets {
+            let when = match trend.map(|x| x.reaches(target)) {
+                _ if score >= target => "already there".to_owned(),
+                Some(Some(when)) => {
+                    format!("around {} ({} days)", when.format("%Y-%m-%d"), (when - Utc::now()).num_days().max(0))
+                },
+                Some(None) => "not at the current rate".to_owned(),
+                None => "unknown until there's more history".to_owned(),
+            };
+            println!("    {:.0}: {}", target, when);
+        }
+    }
+    Ok(())
+}
+
+/// Founding and login times for everyone in a region, from the cache where
+/// it's fresh enough and one request per nation otherwise.
+async fn residency_table(client: &reqwest::Client, region: &str, inactive: Option<Age>, Age(max_age): Age)
+                         -> anyhow::Result<Table> {
+    let data = fetch(client, api::Target::Region(region), vec![api::Shard::Nations]).await?;
+    let residents = shard!(data, Nations).unwrap_or_default();
+    let index = Index::open(&index_path())?;
+    let mut residencies = Vec::new();
+    for (i, nation) in residents.iter().enumerate() {
+        if interrupted() {
+            break;
+        }
+        eprint!("\r{}/{} residents checked", i + 1, residents.len());
+        let residency = match index.cached_residency(nation, max_age)? {
+            Some(x) => x,
+            None => {
+                let data = fetch(client, api::Target::Nation(nation), Residency::shards()).await?;
+                let residency = Residency::from_shards(nation, data.inner);
+                index.cache_residency(&residency)?;
+                residency
+            },
+        };
+        residencies.push(residency);
+    }
+    eprintln!();
+    let now = Utc::now();
+    let days = |x: Option<chrono::DateTime<Utc>>| x.map(|x| now.signed_duration_since(x).num_days());
+    if let Some(Age(inactive)) = inactive {
+        residencies.retain(|x| x.last_login.is_none_or(|x| now.signed_duration_since(x) >= inactive));
+    }
+    // Never logged in sorts first, as the least active of all.
+    residencies.sort_by_key(|x| x.last_login);
+    let date = |x: Option<chrono::DateTime<Utc>>| x.map_or_else(|| "unknown".to_owned(), |x| x.format("%Y-%m-%d").to_string());
+    let number = |x: Option<i64>| x.map_or_else(String::new, |x| x.to_string());
+    Ok(Table {
+        columns: ["nation", "founded", "age (days)", "last login", "inactive (days)"].iter().map(|&x| x.into()).collect(),
+        rows: residencies.iter()
+            .map(|x| vec![
+                x.nation.clone(), date(x.founded), number(days(x.founded)),
+                date(x.last_login), number(days(x.last_login)),
+            ])
+            .collect(),
+    })
+}
+
+/// Estimate a deck's value from junk values, market values, and recent trades.
+async fn value_deck(client: &reqwest::Client, nation: &str, lookups: usize, output: Output) -> anyhow::Result<()> {
+    // Card prices don't move quickly, and every lookup is a request.
+    let max_age = chrono::Duration::hours(12);
+    let data = fetch(client, api::Target::World, vec![api::Shard::Deck { nation: nation.to_owned() }]).await?;
+    let mut cards = shard!(data, Deck).unwrap_or_default().cards;
+    cards.sort_by(|a, b| b.market_value.partial_cmp(&a.market_value).unwrap_or(std::cmp::Ordering::Equal));
+    let junk: f64 = cards.iter().filter_map(|x| x.rarity()).map(Rarity::junk_value).sum();
+    let market: f64 = cards.iter().filter_map(|x| x.market_value).sum();
+    let index = Index::open(&index_path())?;
+    let mut estimate = market;
+    let mut table = Table {
+        columns: ["season", "id", "rarity", "market value", "last sold"].iter().map(|&x| x.into()).collect(),
+        rows: Vec::new(),
+    };
+    if output == Output::Text {
+        println!("{}: {} cards", nation, cards.len());
+    }
+    for card in cards.iter().take(lookups) {
+        let price = match index.cached_price(card.id, card.season, max_age)? {
+            Some(price) => price,
+            None => {
+                let shards = vec![api::Shard::CardTrades { id: card.id, season: card.season }];
+                let data = fetch(client, api::Target::World, shards).await?;
+                let price = shard!(data, Trades).unwrap_or_default().last_price();
+                index.cache_price(card.id, card.season, price)?;
+                price
+            },
+        };
+        let market_value = card.market_value.unwrap_or_default();
+        if let Some(price) = price {
+            estimate += price - market_value;
+        }
+        if output == Output::Text {
+            println!("    S{} #{:<8} {:<10} market {:>8.2}  last sold {}", card.season, card.id, card.category, market_value,
+                     price.map_or_else(|| "never".to_owned(), |x| format!("{:.2}", x)));
+        } else {
+            table.rows.push(vec![
+                card.season.to_string(), card.id.to_string(), card.category.clone(),
+                format!("{:.2}", market_value), price.map_or_else(String::new, |x| format!("{:.2}", x)),
+            ]);
+        }
+    }
+    if output != Output::Text {
+        print_table(&table, output);
+        return Ok(());
+    }
+    println!("junk value:   {:.2}", junk);
+    println!("market value: {:.2}", market);
+    println!("estimate:     {:.2} (market value, with recent trade prices for the top {})", estimate, lookups.min(cards.len()));
+    Ok(())
+}
+
+/// Poll a card's market until its lowest ask drops to `max_bid`.
+async fn watch_card(client: &reqwest::Client, id: u64, season: u8, max_bid: f64, interval: u64,
+                    notifier: &Notifier) -> anyhow::Result<()> {
+    // The cards API shares the usual rate limit, so don't let the interval get silly.
+    let interval = std::time::Duration::from_secs(interval.max(10));
+    loop {
+        let data = fetch(client, api::Target::World, vec![api::Shard::CardMarkets { id, season }]).await?;
+        let markets = shard!(data, Markets).unwrap_or_default();
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S");
+        let price = |x: Option<&cards::Offer>| x.map_or_else(|| "none".to_owned(), |x| format!("{:.2}", x.price));
+        println!("[{}] S{} #{}: lowest ask {}, highest bid {}",
+                 now, season, id, price(markets.lowest_ask()), price(markets.highest_bid()));
+        if let Some(ask) = markets.lowest_ask().filter(|x| x.price <= max_bid) {
+            let body = format!("{} is asking {:.2} for S{} #{}, at or below your {:.2}. Buy it at {}",
+                               ask.nation, ask.price, season, id, max_bid, cards::card_page(id, season));
+            notifier.send(&Notification::new("Card available", body))?;
+            return Ok(());
+        }
+        idle(interval).await;
+        if interrupted() {
+            return Ok(());
+        }
+    }
+}
+
+/// Fetch a nation's pending issues, keeping any new credentials.
+async fn fetch_issues(client: &reqwest::Client, nation: &mut nation::Nation) -> anyhow::Result<Vec<Issue>> {
+    let data = session(client, nation).request(vec![api::Shard::Issues]).await;
+    warn_cut_short(nation);
+    Ok(shard!(data?.data, Issues).unwrap_or_default().inner)
+}
+
+/// Answer pending issues by a policy, logging each decision to the local store.
+async fn auto_answer(client: &reqwest::Client, nation: &mut nation::Nation, policy: &Policy,
+                     notifier: Option<&Notifier>) -> anyhow::Result<()> {
+    let mut index = Index::open(&index_path())?;
+    let issues = fetch_issues(client, nation).await?;
+    if issues.is_empty() {
+        println!("{} has no pending issues.", nation.name);
+    }
+    for issue in issues {
+        if interrupted() {
+            break;
+        }
+        let decision = policy.decide(&issue, |issue, option, scale| {
+            index.average_effect(issue, option, scale.id()).ok().flatten()
+        });
+        let (option, reason) = match decision {
+            Decision::Skip { reason } => {
+                println!("#{} {}: skipped ({})", issue.id, issue.title, reason);
+                if !dry_run() {
+                    index.record_decision(&nation.name, issue.id, None, &reason)?;
+                }
+                continue;
+            },
+            Decision::Answer { option, reason } => (option, reason),
+        };
+        let label = match option {
+            issues::DISMISS => "dismiss".to_owned(),
+            _ => format!("option {}", option),
+        };
+        println!("#{} {}: {} ({})", issue.id, issue.title, label, reason);
+        let command = api::Command {
+            nation: &nation.name,
+            auth: &nation.auth,
+            action: api::Action::AnswerIssue { issue: issue.id, option },
+            version: api_version(),
+            readonly: nation.readonly,
+        };
+        if dry_run() {
+            println!("Would send:\n{}", command.preview());
+            continue;
+        }
+        let action = command.action;
+        let outcome = session(client, nation).command::<AnswerResponse>(action).await?.data.issue;
+        if let Some(error) = &outcome.error {
+            println!("    failed: {}", error);
+            continue;
+        }
+        nation.last_issue = Some(Utc::now());
+        metrics::answered();
+        index.record_decision(&nation.name, issue.id, Some(option), &reason)?;
+        index.record_outcome(&nation.name, Some(&issue.title), &outcome)?;
+        print_outcome(&outcome);
+        if let (Some(notifier), Some(notification)) = (notifier, reclassify(nation, &outcome)) {
+            notifier.send(&notification)?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep a nation's government category and freedoms up to date with an
+/// issue outcome, giving a notification if the outcome changed any of them.
+fn reclassify(nation: &mut nation::Nation, outcome: &issues::Outcome) -> Option<Notification> {
+    let changes = &outcome.reclassifications.inner;
+    if changes.is_empty() {
+        return None;
+    }
+    if let Some(details) = &mut nation.details {
+        for x in changes {
+            details.reclassify(&x.kind, &x.to);
+        }
+    }
+    let body = changes.iter().map(|x| format!("{}: {} -> {}", x.subject(), x.from, x.to)).join("; ");
+    Some(Notification::new(format!("{} reclassified", nation.name), body))
+}
+
+/// Print the interesting parts of an issue outcome.
+fn print_outcome(outcome: &issues::Outcome) {
+    if let Some(description) = &outcome.description {
+        println!("    {}", description);
+    }
+    for headline in &outcome.headlines.inner {
+        println!("    headline: {}", headline);
+    }
+    for x in &outcome.reclassifications.inner {
+        println!("    reclassified: {} {} -> {}", x.subject(), x.from, x.to);
+    }
+    for banner in &outcome.unlocks.banners {
+        println!("    unlocked banner: {}", banner);
+    }
+    for policy in &outcome.new_policies.inner {
+        println!("    new policy: {}", policy.name);
+    }
+    for policy in &outcome.removed_policies.inner {
+        println!("    removed policy: {}", policy.name);
+    }
+}
+
+fn audit_path(config: &Config) -> PathBuf {
+    config.audit_log.clone().unwrap_or_else(|| data_dir().join("audit.jsonl"))
+}
+
+fn index_path() -> PathBuf {
+    data_dir().join("dumps").join("index.sqlite")
+}
+
+/// How tables are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Output {
+    /// Aligned columns for reading.
+    #[default]
+    Text,
+    /// Comma separated, quoted where needed, for spreadsheets.
+    Csv,
+    /// Tab separated.
+    Tsv,
+}
+impl FromStr for Output {
+    type Err = String;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Output::Text),
+            "csv" => Ok(Output::Csv),
+            "tsv" => Ok(Output::Tsv),
+            _ => Err(format!("unknown output format {:?}, expected text, csv, or tsv", input)),
+        }
+    }
+}
+impl core::fmt::Display for Output {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(match self {
+            Output::Text => "text",
+            Output::Csv => "csv",
+            Output::Tsv => "tsv",
+        })
+    }
+}
+
+/// How events are printed by commands that can keep following them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Stream {
+    /// A line of text per event.
+    #[default]
+    Text,
+    /// A JSON object per line, for bots and log pipelines.
+    Ndjson,
+}
+impl FromStr for Stream {
+    type Err = String;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Stream::Text),
+            "ndjson" => Ok(Stream::Ndjson),
+            _ => Err(format!("unknown output format {:?}, expected text or ndjson", input)),
+        }
+    }
+}
+impl core::fmt::Display for Stream {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(match self {
+            Stream::Text => "text",
+            Stream::Ndjson => "ndjson",
+        })
+    }
+}
+
+/// An event as printed with `--output ndjson`. Every object has a `type`,
+/// and fields are only ever added, so consumers can rely on the ones here.
+/// Times are RFC 3339 in UTC.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent<'a> {
+    Happening {
+        id: u64,
+        time: DateTime<Utc>,
+        category: String,
+        /// Without markers or HTML.
+        text: String,
+        /// As the API gave it.
+        raw: &'a str,
+    },
+    NewNation {
+        nation: &'a str,
+        region: &'a str,
+        time: DateTime<Utc>,
+    },
+    RmbPost {
+        id: u64,
+        region: &'a str,
+        nation: &'a str,
+        time: DateTime<Utc>,
+        status: String,
+        likes: u32,
+        suppressor: Option<&'a str>,
+        /// In BBCode.
+        message: &'a str,
+    },
+}
+impl StreamEvent<'_> {
+    fn print(&self) {
+        // Nothing in these can fail to serialize.
+        println!("{}", serde_json::to_string(self).unwrap());
+    }
+}
+
+/// Quote a CSV field if it needs it.
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// TSV has no quoting, so tabs and line breaks inside a field become spaces.
+fn tsv_field(field: &str) -> Cow<'_, str> {
+    const BREAKS: &[char] = &['\t', '\n', '\r'];
+    if field.contains(BREAKS) {
+        Cow::Owned(field.replace(BREAKS, " "))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Print a query result as aligned columns, or delimited for other tools.
+fn print_table(table: &Table, output: Output) {
+    let delimited = |field: fn(&str) -> Cow<'_, str>, separator: &str| {
+        for row in Some(&table.columns).into_iter().chain(&table.rows) {
+            println!("{}", row.iter().map(|x| field(x)).join(separator));
+        }
+    };
+    match output {
+        Output::Text => (),
+        Output::Csv => return delimited(csv_field, ","),
+        Output::Tsv => return delimited(tsv_field, "\t"),
+    }
+    println!("{}", term::table(&table.columns, &table.rows));
+}
+
+/// Format a population given in millions, the way the site does.
+fn format_population(millions: u64) -> String {
+    if millions >= 1000 {
+        format!("{:.3} billion", millions as f64 / 1000.0)
+    } else {
+        format!("{} million", millions)
+    }
+}
+
+/// Amounts of money, which run into the trillions.
+fn format_amount(amount: u64) -> String {
+    const UNITS: [(u64, &str); 3] = [(1_000_000_000_000, "trillion"), (1_000_000_000, "billion"), (1_000_000, "million")];
+    match UNITS.iter().find(|(size, _)| amount >= *size) {
+        Some((size, unit)) => format!("{:.2} {}", amount as f64 / *size as f64, unit),
+        None => amount.to_string(),
+    }
+}
+
+fn print_economy(economy: &Economy) {
+    let sectors = &economy.sectors;
+    println!("    GDP:            {}", format_amount(economy.gdp));
+    println!("    income:         {} on average", format_amount(economy.income));
+    println!("    richest 10%:    {}", format_amount(economy.richest));
+    println!("    poorest 10%:    {}", format_amount(economy.poorest));
+    println!("    tax rate:       {:.1}%", economy.tax);
+    println!("    major industry: {}", economy.major_industry);
+    println!("    sectors:        {:.1}% government, {:.1}% industry, {:.1}% public, {:.1}% black market",
+             sectors.government, sectors.industry, sectors.public, sectors.black_market);
+}
+
+/// Sum up the economies of every nation in a region.
+async fn region_economy(client: &reqwest::Client, region: &str) -> anyhow::Result<Aggregate> {
+    let data = fetch(client, api::Target::Region(region), vec![api::Shard::Nations]).await?;
+    let nations = shard!(data, Nations).unwrap_or_default();
+    let mut aggregate = Aggregate::default();
+    for (i, name) in nations.iter().enumerate() {
+        if interrupted() {
+            break;
+        }
+        eprint!("\r{}/{} nations", i + 1, nations.len());
+        match fetch(client, api::Target::Nation(name), economy::shards()).await {
+            Ok(data) => aggregate.add(&Economy::from_shards(data.inner)),
+            // Nations can move out or cease to exist partway through.
+            Err(e) if is_not_found(&e) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    eprintln!();
+    if aggregate.nations < nations.len() {
+        eprintln!("Skipped {} of {} nations", nations.len() - aggregate.nations, nations.len());
+    }
+    Ok(aggregate)
+}
+
+/// Check `changes` against how `nation` looks now, returning each one that changes
+/// anything as the setting with its old and new values.
+async fn plan_changes(client: &reqwest::Client, nation: &str, changes: &[Change])
+                      -> anyhow::Result<Vec<(&'static str, String, String)>> {
+    let mut shards = Appearance::shards();
+    if changes.iter().any(|x| matches!(x, Change::Banner(_))) {
+        shards.push(api::Shard::Banners);
+    }
+    let data = fetch(client, api::Target::Nation(nation), shards).await?;
+    let mut banners = None;
+    let current = Appearance::from_shards(data.inner.into_iter().filter_map(|shard| match shard {
+        api::ResolvedShard::Banners(x) => {
+            banners = Some(x);
+            None
+        },
+        shard => Some(shard),
+    }));
+    let mut planned = Vec::new();
+    for change in changes {
+        change.check(&current, banners.as_ref()).map_err(|e| anyhow::anyhow!("{}: {}", nation, e))?;
+        if change.is_noop(&current) {
+            continue;
+        }
+        let (old, new) = match change {
+            Change::Motto(x) => (current.motto.clone(), x.trim().to_owned()),
+            Change::Pretitle(x) => (current.pretitle.clone(), x.trim().to_owned()),
+            Change::Flag(bytes) => {
+                let format = settings::FlagFormat::sniff(bytes).expect("checked above");
+                (current.flag.clone(), format!("a {} image of {} bytes", format, bytes.len()))
+            },
+            Change::Banner(x) => (current.banner.clone(), x.clone()),
+            Change::Field(field, x) => (current.fields.get(field).cloned(), x.trim().to_owned()),
+        };
+        planned.push((change.setting(), old.unwrap_or_else(|| "none".into()), new));
+    }
+    Ok(planned)
+}
+
+/// Print what `theme` would change about each of `names`. Returns whether anything would change.
+async fn plan_theme(client: &reqwest::Client, names: &[String], theme: &settings::Theme) -> anyhow::Result<bool> {
+    let mut changed = false;
+    let mut failed = 0;
+    for name in names {
+        if interrupted() {
+            break;
+        }
+        let planned = match theme.changes(name) {
+            Ok(changes) => plan_changes(client, name, &changes).await,
+            Err(e) => Err(e.into()),
+        };
+        match planned {
+            Ok(planned) if planned.is_empty() => println!("{}: nothing to change", name),
+            Ok(planned) => {
+                changed = true;
+                println!("{}:", name);
+                for (setting, old, new) in planned {
+                    println!("    {}: {} -> {}", setting, old, term::good(new));
+                }
+            },
+            Err(e) => {
+                failed += 1;
+                eprintln!("{}: {}", name, term::bad(e));
+            },
+        }
+    }
+    if failed > 0 {
+        return Err(PartialFailure(format!("{} of {} nations couldn't be checked", failed, names.len())).into());
+    }
+    Ok(changed)
+}
+
+/// Print a short human readable card about a nation, and return its public details.
+async fn show_nation(client: &reqwest::Client, name: &str) -> anyhow::Result<Option<Details>> {
+    let shards = ShardSet::new()
+        .shard(api::Shard::FullName)
+        .shard(api::Shard::Motto)
+        .shard(api::Shard::Category)
+        .region()
+        .wa()
+        .population()
+        .influence()
+        .shard(api::Shard::Endorsements)
+        .shard(api::Shard::Flag)
+        .shard(api::Shard::WaBadges)
+        .census([])
+        .build()?;
+    let data = fetch(client, api::Target::Nation(name), shards).await?;
+    let details = Details::from_shards(&data.inner);
+    let mut badges = Vec::new();
+    let mut endorsements = 0;
+    let mut wa_status = String::new();
+    let mut wa_badges = wa::Badges::default();
+    for shard in data.inner {
+        match shard {
+            api::ResolvedShard::FullName(x) => println!("{}", x),
+            api::ResolvedShard::Motto(x) => println!("    \"{}\"", x),
+            api::ResolvedShard::Category(x) => println!("    category:   {}", x),
+            api::ResolvedShard::Region(x) => println!("    region:     {}", x),
+            api::ResolvedShard::WaStatus(x) => wa_status = x,
+            api::ResolvedShard::Population(x) => println!("    population: {}", format_population(x)),
+            api::ResolvedShard::Influence(x) => println!("    influence:  {}", x),
+            api::ResolvedShard::Endorsements(x) => endorsements = x.len(),
+            api::ResolvedShard::WaBadges(x) => wa_badges = x,
+            api::ResolvedShard::Census(census) => {
+                // The site awards badges for being in the top 1%, 5%, and 10% of the world.
+                for score in census.scales {
+                    let tier = match score.percent_rank {
+                        Some(x) if x <= 1.0 => 1,
+                        Some(x) if x <= 5.0 => 5,
+                        Some(x) if x <= 10.0 => 10,
+                        _ => continue,
+                    };
+                    if let Some(scale) = CensusScale::from_id(score.id) {
+                        badges.push((tier, scale));
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+    println!("    wa:         {} ({} endorsements)", wa_status, endorsements);
+    print_wa_badges(client, &wa_badges).await?;
+    badges.sort();
+    for (tier, scale) in badges.iter().take(5) {
+        println!("    {:<12}{}", format!("top {}%:", tier), scale);
+    }
+    Ok(details)
+}
+
+/// Print WA badges with the titles of the Security Council resolutions behind
+/// them, returning the resolutions that could be found.
+async fn print_wa_badges(client: &reqwest::Client, badges: &wa::Badges) -> anyhow::Result<Vec<Box<wa::Resolution>>> {
+    let mut found = Vec::new();
+    for badge in &badges.inner {
+        let shards = vec![api::Shard::Resolution { id: Some(badge.resolution) }];
+        let data = fetch(client, api::Target::Wa(wa::Council::Security), shards).await?;
+        let resolution = shard!(data, Resolution).filter(|x| x.exists());
+        let title = resolution.as_ref().map_or("(title unknown)", |x| &x.name);
+        println!("    {:<12}SC #{} {}", format!("{}:", badge.action()), badge.resolution, title);
+        found.extend(resolution);
+    }
+    Ok(found)
+}
+
+/// Print Security Council resolutions that change what can be done at a
+/// region's borders: ones in force, from its badges, and ones at vote or
+/// in the queue that target it.
+async fn print_border_resolutions(client: &reqwest::Client, region: &str,
+                                  in_force: &[Box<wa::Resolution>]) -> anyhow::Result<()> {
+    let mut lines = Vec::new();
+    for resolution in in_force.iter().filter(|x| wa::affects_borders(&x.category)) {
+        lines.push(format!("{} in force: {}", resolution.category, resolution.name));
+    }
+    let shards = vec![api::Shard::Resolution { id: None }, api::Shard::Proposals];
+    let data = fetch(client, api::Target::Wa(wa::Council::Security), shards).await?;
+    let targets = |category: &str, option: &str| {
+        wa::affects_borders(category)
+            && wa::target_region(option).is_some_and(|x| nation::canonical_name(x) == nation::canonical_name(region))
+    };
+    for shard in data.inner {
+        match shard {
+            api::ResolvedShard::Resolution(x) if x.exists() && targets(&x.category, &x.option) => {
+                lines.push(format!("{} at vote: {}", x.category, x.name));
+            },
+            api::ResolvedShard::Proposals(x) => {
+                for proposal in x.inner.iter().filter(|x| targets(&x.category, &x.option)) {
+                    lines.push(format!("{} proposed: {} ({} approvals)",
+                                       proposal.category, proposal.name, proposal.approvals.len()));
+                }
+            },
+            _ => (),
+        }
+    }
+    for line in lines {
+        println!("    {}", term::warn(line));
+    }
+    Ok(())
+}
+
+/// Print how far into each update a region updates, and when it next should.
+async fn region_update_time(client: &reqwest::Client, region: &str) -> anyhow::Result<()> {
+    let shards = vec![api::Shard::Happenings {
+        view: Some(View::Region(nation::canonical_name(region))),
+        filter: vec![happenings::Category::Change],
+        limit: None,
+    }];
+    let data = fetch(client, api::Target::World, shards).await?;
+    let events = shard!(data, Happenings).unwrap_or_default().events;
+    let mut times = happenings::update_times(&events, region);
+    times.extend(Index::open(&index_path())?.region_updates(region)?);
+    let windows = UpdateWindow::estimate(times);
+    if windows.is_empty() {
+        anyhow::bail!("{} hasn't been seen updating in its recent happenings or in indexed dumps. \
+                       Try again after the next update, or run `nation dump download`.", region);
+    }
+    let offset = |x: chrono::Duration| format!("{:02}:{:02}:{:02}", x.num_hours(), x.num_minutes() % 60, x.num_seconds() % 60);
+    let now = Utc::now();
+    println!("{}", region);
+    for window in windows {
+        let seen = if window.seen == 1 { "once".to_owned() } else { format!("{} times", window.seen) };
+        println!("    {}: {} into the update ({} to {}, seen {}), next around {}",
+                 window.kind, offset(window.median), offset(window.earliest), offset(window.latest),
+                 seen, window.next(now).format("%Y-%m-%d %H:%M:%S UTC"));
+    }
+    Ok(())
+}
+
+/// Print who runs a region, how many live there, its tags and WA badges,
+/// and any liberation or injunction that's in force or on the way.
+async fn region_info(client: &reqwest::Client, region: &str) -> anyhow::Result<()> {
+    let shards = vec![
+        api::Shard::Name,
+        api::Shard::Founder,
+        api::Shard::Delegate,
+        api::Shard::DelegateVoteCount,
+        api::Shard::Nations,
+        api::Shard::Tags,
+        api::Shard::WaBadges,
+    ];
+    let data = fetch(client, api::Target::Region(region), shards).await?;
+    let mut name = region.to_owned();
+    let mut founder = None;
+    let mut delegate = None;
+    let mut delegate_votes = 0;
+    let mut nations = Vec::new();
+    let mut tags = Vec::new();
+    let mut badges = wa::Badges::default();
+    for shard in data.inner {
+        match shard {
+            api::ResolvedShard::Name(x) => name = x,
+            api::ResolvedShard::Founder(x) => founder = region::officeholder(x),
+            api::ResolvedShard::Delegate(x) => delegate = region::officeholder(x),
+            api::ResolvedShard::DelegateVoteCount(x) => delegate_votes = x,
+            api::ResolvedShard::Nations(x) => nations = x,
+            api::ResolvedShard::Tags(x) => tags = x.inner,
+            api::ResolvedShard::WaBadges(x) => badges = x,
+            _ => (),
+        }
+    }
+    println!("{}", name);
+    println!("    founder:    {}", founder.as_deref().unwrap_or("none"));
+    match delegate {
+        // Delegate votes count the delegate's own vote on top of their endorsements.
+        Some(x) => println!("    delegate:   {} ({} endorsements)", x, delegate_votes.saturating_sub(1)),
+        None => println!("    delegate:   none"),
+    }
+    println!("    nations:    {}", nations.len());
+    if !tags.is_empty() {
+        println!("    tags:       {}", tags.join(", "));
+    }
+    let in_force = print_wa_badges(client, &badges).await?;
+    print_border_resolutions(client, &name, &in_force).await
+}
+
+/// A nation's or region's Z-Day counts, on one line.
+fn format_zombie(zombie: &zday::Zombie) -> String {
+    format!("{} survivors, {} zombies, {} dead", format_population(zombie.survivors),
+            format_population(zombie.zombies), format_population(zombie.dead))
+}
+
+/// Show how `nation`, and `target` if given, are faring, and check `action` makes sense for them.
+async fn zday_act(client: &reqwest::Client, nation: &str, action: &str, target: Option<&str>) -> anyhow::Result<()> {
+    let data = fetch(client, api::Target::Nation(nation), vec![api::Shard::Zombie]).await?;
+    let zombie = shard!(data, Zombie).unwrap_or_default();
+    println!("{}: {}", nation, format_zombie(&zombie));
+    if !zombie.action.is_empty() {
+        println!("    doing:   {}", zombie.action);
+    }
+    if !zombie.intended.is_empty() && zombie.intended != zombie.action {
+        println!("    next:    {}", zombie.intended);
+    }
+    match action {
+        "exterminate" if zombie.zombies == 0 => println!("{}", term::warn(format!("{} has no zombies to exterminate.", nation))),
+        "research" if zombie.action == "research" => println!("{} is already researching a cure.", nation),
+        _ => (),
+    }
+    if let Some(target) = target {
+        let data = match fetch(client, api::Target::Nation(target), vec![api::Shard::Zombie]).await {
+            Err(e) if is_not_found(&e) => anyhow::bail!("There's no nation called {}.", target),
+            result => result?,
+        };
+        let zombie = shard!(data, Zombie).unwrap_or_default();
+        println!("{}: {}", target, format_zombie(&zombie));
+        if zombie.zombies == 0 {
+            println!("{}", term::warn(format!("{} has no zombies for a cure to help with.", target)));
+        }
+    }
+    let doing = match (action, target) {
+        ("cure", Some(target)) => format!("fire a cure missile at {}", target),
+        _ => action.to_owned(),
+    };
+    println!("Log in as {} and {} on the site: https://www.nationstates.net/nation={}", nation, doing, nation::canonical_name(nation));
+    Ok(())
+}
+
+/// Check `nation` can join, or with `leave` resign from, the World Assembly,
+/// and link to the page where that's done.
+async fn wa_membership(client: &reqwest::Client, nation: &str, leave: bool) -> anyhow::Result<()> {
+    let data = match fetch(client, api::Target::Nation(nation), vec![api::Shard::Wa, api::Shard::Endorsements]).await {
+        Err(e) if is_not_found(&e) => anyhow::bail!("There's no nation called {}.", nation),
+        result => result?,
+    };
+    let mut status = String::new();
+    let mut endorsements = Vec::new();
+    for shard in data.inner {
+        match shard {
+            api::ResolvedShard::WaStatus(x) => status = x,
+            api::ResolvedShard::Endorsements(x) => endorsements = x,
+            _ => (),
+        }
+    }
+    let member = !status.is_empty() && status != "Non-member";
+    match (leave, member) {
+        (false, true) => {
+            println!("{} is already in the World Assembly ({}).", nation, status);
+            return Ok(());
+        },
+        (true, false) => {
+            println!("{} isn't in the World Assembly.", nation);
+            return Ok(());
+        },
+        (false, false) => println!("Only one nation per player may be in the World Assembly."),
+        (true, true) => {
+            let delegacy = if status == "WA Delegate" { ", and its delegacy with them" } else { "" };
+            println!("{}", term::warn(format!("Resigning clears {}'s {} endorsements{}.", nation, endorsements.len(), delegacy)));
+        },
+    }
+    println!("Log in as {} and {} from the World Assembly page: https://www.nationstates.net/page=un",
+             nation, if leave { "resign" } else { "apply" });
+    Ok(())
+}
+
+/// Print `region`'s Z-Day counts, and keep printing them as they change if `monitor` is set.
+async fn zday_region(client: &reqwest::Client, region: &str, monitor: bool, interval: u64) -> anyhow::Result<()> {
+    let mut last: Option<zday::Zombie> = None;
+    loop {
+        let data = fetch(client, api::Target::Region(region), vec![api::Shard::Zombie]).await?;
+        let zombie = shard!(data, Zombie).unwrap_or_default();
+        if last.as_ref() != Some(&zombie) {
+            let change = match &last {
+                Some(earlier) => {
+                    let [survivors, zombies, dead] = zombie.since(earlier);
+                    format!(" ({:+} survivors, {:+} zombies, {:+} dead)", survivors, zombies, dead)
+                },
+                None => String::new(),
+            };
+            println!("[{}] {}: {}{}", Utc::now().format("%Y-%m-%d %H:%M:%S"), region, format_zombie(&zombie), change);
+            last = Some(zombie);
+        }
+        if !monitor {
+            return Ok(());
+        }
+        idle(std::time::Duration::from_secs(interval.max(10))).await;
+        if interrupted() {
+            return Ok(());
+        }
+    }
+}
+
+/// Check `nation` can move to `region`. If it's there already, with `update`,
+/// its details in the profile are brought up to date.
+async fn move_nation(client: &reqwest::Client, profile_path: &Path, nation: &str, region: &str, update: bool) -> anyhow::Result<()> {
+    let mut profile = load_profile(profile_path)?;
+    if let Some(x) = profile.nations.inner.iter().find(|x| nation::canonical_name(&x.name) == nation::canonical_name(nation) && x.readonly) {
+        return Err(api::Failure::ReadOnly(x.name.clone()).into());
+    }
+    let data = match fetch(client, api::Target::Region(region), vec![api::Shard::Name, api::Shard::Tags]).await {
+        Err(e) if is_not_found(&e) => anyhow::bail!("There's no region called {}.", region),
+        result => result?,
+    };
+    let mut name = region.to_owned();
+    let mut tags = region::Tags::default();
+    for shard in data.inner {
+        match shard {
+            api::ResolvedShard::Name(x) => name = x,
+            api::ResolvedShard::Tags(x) => tags = x,
+            _ => (),
+        }
+    }
+    let data = fetch(client, api::Target::Nation(nation), vec![api::Shard::Region]).await?;
+    let current = shard!(data, Region).unwrap_or_default();
+    if nation::canonical_name(&current) == nation::canonical_name(&name) {
+        println!("{} is in {}.", nation, name);
+        if update {
+            for x in select(&mut profile, Some(nation), None)? {
+                match &mut x.details {
+                    Some(details) => details.region = Some(name.clone()),
+                    None => println!("{} has no details yet to update; ping it or run `nation show` on it.", x.name),
+                }
+            }
+            save_profile(&mut profile, profile_path)?;
+        }
+        return Ok(());
+    }
+    println!("{}: {} -> {}", nation, current, term::good(&name));
+    println!("Log in as {} and move it from the region's page: https://www.nationstates.net/region={}",
+             nation, nation::canonical_name(&name));
+    if tags.contains("password") {
+        println!("{} is password protected; the site will ask for its password.", name);
+    }
+    Ok(())
+}
+
+/// Classification of a nation, for `compare`.
+#[derive(Serialize, Default)]
+struct Classification {
+    name: String,
+    category: String,
+    freedom: Option<api::Freedom>,
+}
+/// A single row of a comparison.
+#[derive(Serialize)]
+struct ScaleComparison {
+    id: u16,
+    name: &'static str,
+    scores: [Option<f64>; 2],
+    difference: Option<f64>,
+}
+#[derive(Serialize)]
+struct Comparison {
+    nations: [Classification; 2],
+    scales: Vec<ScaleComparison>,
+}
+
+async fn compare(client: &reqwest::Client, names: [&str; 2], scales: Vec<CensusScale>) -> anyhow::Result<Comparison> {
+    let mut nations: [Classification; 2] = Default::default();
+    let mut scores: [Vec<api::CensusScore>; 2] = Default::default();
+    for i in 0..2 {
+        let shards = ShardSet::new()
+            .name()
+            .shard(api::Shard::Category)
+            .shard(api::Shard::Freedom)
+            .census(scales.clone())
+            .build()?;
+        let data = fetch(client, api::Target::Nation(names[i]), shards).await?;
+        for shard in data.inner {
+            match shard {
+                api::ResolvedShard::Name(x) => nations[i].name = x,
+                api::ResolvedShard::Category(x) => nations[i].category = x,
+                api::ResolvedShard::Freedom(x) => nations[i].freedom = Some(x),
+                api::ResolvedShard::Census(x) => scores[i] = x.scales,
+                _ => (),
+            }
+        }
+    }
+    let score = |i: usize, scale: CensusScale| {
+        scores[i].iter().find(|x| x.id == scale.id()).and_then(|x| x.score)
+    };
+    let scales = scales.into_iter().map(|scale| {
+        let pair = [score(0, scale), score(1, scale)];
+        ScaleComparison {
+            id: scale.id(),
+            name: scale.name(),
+            scores: pair,
+            difference: pair[0].zip(pair[1]).map(|(a, b)| a - b),
+        }
+    }).collect();
+    Ok(Comparison { nations, scales })
+}
+
+fn print_comparison(comparison: &Comparison) {
+    let [a, b] = &comparison.nations;
+    let row = |label: &str, x: &str, y: &str, z: &str| {
+        println!("{:<36} {:>22} {:>22} {:>14}", label, x, y, z);
+    };
+    row("", &a.name, &b.name, "difference");
+    row("Category", &a.category, &b.category, "");
+    if let (Some(x), Some(y)) = (&a.freedom, &b.freedom) {
+        row("Civil Rights", &x.civil_rights, &y.civil_rights, "");
+        row("Economy", &x.economy, &y.economy, "");
+        row("Political Freedom", &x.political_freedom, &y.political_freedom, "");
+    }
+    let show = |x: Option<f64>| x.map_or_else(String::new, |x| format!("{:.2}", x));
+    for scale in &comparison.scales {
+        row(scale.name, &show(scale.scores[0]), &show(scale.scores[1]), &show(scale.difference));
+    }
+}
+
+/// Draw hourly vote totals as horizontal bars,
+/// `+` for votes for and `-` for votes against.
+fn vote_chart(resolution: &wa::Resolution, width: usize) -> String {
+    let max = resolution.vote_track().map(|(f, a)| f + a).max().unwrap_or(0).max(1);
+    let scale = |x: u32| (x as usize * width + max as usize / 2) / max as usize;
+    let mut chart = format!("{:>5} {:>8} {:>8}\n", "hour", "for", "against");
+    for (hour, (votes_for, votes_against)) in resolution.vote_track().enumerate() {
+        chart.push_str(&format!("{:>5} {:>8} {:>8} {}{}\n",
+                                hour, votes_for, votes_against,
+                                "+".repeat(scale(votes_for)),
+                                "-".repeat(scale(votes_against))));
+    }
+    chart
+}
+
+/// Poll a proposal until it leaves the queue,
+/// printing whenever its approvals change.
+async fn watch_proposal(client: &reqwest::Client, council: Council, id: &str, interval: u64) -> anyhow::Result<()> {
+    let mut previous: Option<Vec<String>> = None;
+    loop {
+        let proposals = fetch_proposals(client, council).await?;
+        let proposal = match proposals.inner.into_iter().find(|p| p.id == id) {
+            Some(x) => x,
+            None => {
+                println!("Proposal {} is not in the {} queue.", id, council);
+                return Ok(());
+            }
+        };
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S");
+        match &previous {
+            None => println!("[{}] {}: {} approvals", now, proposal.name, proposal.approvals.len()),
+            Some(old) if *old != proposal.approvals => {
+                let gained = proposal.approvals.iter().filter(|x| !old.contains(x)).join(", ");
+                let lost = old.iter().filter(|x| !proposal.approvals.contains(x)).join(", ");
+                println!("[{}] {} approvals ({} -> {})", now, proposal.approvals.len(), old.len(), proposal.approvals.len());
+                if !gained.is_empty() {
+                    println!("    + {}", gained);
+                }
+                if !lost.is_empty() {
+                    println!("    - {}", lost);
+                }
+            },
+            Some(_) => (),
+        }
+        previous = Some(proposal.approvals);
+        idle(std::time::Duration::from_secs(interval)).await;
+        if interrupted() {
+            return Ok(());
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let matches = match Cli::clap().get_matches_safe() {
+        Ok(x) => x,
+        Err(e) if e.use_stderr() => {
+            eprintln!("{}", e.message);
+            std::process::exit(EXIT_USAGE);
+        },
+        // Help and version.
+        Err(e) => e.exit(),
+    };
+    let command = command_name(&matches);
+    let cli = Cli::from_clap(&matches);
+    let Cli { api_version: version, api_base, record, replay, dry_run: dry, read_only, proxy, verbose, pin_friendly, api_delay, metrics: metrics_addr,
+              color, command: opt } = cli;
+    term::set_color(color);
+    DRY_RUN.store(dry, Ordering::Relaxed);
+    api::set_verbose(verbose);
+    if let Some(addr) = metrics_addr {
+        let listener = metrics::bind(addr).await
+            .map_err(|e| anyhow::anyhow!("Couldn't serve metrics on {}: {}", addr, e))?;
+        tokio::spawn(metrics::serve(listener));
+    }
+    // Replayed requests don't count against anything.
+    if replay.is_none() {
+        ratelimit::share_through(data_dir().join("ratelimit"));
+    }
+    if let Some(path) = record {
+        cassette::insert(Cassette::record(&path));
+    } else if let Some(path) = replay {
+        cassette::insert(Cassette::replay(&path)?);
+    }
+    let config = Config::load(&config_path())?;
+    API_VERSION.store(version.or(config.api_version).unwrap_or(nation::API_VERSION), Ordering::Relaxed);
+    if let Some(base) = api_base.or(config.api_base.clone()) {
+        api::set_base(&base)?;
+    }
+    PIN_FRIENDLY.store(pin_friendly || config.pin_friendly, Ordering::Relaxed);
+    api::set_read_only(read_only || config.read_only);
+    if let Some(delay) = api_delay.or(config.api_delay) {
+        ratelimit::set_min_delay(std::time::Duration::from_millis(delay));
+    }
+    *CLIENT.lock().unwrap() = Some(build_client(&config, proxy.as_deref())?);
+    audit::log_to(&audit_path(&config), config.operator.clone());
+    *OPERATOR.lock().unwrap() = config.operator.clone();
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if interrupted() {
+                eprintln!("Interrupted again, quitting now.");
+                let _saving = SAVING.lock();
+                std::process::exit(INTERRUPTED_EXIT);
+            }
+            eprintln!("Interrupted. Finishing up; press Ctrl-C again to quit right away.");
+            let _ = STOP.0.send(true);
+        }
+    });
+    let args = redacted_args(&matches, std::env::args().skip(1).collect());
+    let result = run_hooked(opt, &command, &args).await;
+    if let Err(e) = &result {
+        eprintln!("Error: {:?}", e);
+    }
+    if interrupted() {
+        std::process::exit(INTERRUPTED_EXIT);
+    }
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) => std::process::exit(exit_code(&e)),
+    }
+}
+
+/// Name of the command given, subcommands and all, like `issues auto`.
+fn command_name(matches: &structopt::clap::ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let (name, Some(sub)) = matches.subcommand() {
+        names.push(name);
+        matches = sub;
+    }
+    names.join(" ")
+}
+
+/// Arguments that are secrets, and so never handed to hooks.
+const SECRET_ARGS: [&str; 3] = ["password", "client-key", "key"];
+
+/// `args` with the values of [`SECRET_ARGS`] replaced, as given to hooks.
+fn redacted_args(matches: &structopt::clap::ArgMatches, args: Vec<String>) -> Vec<String> {
+    let mut secrets = Vec::new();
+    let mut matches = matches;
+    loop {
+        for name in SECRET_ARGS.iter() {
+            secrets.extend(matches.values_of(name).into_iter().flatten().filter(|x| !x.is_empty()));
+        }
+        match matches.subcommand() {
+            (_, Some(sub)) => matches = sub,
+            _ => break,
+        }
+    }
+    args.into_iter()
+        .map(|arg| match arg.split_once('=') {
+            _ if secrets.contains(&arg.as_str()) => "<redacted>".to_owned(),
+            Some((flag, value)) if flag.starts_with('-') && secrets.contains(&value) => format!("{}=<redacted>", flag),
+            _ => arg,
+        })
+        .collect()
+}
+
+/// Run a command along with any hooks the config sets for it.
+async fn run_hooked(opt: Opt, command: &str, args: &[String]) -> anyhow::Result<()> {
+    let hook = Config::load(&config_path())?.hooks.remove(command).unwrap_or_default();
+    if let Some(before) = &hook.pre {
+        if dry_run() {
+            println!("Would run the pre hook for {}: {}", command, before);
+        } else {
+            hooks::before(before, command)
+                .map_err(|e| anyhow::anyhow!("Not running {}, its pre hook failed: {}", command, e))?;
+        }
+    }
+    let started = Utc::now();
+    let result = run(opt).await;
+    if let Some(after) = &hook.post {
+        if dry_run() {
+            println!("Would run the post hook for {}: {}", command, after);
+        } else {
+            let summary = hooks::Summary {
+                command,
+                args,
+                ok: result.is_ok(),
+                exit_code: result.as_ref().err().map_or(0, exit_code),
+                error: result.as_ref().err().map(|e| format!("{:#}", e)),
+                started,
+                finished: Utc::now(),
+            };
+            // The command's done either way, so this is only worth a warning.
+            if let Err(e) = hooks::after(after, &summary) {
+                eprintln!("Warning: the post hook for {} failed: {}", command, e);
+            }
+        }
+    }
+    result
+}
+
+async fn run(opt: Opt) -> anyhow::Result<()> {
+    // println!("timestamp: {}", quick_xml::se::to_string(&Utc::now()).unwrap());
+    match opt {
+        Opt::Init => init().await?,
+        Opt::Run { alias } => {
+            let config = Config::load(&config_path())?;
+            let steps = match config.aliases.get(&alias) {
+                Some(x) => x,
+                None => anyhow::bail!("No alias {} in {}. Aliases: {}.", alias, config_path().display(),
+                                      config.aliases.keys().sorted().join(", ")),
+            };
+            // Every step is read before any runs, so a typo in the last
+            // doesn't turn up after the first few are done.
+            let opts = steps.iter()
+                .map(|step| {
+                    let args = split_args(step).map_err(|e| anyhow::anyhow!("Bad step {:?} in {}: {}", step, alias, e))?;
+                    let matches = Opt::clap().get_matches_from_safe(std::iter::once("nation".to_owned()).chain(args.iter().cloned()))
+                        .map_err(|e| anyhow::anyhow!("Bad step {:?} in {}: {}", step, alias, e.message))?;
+                    let opt = Opt::from_clap(&matches);
+                    if let Opt::Run { .. } = opt {
+                        anyhow::bail!("Step {:?} in {} runs another alias, which aliases can't do.", step, alias);
+                    }
+                    Ok((opt, command_name(&matches), redacted_args(&matches, args)))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            // Steps share the one client and rate limit, since they all go through the globals.
+            for (step, (opt, command, args)) in steps.iter().zip(opts) {
+                if interrupted() {
+                    break;
+                }
+                eprintln!("==> nation {}", step);
+                Box::pin(run_hooked(opt, &command, &args)).await
+                    .map_err(|e| e.context(format!("Step {:?} of {} failed", step, alias)))?;
+            }
+        }
+        #[cfg(feature = "self-update")]
+        Opt::SelfUpdate { check } => {
+            use nation::update;
+            let client = client();
+            let release = update::latest(&client).await?;
+            let current = env!("CARGO_PKG_VERSION");
+            if !release.is_newer() {
+                println!("Already up to date: {} is the latest release, running {}.", release.tag_name, current);
+            } else if check {
+                println!("{} is out, running {}.", release.tag_name, current);
+            } else if dry_run() {
+                println!("Would update from {} to {}.", current, release.tag_name);
+            } else {
+                let exe = std::env::current_exe()?;
+                update::install(&client, &release, &exe).await?;
+                println!("Updated {} from {} to {}.", exe.display(), current, release.tag_name);
+            }
+        }
+        Opt::Ping { profile: profile_path, group: Some(group), .. } => {
+            let mut profile = load_profile(&profile_path.path)?;
+            let client = client();
+            let mut failed = 0;
+            for nation in select(&mut profile, None, Some(&group))? {
+                if interrupted() {
+                    break;
+                }
+                match ping(&client, nation).await {
+                    Ok(_) => println!("{}: {}", nation.name, term::good("ok")),
+                    Err(e) => {
+                        println!("{}: {}", nation.name, term::bad(e));
+                        failed += 1;
+                    },
+                }
+            }
+            save_profile(&mut profile, &profile_path.path)?;
+            if failed > 0 {
+                return Err(PartialFailure(format!("{} nations in {} failed.", failed, group)).into());
+            }
+        }
+        Opt::Ping { profile: profile_path, nation: Some(nation), retry_pin, .. } => {
+            let mut profile = load_profile(&profile_path.path)?;
+            // println!("Profile: {:#?}", profile);
+            // println!("XML Profile: {}", quick_xml::se::to_string(&profile.nations).unwrap());
+            let nation = match profile.nations.inner.iter_mut().find(|x| x.name == nation) {
+                Some(x) => x,
+                None => anyhow::bail!("Nation {} not found.", nation),
+            };
+            let client = client();
+            let policy = RetryPolicy { refresh_pin: retry_pin, ..RetryPolicy::default() };
+            let shards = Some(api::Shard::Ping).into_iter().chain(Details::shards()).collect();
+            let res = session(&client, nation).policy(policy).request(shards).await;
+            match res {
+                Ok(response) => {
+                    let old = nation.details.take();
+                    let new = Details::from_shards(&response.data.inner).map(|x| x.or_older(old.as_ref()));
+                    warn_cut_short(nation);
+                    println!("{}: {}", nation.name, term::good("ok"));
+                    // Issues answered on the site show up here instead of in an outcome.
+                    if let (Some(old), Some(new)) = (&old, &new) {
+                        for (what, from, to) in old.reclassified(new) {
+                            println!("    reclassified: {} {} -> {}", what, from, to);
+                        }
+                    }
+                    nation.details = new.or(old);
+                    nation.last_ping = Some(Utc::now());
+                    metrics::pinged();
+                    save_profile(&mut profile, &profile_path.path)?;
+                },
+                Err(api::Failure::BadPin) => {
+                    warn_cut_short(nation);
+                    save_profile(&mut profile, &profile_path.path)?;
+                    return Err(api::Failure::BadPin.into());
+                },
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Opt::Ping { .. } => unreachable!("structopt requires a nation or a group"),
+        Opt::List { profile: profile_path, stale, details } => {
+            let profile = load_profile(&profile_path.path)?;
+            if profile.nations.inner.is_empty() {
+                println!("No nations in {}.", profile_path);
+            }
+            for nation in &profile.nations.inner {
+                let credential = match nation.auth.login() {
+                    Some(nation::Login::Autologin(_)) => "autologin",
+                    Some(nation::Login::Password(_)) => "password",
+                    None => "no credentials",
+                };
+                let pin = match (&nation.auth.pin, nation.auth.pin_state()) {
+                    (Some(pin), PinState::Valid) => format!("pin valid, {} min old",
+                                                            Utc::now().signed_duration_since(pin.timestamp()).num_minutes()),
+                    (_, PinState::Rejected) => format!("pin rejected {}",
+                                                       nation.auth.pin_rejected.map(|x| x.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default()),
+                    (_, state) => format!("pin {}", state),
+                };
+                let now = Utc::now();
+                let active = match nation.last_active() {
+                    Some(x) => format!("active {}d ago", now.signed_duration_since(x).num_days()),
+                    None => "never active".to_owned(),
+                };
+                let is_stale = match (stale, nation.last_active()) {
+                    (Some(Age(age)), Some(x)) => now.signed_duration_since(x) > age,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                // Padded before coloring, since the color codes would count toward the width.
+                let credential = format!("{:<15}", credential);
+                let credential = if nation.auth.login().is_none() { term::bad(credential) } else { credential };
+                let pin = if nation.auth.pin_state() == PinState::Rejected { term::bad(pin) } else { pin };
+                let active = format!("{:<16}", active);
+                let active = if is_stale { term::warn(active) } else { active };
+                println!("{} {:<30} {} {} {}{}", if is_stale { "!" } else { " " },
+                         nation.name, credential, active, pin, if nation.readonly { ", read-only" } else { "" });
+                if !details {
+                    continue;
+                }
+                match &nation.details {
+                    Some(x) => {
+                        let unknown = || "?".to_owned();
+                        println!("    {} in {}, {}, as of {}d ago",
+                                 x.population.map_or_else(unknown, format_population),
+                                 x.region.clone().unwrap_or_else(unknown),
+                                 x.wa.clone().unwrap_or_else(unknown),
+                                 now.signed_duration_since(x.fetched).num_days());
+                        if let Some(category) = &x.category {
+                            println!("    category: {}", category);
+                        }
+                        if let Some(flag) = &x.flag {
+                            println!("    flag: {}", flag);
+                        }
+                    },
+                    None => println!("    no details yet; ping it or run `nation show` on it"),
+                }
+                if let (Some(by), Some(changed)) = (&nation.changed_by, nation.changed) {
+                    println!("    last changed by {}, {}d ago", by, now.signed_duration_since(changed).num_days());
+                }
+            }
+        }
+        Opt::Add {
+            profile: profile_path,
+            name,
+            password,
+            verify,
+        } => {
+            let password = password.read()?;
+            let mut profile = load_profile(&profile_path.path)?;
+            let canonical = nation::canonical_name(&name);
+            if profile.nations.inner.iter().any(|x| nation::canonical_name(&x.name) == canonical) {
+                anyhow::bail!("Nation {} is already in the profile.", name);
+            }
+            let mut new = nation::Nation {
+                name,
+                auth: nation::Auth {
+                    password: Some(password),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            if verify {
+                let client = client();
+                if let Err(e) = ping(&client, &mut new).await {
+                    return Err(anyhow::Error::new(e).context(format!("Verification failed, not adding {}", new.name)));
+                }
+                let data = fetch(&client, api::Target::Nation(&new.name), vec![api::Shard::Region]).await?;
+                new.founding_region = shard!(data, Region);
+            }
+            println!("Added {} to {}.", new.name, profile_path);
+            profile.nations.inner.push(new);
+            save_profile(&mut profile, &profile_path.path)?;
+        }
+        Opt::NewPassword { profile: profile_path, nation, password } => {
+            let password = password.read()?;
+            let mut profile = load_profile(&profile_path.path)?;
+            let nation = match profile.nations.inner.iter_mut().find(|x| x.name == nation) {
+                Some(x) => x,
+                None => anyhow::bail!("Nation {} not found.", nation),
+            };
+            // Changing the password logs out every session,
+            // so the autologin and pin from the old one are no good.
+            nation.auth = nation::Auth {
+                password: Some(password),
+                ..Default::default()
+            };
+            println!("Saved the new password for {}.", nation.name);
+            save_profile(&mut profile, &profile_path.path)?;
+        }
+        Opt::Doctor { profile: profile_path, ping, fix_perms } => {
+            let problems = doctor(&client(), &profile_path.path, ping, fix_perms).await?;
+            if problems > 0 {
+                anyhow::bail!("{} problems found.", problems);
+            }
+            println!("No problems found.");
+        }
+        Opt::Restore { profile: profile_path, nation } => {
+            let mut profile = load_profile(&profile_path.path)?;
+            let nation = match profile.nations.inner.iter_mut().find(|x| x.name == nation) {
+                Some(x) => x,
+                None => anyhow::bail!("Nation {} not found.", nation),
+            };
+            let client = client();
+            // The API has no way to restore a nation,
+            // so all we can do is tell whether the site's restore page is needed.
+            match fetch(&client, api::Target::Nation(&nation.name), vec![api::Shard::Name]).await {
+                Ok(_) => (),
+                Err(e) if is_not_found(&e) => {
+                    anyhow::bail!("{} has ceased to exist. Restore it by logging in at \
+                                   https://www.nationstates.net/page=boneyard?nation={}, then run this again.",
+                                  nation.name, nation::canonical_name(&nation.name));
+                },
+                Err(e) => return Err(e),
+            }
+            match ping(&client, nation).await {
+                Ok(_) => println!("{} exists and its credentials work.", nation.name),
+                Err(e) => return Err(anyhow::Error::new(e).context(format!("{} exists, but pinging it failed", nation.name))),
+            }
+            save_profile(&mut profile, &profile_path.path)?;
+        }
+        Opt::Show { nation } => {
+            let details = show_nation(&client(), &nation).await?;
+            // Keep what was fetched for `list --details`, if the nation is one of ours.
+            let path = ProfilePath::default().path;
+            let mut profile = load_profile(&path)?;
+            let canonical = nation::canonical_name(&nation);
+            if let Some(ours) = profile.nations.inner.iter_mut().find(|x| nation::canonical_name(&x.name) == canonical) {
+                if details.is_some() && !ours.transient {
+                    ours.details = details.map(|x| x.or_older(ours.details.as_ref()));
+                    save_profile(&mut profile, &path)?;
+                }
+            }
+        }
+        Opt::Endo(EndoCommand::Sweep { nation, region }) => {
+            let me = nation::canonical_name(&nation);
+            let missing: Vec<_> = endorsements_in(&client(), &nation, &region).await?.into_iter()
+                .filter(|(_, endorsements)| !endorsements.contains(&me))
+                .map(|(name, _)| name)
+                .collect();
+            eprintln!("{} hasn't endorsed {} WA members in {}", nation, missing.len(), region);
+            for name in &missing {
+                println!("{}", name);
+            }
+            // The list stays on its own on standard output, for other tools.
+            if let Some(first) = missing.first() {
+                eprintln!("Log in as {} and endorse them from each nation's page, like https://www.nationstates.net/nation={}",
+                          nation, first);
+            }
+        }
+        Opt::Endo(EndoCommand::Withdraw { nation, target, all_except_file }) => {
+            let client = client();
+            let me = nation::canonical_name(&nation);
+            let data = fetch(&client, api::Target::Nation(&nation), vec![api::Shard::Region]).await?;
+            let region = shard!(data, Region).unwrap_or_default();
+            let data = fetch(&client, api::Target::Region(&region), vec![api::Shard::Delegate]).await?;
+            let delegate = shard!(data, Delegate).and_then(region::officeholder);
+            let targets = match (target, all_except_file) {
+                (Some(target), _) => {
+                    let data = fetch(&client, api::Target::Nation(&target), vec![api::Shard::Endorsements]).await?;
+                    vec![(nation::canonical_name(&target), shard!(data, Endorsements).unwrap_or_default())]
+                },
+                (None, Some(path)) => {
+                    let text = std::fs::read_to_string(&path)
+                        .map_err(|e| anyhow::anyhow!("Couldn't read {}: {}", path.display(), e))?;
+                    let keep: std::collections::HashSet<_> = text.lines().map(str::trim).filter(|x| !x.is_empty())
+                        .map(nation::canonical_name).collect();
+                    endorsements_in(&client, &nation, &region).await?.into_iter()
+                        .filter(|(name, _)| !keep.contains(name))
+                        .collect()
+                },
+                (None, None) => unreachable!("structopt requires a target without --all-except-file"),
+            };
+            let targets: Vec<_> = targets.into_iter().filter(|(_, endorsements)| endorsements.contains(&me)).collect();
+            if targets.is_empty() {
+                println!("{} has no endorsements to withdraw.", nation);
+                return Ok(());
+            }
+            print_withdrawals(&nation, &targets, delegate.as_deref());
+            println!("Log in as {} and withdraw them from each nation's page, like https://www.nationstates.net/nation={}",
+                     nation, targets[0].0);
+        }
+        Opt::Economy { region: Some(region), .. } => {
+            let aggregate = region_economy(&client(), &region).await?;
+            let summary = match aggregate.summary() {
+                Some(x) => x,
+                None => anyhow::bail!("No nations in {}", region),
+            };
+            println!("{}: {} nations, totals and averages", region, aggregate.nations);
+            print_economy(&summary);
+            println!("    industries:");
+            for (industry, count) in aggregate.industries.iter().sorted_by_key(|(_, &count)| std::cmp::Reverse(count)) {
+                println!("        {:>5}  {}", count, industry);
+            }
+        }
+        Opt::Economy { nation, .. } => {
+            // Presence is enforced by `required_unless`.
+            let nation = nation.unwrap();
+            let data = fetch(&client(), api::Target::Nation(&nation), economy::shards()).await?;
+            println!("{}", nation);
+            print_economy(&Economy::from_shards(data.inner));
+        }
+        Opt::Compare { a, b, mut scales, json } => {
+            if scales.is_empty() {
+                scales = vec![CensusScale::CivilRights, CensusScale::Economy, CensusScale::PoliticalFreedom];
+            }
+            let comparison = compare(&client(), [&a, &b], scales).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&comparison)?);
+            } else {
+                print_comparison(&comparison);
+            }
+        }
+        Opt::Census { list_scales: true, .. } => {
+            for scale in CensusScale::ALL {
+                println!("{:>3}  {}", scale.id(), scale);
+            }
+        }
+        Opt::Wa(WaCommand::Proposals { council, watch: Some(id), interval }) => {
+            watch_proposal(&client(), council, &id, interval).await?;
+        }
+        Opt::Wa(WaCommand::Proposals { council, watch: None, .. }) => {
+            let proposals = fetch_proposals(&client(), council).await?;
+            if proposals.inner.is_empty() {
+                println!("No proposals in the {} queue.", council);
+            }
+            for proposal in proposals.inner {
+                let category = if proposal.option.is_empty() {
+                    proposal.category.clone()
+                } else {
+                    format!("{}: {}", proposal.category, proposal.option)
+                };
+                println!("{}", proposal.name);
+                println!("    id: {}", proposal.id);
+                println!("    category: {}", category);
+                println!("    proposed by: {} on {}", proposal.proposed_by, proposal.created.format("%Y-%m-%d"));
+                println!("    approvals: {}", proposal.approvals.len());
+                if council == Council::General {
+                    println!("    legality: {}", proposal.legal_status());
+                    if let Some(gensec) = &proposal.gensec {
+                        let rulings = gensec.legal.entries.iter().map(|r| ("legal", r))
+                            .chain(gensec.illegal.entries.iter().map(|r| ("illegal", r)));
+                        for (ruling, entry) in rulings {
+                            println!("        {} by {}: {}", ruling, entry.nation, entry.reason);
+                        }
+                    }
+                }
+            }
+        }
+        Opt::Wa(WaCommand::Join { nation }) => wa_membership(&client(), &nation, false).await?,
+        Opt::Wa(WaCommand::Leave { nation }) => wa_membership(&client(), &nation, true).await?,
+        Opt::Wa(WaCommand::Votes { council, resolution, chart, output, log }) => {
+            if chart && output != Output::Text {
+                anyhow::bail!("--chart only goes with text output.");
+            }
+            let shards = match resolution {
+                ResolutionId::Current => vec![
+                    api::Shard::Resolution { id: None },
+                    api::Shard::VoteTrack,
+                    api::Shard::DelegateLog,
+                    api::Shard::DelegateVotes,
+                ],
+                ResolutionId::Passed(id) => vec![api::Shard::Resolution { id: Some(id) }],
+            };
+            let data = fetch(&client(), api::Target::Wa(council), shards).await?;
+            let resolution = shard!(data, Resolution).filter(|x| x.exists());
+            let resolution = match resolution {
+                Some(x) => x,
+                None => anyhow::bail!("No such resolution in the {}.", council),
+            };
+            if output != Output::Text {
+                let table = Table {
+                    columns: vec!["hour".into(), "for".into(), "against".into()],
+                    rows: resolution.vote_track().enumerate()
+                        .map(|(hour, (votes_for, votes_against))| {
+                            vec![hour.to_string(), votes_for.to_string(), votes_against.to_string()]
+                        })
+                        .collect(),
+                };
+                print_table(&table, output);
+                return Ok(());
+            }
+            println!("{}", resolution.name);
+            println!("    category: {}", resolution.category);
+            println!("    proposed by: {}", resolution.proposed_by);
+            let total = (resolution.votes_for + resolution.votes_against).max(1) as f64;
+            println!("    for: {} votes ({:.1}%), {} nations",
+                     resolution.votes_for, 100.0 * resolution.votes_for as f64 / total, resolution.nations_for);
+            println!("    against: {} votes ({:.1}%), {} nations",
+                     resolution.votes_against, 100.0 * resolution.votes_against as f64 / total, resolution.nations_against);
+            let mut delegates: Vec<_> = resolution.delegates_for.delegates.iter().map(|d| ("for", d))
+                .chain(resolution.delegates_against.delegates.iter().map(|d| ("against", d)))
+                .collect();
+            delegates.sort_by_key(|(_, delegate)| core::cmp::Reverse(delegate.votes));
+            if !delegates.is_empty() {
+                println!("Largest delegate votes:");
+                for (side, delegate) in delegates.iter().take(10) {
+                    println!("    {:<30} {:>6} {:<8} since {}",
+                             delegate.nation, delegate.votes, side, delegate.timestamp.format("%Y-%m-%d %H:%M"));
+                }
+            }
+            if chart {
+                print!("{}", vote_chart(&resolution, 60));
+            }
+            if log {
+                println!("Delegate log:");
+                for entry in &resolution.delegate_log.entries {
+                    println!("    {} {} {} ({} votes)",
+                             entry.timestamp.format("%Y-%m-%d %H:%M"), entry.nation, entry.action, entry.votes);
+                }
+            }
+        }
+        Opt::Happenings { region, nation, only, limit, follow, interval, output } => {
+            let view = match (region, nation) {
+                (Some(region), _) => Some(View::Region(region)),
+                (None, Some(nation)) => Some(View::Nation(nation)),
+                (None, None) => None,
+            };
+            show_happenings(&client(), view, &only, limit, follow, interval, output).await?;
+        }
+        Opt::Region(RegionCommand::Watch { region, interval }) => {
+            let client = client();
+            loop {
+                watch_region(&client, &region).await?;
+                match interval {
+                    Some(secs) => idle(std::time::Duration::from_secs(secs)).await,
+                    None => break,
+                }
+                if interrupted() {
+                    break;
+                }
+            }
+        }
+        Opt::Influence { profile: profile_path, group, nation, project, targets } => {
+            let names = match nation {
+                Some(nation) => vec![nation],
+                None => {
+                    let profile = load_profile(&profile_path.path)?;
+                    let names: Vec<_> = profile.nations.inner.iter()
+                        .filter(|x| group.as_deref().is_none_or(|group| x.in_group(group)))
+                        .map(|x| x.name.clone())
+                        .collect();
+                    if names.is_empty() {
+                        anyhow::bail!("No nations in {}.", group.map_or_else(|| profile_path.to_string(), |x| format!("group {}", x)));
+                    }
+                    names
+                },
+            };
+            track_influence(&client(), &names, project, &targets).await?;
+        }
+        Opt::Region(RegionCommand::Audit { region }) => {
+            audit_region(&client(), &region).await?;
+        }
+        Opt::Zday(ZdayCommand::Act { nation, action, target }) => {
+            zday_act(&client(), &nation, &action, target.as_deref()).await?;
+        }
+        Opt::Zday(ZdayCommand::Region { region, monitor, interval }) => {
+            zday_region(&client(), &region, monitor, interval).await?;
+        }
+        Opt::Move { profile, nation, region, update } => {
+            move_nation(&client(), &profile.path, &nation, &region, update).await?;
+        }
+        Opt::Region(RegionCommand::Info { region }) => {
+            region_info(&client(), &region).await?;
+        }
+        Opt::Region(RegionCommand::UpdateTime { region }) => {
+            region_update_time(&client(), &region).await?;
+        }
+        Opt::Region(RegionCommand::Factbook { region, raw }) => {
+            let data = fetch(&client(), api::Target::Region(&region), vec![api::Shard::Factbook]).await?;
+            let factbook = shard!(data, Factbook).unwrap_or_default();
+            if factbook.trim().is_empty() {
+                println!("{} has no factbook.", region);
+            } else if raw {
+                println!("{}", factbook);
+            } else {
+                println!("{}", render_text(&factbook));
+            }
+        }
+        Opt::Region(RegionCommand::Residency { region, inactive, max_age, output }) => {
+            let table = residency_table(&client(), &region, inactive, max_age).await?;
+            print_table(&table, output);
+        }
+        Opt::Region(RegionCommand::Tag { nation, region, add, remove }) => {
+            let (add, remove) = check_tags(&client(), &nation, &region, &add, &remove).await?;
+            if add.is_empty() && remove.is_empty() {
+                println!("Nothing to change.");
+                return Ok(());
+            }
+            for tag in &add {
+                println!("add:    {}", tag);
+            }
+            for tag in &remove {
+                println!("remove: {}", tag);
+            }
+            println!("Change them on the site: https://www.nationstates.net/page=region_control/region={}",
+                     nation::canonical_name(&region));
+        }
+        Opt::Region(RegionCommand::Embassy(command)) => {
+            let (action, args) = match command {
+                EmbassyCommand::Request(x) => (EmbassyAction::Request, x),
+                EmbassyCommand::Accept(x) => (EmbassyAction::Accept, x),
+                EmbassyCommand::Reject(x) => (EmbassyAction::Reject, x),
+                EmbassyCommand::Close(x) => (EmbassyAction::Close, x),
+            };
+            let EmbassyArgs { nation, region, target } = args;
+            check_embassy(&client(), action, &nation, &region, &target).await?;
+            println!("{} can {} the embassy between {} and {}.", nation, action, region, target);
+            println!("Do it on the site: https://www.nationstates.net/page=region_control/region={}", nation::canonical_name(&region));
+        }
+        Opt::Region(RegionCommand::Password(RegionPasswordCommand::Set { nation, region })) => {
+            if check_region_password(&client(), &nation, &region).await? {
+                println!("{} already has a password; the new one replaces it.", region);
+            }
+            println!("Set it on the site: https://www.nationstates.net/page=region_control/region={}",
+                     nation::canonical_name(&region));
+        }
+        Opt::Region(RegionCommand::Password(RegionPasswordCommand::Clear { nation, region })) => {
+            if !check_region_password(&client(), &nation, &region).await? {
+                println!("{} has no password.", region);
+                return Ok(());
+            }
+            println!("Clear it on the site: https://www.nationstates.net/page=region_control/region={}",
+                     nation::canonical_name(&region));
+        }
+        Opt::Region(RegionCommand::Eject(args)) => {
+            remove_nation(&client(), "eject", args).await?;
+        }
+        Opt::Region(RegionCommand::Ban(args)) => {
+            remove_nation(&client(), "ban", args).await?;
+        }
+        Opt::Region(RegionCommand::Wfe(WfeCommand::Set { nation, region, file })) => {
+            let new = std::fs::read_to_string(&file)
+                .map_err(|e| anyhow::anyhow!("Couldn't read {}: {}", file.display(), e))?
+                .replace("\r\n", "\n");
+            if !preview_wfe(&client(), &nation, &region, &new).await? {
+                println!("{} is the same as the current WFE of {}.", file.display(), region);
+                return Ok(());
+            }
+            println!("Paste {} in on the site: https://www.nationstates.net/page=region_control/region={}",
+                     file.display(), nation::canonical_name(&region));
+        }
+        Opt::Regions { tags, page, per_page, output } => {
+            if tags.len() > 10 {
+                anyhow::bail!("The API accepts at most 10 tags, got {}.", tags.len());
+            }
+            let data = fetch(&client(), api::Target::World, vec![api::Shard::RegionsByTag { tags }]).await?;
+            let regions = shard!(data, Regions).unwrap_or_default();
+            if output != Output::Text {
+                let table = Table {
+                    columns: vec!["region".into()],
+                    rows: regions.into_iter().map(|x| vec![x]).collect(),
+                };
+                print_table(&table, output);
+                return Ok(());
+            }
+            let per_page = per_page.max(1);
+            let pages = regions.len().div_ceil(per_page);
+            for region in regions.iter().skip((page.max(1) - 1) * per_page).take(per_page) {
+                println!("{}", region);
+            }
+            println!("Page {} of {} ({} regions)", page, pages.max(1), regions.len());
+        }
+        Opt::Dump(DumpCommand::Download { kinds }) => {
+            let client = client();
+            let mut index = Index::open(&index_path())?;
+            for kind in kinds {
+                println!("Downloading {} dump...", kind);
+                let fetched = dump::download(&client, kind, &data_dir().join("dumps")).await?;
+                let day = dump::day_of(fetched.path()).expect("downloaded dumps are named by day");
+                if let dump::Fetched::Unchanged(_) = fetched {
+                    if index.has_day(kind, day)? {
+                        println!("The {} dump hasn't changed since {}", kind, day);
+                        continue;
+                    }
+                }
+                let path = fetched.path();
+                let count = index.ingest(path, kind, day)?;
+                println!("Indexed {} {} from {}", count, kind, day);
+            }
+        }
+        Opt::Dump(DumpCommand::Ingest { file, kind, date }) => {
+            let day = match date.or_else(|| dump::day_of(&file)) {
+                Some(x) => x,
+                None => anyhow::bail!("Couldn't tell what day {} is from, pass --date.", file.display()),
+            };
+            let count = Index::open(&index_path())?.ingest(&file, kind, day)?;
+            println!("Indexed {} {} from {}", count, kind, day);
+        }
+        Opt::Dump(DumpCommand::Sql { query, output }) => {
+            print_table(&Index::open(&index_path())?.query(&query)?, output);
+        }
+        Opt::Dump(DumpCommand::Diff { from, to, what, min_swing }) => {
+            let index = Index::open(&index_path())?;
+            for day in &[from, to] {
+                if !index.has_day(what, *day)? {
+                    anyhow::bail!("No {} dump indexed for {}.", what, day);
+                }
+            }
+            match what {
+                dump::Kind::Regions => {
+                    let diff = index.region_diff(from, to, min_swing)?;
+                    println!("Delegate changes: {}", diff.delegates.len());
+                    for change in &diff.delegates {
+                        let name = |x: &Option<String>| x.clone().unwrap_or_else(|| "none".to_owned());
+                        println!("    {}: {} -> {}", change.region, name(&change.before), name(&change.after));
+                    }
+                    println!("Founders that ceased to exist: {}", diff.founder_ctes.len());
+                    for (region, founder) in &diff.founder_ctes {
+                        println!("    {}: {}", region, founder);
+                    }
+                    if !index.has_day(dump::Kind::Nations, to)? {
+                        println!("    (index the nations dump for {} to check founders)", to);
+                    }
+                    println!("Nation count swings of {} or more: {}", min_swing, diff.swings.len());
+                    for swing in &diff.swings {
+                        println!("    {}: {} -> {} ({:+})", swing.region, swing.before, swing.after,
+                                 swing.after as i64 - swing.before as i64);
+                    }
+                    // The regions dump doesn't say which regions have passwords.
+                    println!("Password changes: not available from dumps");
+                },
+                dump::Kind::Nations => {
+                    let diff = index.nation_diff(from, to)?;
+                    println!("Moves: {}", diff.moves.len());
+                    for (nation, old, new) in &diff.moves {
+                        println!("    {}: {} -> {}", nation, old, new);
+                    }
+                    println!("Ceased to exist: {}", diff.ceased.len());
+                    for (nation, region) in &diff.ceased {
+                        println!("    {} (last in {})", nation, region);
+                    }
+                },
+            }
+        }
+        Opt::Dump(DumpCommand::Regions { founderless, min_nations, date, output }) => {
+            let index = Index::open(&index_path())?;
+            let day = match date {
+                Some(x) => x,
+                None => match index.latest_day(dump::Kind::Regions)? {
+                    Some(x) => x,
+                    None => anyhow::bail!("No regions dump indexed yet, run `nation dump download` first."),
+                },
+            };
+            let filter = RegionFilter { founderless, min_nations };
+            print_table(&index.regions(day, &filter)?, output);
+        }
+        Opt::Cards(CardsCommand::Download { season }) => {
+            let client = client();
+            let mut index = Index::open(&index_path())?;
+            for season in season {
+                println!("Downloading season {} cards...", season);
+                let path = cards::download(&client, season, &data_dir().join("dumps")).await?;
+                let count = index.ingest_cards(&path, season)?;
+                println!("Indexed {} cards from season {}", count, season);
+            }
+        }
+        Opt::Cards(CardsCommand::Value { nation, lookups, output }) => {
+            value_deck(&client(), &nation, lookups, output).await?;
+        }
+        Opt::Cards(CardsCommand::Watch { card, season, max_bid, interval, notify_command }) => {
+            let notifier = Notifier::from_command(notify_command);
+            watch_card(&client(), card, season, max_bid, interval, &notifier).await?;
+        }
+        Opt::Cards(CardsCommand::Search { name, rarity, season, output }) => {
+            let table = Index::open(&index_path())?.cards(&CardFilter { name, rarity, season })?;
+            if table.rows.is_empty() {
+                println!("No matching cards, run `nation cards download` first if you haven't.");
+            } else {
+                print_table(&table, output);
+            }
+        }
+        Opt::Issues(IssuesCommand::List { profile: profile_path, nation }) => {
+            let mut profile = load_profile(&profile_path.path)?;
+            let nation = match profile.nations.inner.iter_mut().find(|x| x.name == nation) {
+                Some(x) => x,
+                None => anyhow::bail!("Nation {} not found.", nation),
+            };
+            let issues = fetch_issues(&client(), nation).await?;
+            save_profile(&mut profile, &profile_path.path)?;
+            for issue in issues {
+                println!("#{} {}", issue.id, issue.title);
+                for line in render_text(&issue.text).lines() {
+                    println!("    {}", line);
+                }
+                for option in issue.options {
+                    println!("    {}: {}", option.id, render_text(&option.text));
+                }
+            }
+        }
+        Opt::Issues(IssuesCommand::Answer { profile: profile_path, nation, issue, option, notify_command }) => {
+            let mut profile = load_profile(&profile_path.path)?;
+            let nation = match profile.nations.inner.iter_mut().find(|x| x.name == nation) {
+                Some(x) => x,
+                None => anyhow::bail!("Nation {} not found.", nation),
+            };
+            let client = client();
+            let title = fetch_issues(&client, nation).await?.into_iter()
+                .find(|x| x.id == issue)
+                .map(|x| x.title);
+            let command = api::Command {
+                nation: &nation.name,
+                auth: &nation.auth,
+                action: api::Action::AnswerIssue { issue, option },
+                version: api_version(),
+                readonly: nation.readonly,
+            };
+            if dry_run() {
+                println!("Would send:\n{}", command.preview());
+                return Ok(());
+            }
+            let action = command.action;
+            let outcome = match session(&client, nation).command::<AnswerResponse>(action).await {
+                Ok(response) => response.data.issue,
+                Err(e) => {
+                    save_profile(&mut profile, &profile_path.path)?;
+                    return Err(e.into());
+                },
+            };
+            let mut notification = None;
+            if outcome.error.is_none() {
+                nation.last_issue = Some(Utc::now());
+                metrics::answered();
+                notification = reclassify(nation, &outcome);
+            }
+            let name = nation.name.clone();
+            save_profile(&mut profile, &profile_path.path)?;
+            if let Some(error) = outcome.error {
+                anyhow::bail!("Couldn't answer issue #{}: {}", issue, error);
+            }
+            let mut index = Index::open(&index_path())?;
+            index.record_decision(&name, issue, Some(option), "answered by hand")?;
+            index.record_outcome(&name, title.as_deref(), &outcome)?;
+            println!("#{} {}", issue, title.unwrap_or_default());
+            print_outcome(&outcome);
+            if let (Some(command), Some(notification)) = (notify_command, notification) {
+                Notifier::Command(command).send(&notification)?;
+            }
+        }
+        Opt::Issues(IssuesCommand::History { nation, limit }) => {
+            let history = Index::open(&index_path())?.issue_history(&nation, limit)?;
+            if history.is_empty() {
+                println!("No issues answered for {} yet.", nation);
+            }
+            for answered in history {
+                let choice = match answered.option {
+                    issues::DISMISS => "dismissed".to_owned(),
+                    option => format!("option {}", option),
+                };
+                println!("{} #{} {}: {}", answered.answered.format("%Y-%m-%d %H:%M"), answered.issue,
+                         answered.title.as_deref().unwrap_or("(untitled)"), choice);
+                if let Some(description) = &answered.description {
+                    println!("    {}", description);
+                }
+                for headline in &answered.headlines {
+                    println!("    headline: {}", headline);
+                }
+                for line in &answered.reclassifications {
+                    println!("    reclassified: {}", line);
+                }
+                for banner in &answered.banners {
+                    println!("    unlocked banner: {}", banner);
+                }
+                for policy in &answered.new_policies {
+                    println!("    new policy: {}", policy);
+                }
+                for policy in &answered.removed_policies {
+                    println!("    removed policy: {}", policy);
+                }
+                for (id, change) in answered.effects.iter().take(5) {
+                    let scale = CensusScale::from_id(*id).map_or_else(|| format!("scale {}", id), |x| x.to_string());
+                    println!("    {}: {:+.2}", scale, change);
+                }
+            }
+        }
+        Opt::Issues(IssuesCommand::Auto { profile: profile_path, nation, group, notify_command }) => {
+            // Reclassifications are printed with the rest of each outcome,
+            // so only a command needs telling about them.
+            let notifier = notify_command.map(Notifier::Command);
+            let config = Config::load(&config_path())?;
+            let mut profile = load_profile(&profile_path.path)?;
+            let client = client();
+            let mut failed = 0;
+            for nation in select(&mut profile, nation.as_deref(), group.as_deref())? {
+                if interrupted() {
+                    break;
+                }
+                let policy = match config.policy(&nation.name) {
+                    Some(x) => x,
+                    None => {
+                        println!("No issue policy for {} in {}.", nation.name, config_path().display());
+                        failed += 1;
+                        continue;
+                    },
+                };
+                let result = match ping(&client, nation).await {
+                    Ok(_) => auto_answer(&client, nation, policy, notifier.as_ref()).await,
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = result {
+                    println!("{}: {}", nation.name, e);
+                    failed += 1;
+                }
+            }
+            // Save new credentials even if answering failed partway.
+            save_profile(&mut profile, &profile_path.path)?;
+            if failed > 0 {
+                return Err(PartialFailure(format!("Answering failed for {} nations.", failed)).into());
+            }
+        }
+        Opt::Tag(TagCommand::Add { profile: profile_path, nation, group }) => {
+            let mut profile = load_profile(&profile_path.path)?;
+            for nation in select(&mut profile, Some(&nation), None)? {
+                if !nation.join(&group) {
+                    println!("{} is already in {}.", nation.name, group);
+                }
+            }
+            save_profile(&mut profile, &profile_path.path)?;
+        }
+        Opt::Tag(TagCommand::Remove { profile: profile_path, nation, group }) => {
+            let mut profile = load_profile(&profile_path.path)?;
+            for nation in select(&mut profile, Some(&nation), None)? {
+                if !nation.leave(&group) {
+                    println!("{} isn't in {}.", nation.name, group);
+                }
+            }
+            save_profile(&mut profile, &profile_path.path)?;
+        }
+        Opt::Tag(TagCommand::List { profile: profile_path }) => {
+            let profile = load_profile(&profile_path.path)?;
+            let groups = profile.nations.inner.iter()
+                .flat_map(|nation| nation.groups.iter().map(move |group| (group.to_lowercase(), &nation.name)))
+                .into_group_map();
+            if groups.is_empty() {
+                println!("No groups in {}.", profile_path);
+            }
+            for (group, nations) in groups.into_iter().sorted() {
+                println!("{} ({}): {}", group, nations.len(), nations.iter().join(", "));
+            }
+        }
+        Opt::ApiVersion => {
+            let current = api::current_version(&client()).await?;
+            println!("Requesting version {} (built for {}), the API is at version {}.",
+                     api_version(), nation::API_VERSION, current);
+            if api_version() < current {
+                println!("Warning: version {} is behind the API and may be deprecated. \
+                          Newer shards and fields won't be available.", api_version());
+            } else if api_version() > current {
+                println!("Warning: version {} is newer than the API knows about.", api_version());
+            }
+        }
+        Opt::Flag { all: true, out, profile, .. } => {
+            let profile = load_profile(&profile.path)?;
+            let dir = out.unwrap_or_default();
+            let client = client();
+            for nation in &profile.nations.inner {
+                match download_flag(&client, &nation.name, None, &dir).await {
+                    Ok(path) => println!("{}: {}", nation.name, path.display()),
+                    Err(e) => eprintln!("{}: {}", nation.name, e),
+                }
+            }
+        }
+        Opt::Flag { nation: Some(nation), out, .. } => {
+            let path = download_flag(&client(), &nation, out, Path::new("")).await?;
+            println!("Saved {}", path.display());
+        }
+        Opt::Flag { nation: None, .. } => unreachable!("structopt requires a nation without --all"),
+        Opt::Banners { nation } => {
+            let data = fetch(&client(), api::Target::Nation(&nation), vec![api::Shard::Banners]).await?;
+            for code in shard!(data, Banners).unwrap_or_default().codes {
+                println!("{:<6} {}", code, banners::image_url(&code));
+            }
+        }
+        Opt::Set(command) => {
+            let (nation, change) = match command {
+                SetCommand::Motto { nation, motto } => (nation, Change::Motto(motto)),
+                SetCommand::Pretitle { nation, pretitle } => (nation, Change::Pretitle(pretitle)),
+                SetCommand::Flag { nation, file } => {
+                    let bytes = std::fs::read(&file).map_err(|e| anyhow::anyhow!("Couldn't read {}: {}", file.display(), e))?;
+                    (nation, Change::Flag(bytes))
+                },
+                SetCommand::Banner { nation, banner } => (nation, Change::Banner(banner)),
+            };
+            let planned = plan_changes(&client(), &nation, std::slice::from_ref(&change)).await?;
+            if planned.is_empty() {
+                println!("{} already has that {}.", nation, change.setting());
+                return Ok(());
+            }
+            for (setting, old, new) in planned {
+                println!("{}: {} -> {}", setting, old, term::good(new));
+            }
+            println!("Log in as {} and change it on the site: {}", nation, settings::SETTINGS_PAGE);
+        }
+        Opt::Theme(ThemeCommand::Apply { profile: profile_path, group, template }) => {
+            let theme = settings::Theme::load(&template)?;
+            let profile = load_profile(&profile_path.path)?;
+            let names: Vec<_> = profile.nations.inner.iter()
+                .filter(|x| group.as_deref().is_none_or(|group| x.in_group(group)))
+                .map(|x| x.name.clone())
+                .collect();
+            if names.is_empty() {
+                anyhow::bail!("No nations in {}.", group.map_or_else(|| profile_path.to_string(), |x| format!("group {}", x)));
+            }
+            if !plan_theme(&client(), &names, &theme).await? || dry_run() {
+                return Ok(());
+            }
+            println!("Log in as each nation above and make the changes on the site: {}", settings::SETTINGS_PAGE);
+        }
+        Opt::Policies { nation } => {
+            let data = fetch(&client(), api::Target::Nation(&nation), vec![api::Shard::Policies]).await?;
+            let policies = shard!(data, Policies).unwrap_or_default().inner;
+            if policies.is_empty() {
+                println!("{} has no national policies.", nation);
+            }
+            for (category, policies) in &policies.iter().sorted_by(|a, b| a.category.cmp(&b.category)).group_by(|x| &x.category) {
+                println!("{}", category);
+                for policy in policies {
+                    println!("    {}: {}", policy.name, policy.description);
+                }
+            }
+        }
+        Opt::Rmb(RmbCommand::Read { region, limit, follow, interval, output }) => {
+            rmb_read(&client(), &region, limit, follow, interval, output).await?;
+        }
+        Opt::Rmb(RmbCommand::Suppress { nation, region, post, undo }) => {
+            require_power(&client(), &nation, &region, Power::Communications).await?;
+            println!("{} can {} post {}. Do it on the site: https://www.nationstates.net/page=rmb/postid={}",
+                     nation, if undo { "unsuppress" } else { "suppress" }, post, post);
+        }
+        Opt::Dispatch(DispatchCommand::List { nation, output }) => {
+            let data = fetch(&client(), api::Target::Nation(&nation), vec![api::Shard::DispatchList]).await?;
+            let dispatches = shard!(data, DispatchList).unwrap_or_default().inner;
+            if dispatches.is_empty() && output == Output::Text {
+                println!("{} has no dispatches.", nation);
+                return Ok(());
+            }
+            let table = Table {
+                columns: ["ID", "Title", "Category", "Created", "Views", "Score"].iter().map(|&x| x.into()).collect(),
+                rows: dispatches.iter().map(|x| vec![
+                    x.id.to_string(),
+                    x.title.clone(),
+                    format!("{}: {}", x.category, x.subcategory),
+                    x.created.format("%Y-%m-%d").to_string(),
+                    x.views.to_string(),
+                    x.score.to_string(),
+                ]).collect(),
+            };
+            print_table(&table, output);
+        }
+        Opt::Dispatch(DispatchCommand::Read { id, markdown }) => {
+            let data = fetch(&client(), api::Target::World, vec![api::Shard::Dispatch { id }]).await?;
+            let dispatch = shard!(data, Dispatch).ok_or_else(|| anyhow::anyhow!("response did not contain dispatch {}", id))?;
+            let edited = dispatch.edited.map(|x| format!(", edited {}", x.format("%Y-%m-%d"))).unwrap_or_default();
+            if markdown {
+                println!("# {}\n", dispatch.title);
+                println!("*{}: {}, by {}, {}{}*\n", dispatch.category, dispatch.subcategory, dispatch.author,
+                         dispatch.created.format("%Y-%m-%d"), edited);
+            } else {
+                println!("{}", term::bold(&dispatch.title));
+                println!("{}: {}, by {}, {}{}\n", dispatch.category, dispatch.subcategory, dispatch.author,
+                         dispatch.created.format("%Y-%m-%d"), edited);
+            }
+            if markdown {
+                println!("{}", bbcode::render(&dispatch.text, bbcode::Format::Markdown));
+            } else {
+                println!("{}", render_text(&dispatch.text));
+            }
+        }
+        Opt::Poll(PollCommand::Create { nation, region, question, options, duration: Age(duration) }) => {
+            let draft = poll::Draft { question, options, duration }.check()?;
+            require_power(&client(), &nation, &region, Power::Polls).await?;
+            println!("{}", draft.question);
+            for (i, option) in draft.options.iter().enumerate() {
+                println!("    {}. {}", i + 1, option);
+            }
+            match duration.num_hours() {
+                hours if hours % 24 == 0 => println!("open for {} days", hours / 24),
+                hours => println!("open for {} hours", hours),
+            }
+            println!("Open it from the region's page: https://www.nationstates.net/region={}", nation::canonical_name(&region));
+        }
+        Opt::Report(ReportCommand::Census { profile: profile_path, group, scale, output }) => {
+            let profile = load_profile(&profile_path.path)?;
+            let names: Vec<_> = profile.nations.inner.iter()
+                .filter(|x| group.as_deref().is_none_or(|group| x.in_group(group)))
+                .map(|x| x.name.clone())
+                .collect();
+            if names.is_empty() {
+                anyhow::bail!("No nations in {}.", group.map_or_else(|| profile_path.to_string(), |x| format!("group {}", x)));
+            }
+            census_report(&client(), &names, scale, output).await?;
+        }
+        Opt::World(WorldCommand::Newnations { founded_in, follow, interval, output }) => {
+            new_nations(&client(), founded_in.as_deref(), follow, interval, output).await?;
+        }
+        Opt::Tg(TgCommand::Queue) => {
+            let data = fetch(&client(), api::Target::World, vec![api::Shard::TgQueue]).await?;
+            let queue = match shard!(data, TgQueue) {
+                Some(x) => x,
+                None => anyhow::bail!("response did not contain the telegram queue"),
+            };
+            println!("manual: {}", queue.manual);
+            println!("mass:   {}", queue.mass);
+            println!("api:    {}", queue.api);
+        }
+        Opt::Tg(TgCommand::Template(TemplateCommand::Add { name, tgid, key, recruitment })) => {
+            let path = templates_path();
+            let mut templates = Templates::load(&path)?;
+            let replaced = templates.templates.insert(name.clone(), Template { tgid, secret: key, recruitment }).is_some();
+            if dry_run() {
+                println!("Would {} template {}.", if replaced { "replace" } else { "save" }, name);
+                return Ok(());
+            }
+            templates.save(&path)?;
+            println!("{} template {}.", if replaced { "Replaced" } else { "Saved" }, name);
+        }
+        Opt::Tg(TgCommand::Template(TemplateCommand::Remove { name })) => {
+            let path = templates_path();
+            let mut templates = Templates::load(&path)?;
+            if templates.templates.remove(&name).is_none() {
+                anyhow::bail!("No template named {:?}.", name);
+            }
+            if dry_run() {
+                println!("Would remove template {}.", name);
+                return Ok(());
+            }
+            templates.save(&path)?;
+            println!("Removed template {}.", name);
+        }
+        Opt::Tg(TgCommand::Template(TemplateCommand::List)) => {
+            let templates = Templates::load(&templates_path())?;
+            if templates.templates.is_empty() {
+                println!("No saved templates.");
+            }
+            for (name, template) in &templates.templates {
+                let kind = if template.recruitment { " recruitment" } else { "" };
+                println!("{:<20} {}{}", name, template, kind);
+            }
+        }
+        Opt::Tg(TgCommand::Send { template, client_key, to }) => {
+            let template = match template.parse::<Template>() {
+                Ok(x) => x,
+                Err(_) => saved_template(&template)?,
+            };
+            let client = client();
+            for (i, recipient) in to.iter().enumerate() {
+                if i > 0 {
+                    metrics::throttle(template.interval()).await;
+                }
+                if interrupted() {
+                    break;
+                }
+                let tg = Telegram { client_key: &client_key, template: &template, to: recipient };
+                if dry_run() {
+                    println!("Would send:\n{}", tg.preview());
+                    continue;
+                }
+                if let Err(e) = tg.send(&client).await {
+                    return Err(anyhow::Error::new(e).context(format!("Sending to {} failed", recipient)));
+                }
+                println!("[{}] Sent {} to {}", Utc::now().format("%Y-%m-%d %H:%M:%S"), template, recipient);
+            }
+        }
+        Opt::Audit { nation, operator, limit, output } => {
+            let config = Config::load(&config_path())?;
+            let path = audit_path(&config);
+            let matches = |filter: &Option<String>, value: &Option<String>| match (filter, value) {
+                (Some(filter), Some(value)) => nation::canonical_name(filter) == nation::canonical_name(value),
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+            let entries: Vec<_> = audit::read(&path)?.into_iter().rev()
+                .filter(|x| matches(&nation, &x.nation) && matches(&operator, &x.operator))
+                .take(limit)
+                .collect();
+            if entries.is_empty() && output == Output::Text {
+                println!("No matching entries in {}.", path.display());
+                return Ok(());
+            }
+            let params = |x: &audit::Entry| x.params.iter()
+                .filter(|(k, _)| !["nation", "c", "a", "v"].contains(&k.as_str()))
+                .map(|(k, v)| format!("{}={}", k, v))
+                .join(" ");
+            let table = Table {
+                columns: ["Time", "Operator", "Nation", "Command", "Params", "Outcome"].iter().map(|&x| x.into()).collect(),
+                rows: entries.iter()
+                    .map(|x| vec![
+                        x.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        x.operator.clone().unwrap_or_default(),
+                        x.nation.clone().unwrap_or_default(),
+                        x.command.clone(),
+                        params(x),
+                        x.outcome.clone(),
+                    ])
+                    .collect(),
+            };
+            print_table(&table, output);
+        }
+        Opt::Welcome { region, template, client_key, daemon, poll } => {
+            let template = match template.parse::<Template>() {
+                Ok(x) => x,
+                Err(_) => saved_template(&template)?,
+            };
+            welcome(&client(), &region, &template, &client_key, daemon, poll).await?;
+        }
+        Opt::Recruit { region, template, client_key, exclude_file, poll } => {
+            let template = match template.parse::<Template>() {
+                // Given to `recruit` directly, so it's taken to be a recruitment template.
+                Ok(x) => Template { recruitment: true, ..x },
+                Err(_) => saved_template(&template)?,
+            };
+            if !template.recruitment {
+                anyhow::bail!("{} isn't saved as a recruitment template. Recruitment telegrams have to come from one; \
+                               if it's marked for recruitment on the site, save it again with --recruitment.", template);
+            }
+            recruit(&client(), &region, &template, &client_key, exclude_file.as_deref(), poll).await?;
+        }
+        Opt::Exists { mut names, file, offline, output } => {
+            if let Some(path) = file {
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("Couldn't read {}: {}", path.display(), e))?;
+                names.extend(text.lines().map(str::trim).filter(|x| !x.is_empty()).map(str::to_owned));
+            }
+            let names: Vec<_> = names.into_iter().unique_by(|x| nation::canonical_name(x)).collect();
+            let (available, taken) = split_taken(&client(), &names, offline).await?;
+            if output != Output::Text {
+                let rows = available.iter().map(|x| vec![x.clone(), "available".into()])
+                    .chain(taken.iter().map(|x| vec![x.clone(), "taken".into()]))
+                    .collect();
+                print_table(&Table { columns: vec!["name".into(), "status".into()], rows }, output);
+                return Ok(());
+            }
+            println!("Available ({}):", available.len());
+            for name in &available {
+                println!("    {}", name);
+            }
+            println!("Taken ({}):", taken.len());
+            for name in &taken {
+                println!("    {}", name);
+            }
+        }
+        Opt::Names { pattern, count, list, check, offline } => {
+            use rand::seq::SliceRandom;
+            let mut words = names::Words::default();
+            for list in &list {
+                let (name, path) = match list.split_once('=') {
+                    Some(x) => x,
+                    None => anyhow::bail!("--list takes name=file, not {:?}", list),
+                };
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("Couldn't read {}: {}", path, e))?;
+                words.insert(name, text.lines().map(str::trim).filter(|x| !x.is_empty()).map(str::to_owned).collect());
+            }
+            let mut candidates = words.expand(&pattern)?;
+            candidates.shuffle(&mut rand::thread_rng());
+            if !check {
+                candidates.truncate(count);
+                for name in &candidates {
+                    println!("{}", name);
+                }
+                return Ok(());
+            }
+            // Check only as many as are still needed, then more to make up for the taken ones.
+            let mut rest = &candidates[..];
+            let mut found = 0;
+            while found < count && !rest.is_empty() && !interrupted() {
+                let (batch, remaining) = rest.split_at((count - found).min(rest.len()));
+                rest = remaining;
+                let (available, _) = split_taken(&client(), batch, offline).await?;
+                for name in available {
+                    println!("{}", name);
+                    found += 1;
+                }
+            }
+        }
+        Opt::Top { scale, region, limit, output } => {
+            let table = top(&client(), scale, region.as_deref(), limit).await?;
+            if output != Output::Text {
+                print_table(&table, output);
+                return Ok(());
+            }
+            match &region {
+                Some(region) => println!("{} in {}", scale, region),
+                None => println!("{}", scale),
+            }
+            for row in &table.rows {
+                println!("{:>6}  {:<40} {:>14}", row[0], row[1], row[2]);
+            }
+        }
+        Opt::Census { scales, nation, history: true, from, to, output, .. } => {
+            // Presence is enforced by `required_unless`.
+            let nation = nation.unwrap();
+            let midnight = |date: NaiveDate| date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+            let mut shards = ShardSet::new().census(scales).mode(api::CensusMode::History);
+            if let Some(from) = from {
+                shards = shards.from(midnight(from));
+            }
+            if let Some(to) = to {
+                shards = shards.to(midnight(to));
+            }
+            let data = fetch(&client(), api::Target::Nation(&nation), shards.build()?).await?;
+            let census = shard!(data, Census).map_or_else(Vec::new, |x| x.scales);
+            let table = Table {
+                columns: ["Scale", "Date", "Score"].iter().map(|&x| x.into()).collect(),
+                rows: census.iter()
+                    .flat_map(|scale| {
+                        let name = CensusScale::from_id(scale.id)
+                            .map_or_else(|| format!("Scale {}", scale.id), |s| s.to_string());
+                        scale.points.iter().map(move |point| vec![
+                            name.clone(),
+                            point.timestamp.format("%Y-%m-%d").to_string(),
+                            point.score.to_string(),
+                        ])
+                    })
+                    .collect(),
+            };
+            print_table(&table, output);
+        }
+        Opt::Census { scales, nation, output, .. } => {
+            // Presence is enforced by `required_unless`.
+            let nation = nation.unwrap();
+            let req = api::Request {
+                shards: ShardSet::new().census(scales).build()?,
+                target: api::Target::Nation(&nation),
+                auth: None,
+                version: api_version(),
+            };
+            let client = client();
+            let data = req.send(&client).await?.data;
+            for shard in data.inner {
+                if let api::ResolvedShard::Census(census) = shard {
+                    let show = |x: Option<u32>| x.map_or_else(String::new, |x| x.to_string());
+                    let table = Table {
+                        columns: ["Scale", "Score", "World", "Region"].iter().map(|&x| x.into()).collect(),
+                        rows: census.scales.into_iter()
+                            .map(|score| vec![
+                                CensusScale::from_id(score.id)
+                                    .map_or_else(|| format!("Scale {}", score.id), |s| s.to_string()),
+                                score.score.map_or_else(String::new, |x| x.to_string()),
+                                show(score.rank),
+                                show(score.region_rank),
+                            ])
+                            .collect(),
+                    };
+                    if output != Output::Text {
+                        print_table(&table, output);
+                        continue;
+                    }
+                    for row in Some(&table.columns).into_iter().chain(&table.rows) {
+                        println!("{:<40} {:>14} {:>10} {:>10}", row[0], row[1], row[2], row[3]);
+                    }
+                }
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,144 @@
+//! Counters for long-running commands, served in the Prometheus text format.
+//!
+//! Counting is always on and costs an atomic add. Nothing listens for
+//! scrapes unless [`bind`] and [`serve`] are called, which the binary
+//! does with `--metrics`.
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "native")]
+use std::{net::SocketAddr, time::Duration};
+#[cfg(feature = "native")]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "native")]
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::api::Failure;
+
+static REQUESTS: AtomicU64 = AtomicU64::new(0);
+static RATE_LIMIT_WAITS: AtomicU64 = AtomicU64::new(0);
+/// Milliseconds, so short waits still add up.
+static RATE_LIMIT_WAIT_MILLIS: AtomicU64 = AtomicU64::new(0);
+static NATIONS_PINGED: AtomicU64 = AtomicU64::new(0);
+static ISSUES_ANSWERED: AtomicU64 = AtomicU64::new(0);
+
+/// Failure labels, in the order of `FAILURES`.
+const FAILURE_KINDS: [&str; 9] = [
+    "no_auth", "bad_auth", "bad_pin", "rate_limited", "malformed", "not_recorded", "network", "other", "read_only",
+];
+static FAILURES: [AtomicU64; 9] = [
+    AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0),
+    AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+fn failure_index(failure: &Failure) -> usize {
+    match failure {
+        Failure::NoAuth => 0,
+        Failure::BadAuth => 1,
+        Failure::BadPin => 2,
+        Failure::RateLimited(_) => 3,
+        Failure::Malformed(_) => 4,
+        Failure::NotRecorded(_) => 5,
+        Failure::Network(_) => 6,
+        Failure::Other(..) => 7,
+        Failure::ReadOnly(_) => 8,
+    }
+}
+
+/// An API request went out, or was answered from a cassette.
+pub(crate) fn requested() {
+    REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+pub(crate) fn failed(failure: &Failure) {
+    FAILURES[failure_index(failure)].fetch_add(1, Ordering::Relaxed);
+}
+pub fn pinged() {
+    NATIONS_PINGED.fetch_add(1, Ordering::Relaxed);
+}
+pub fn answered() {
+    ISSUES_ANSWERED.fetch_add(1, Ordering::Relaxed);
+}
+/// Sleep to stay under a rate limit, counting the wait.
+/// Waits out the rest of the API's window instead if the last response said it's used up.
+#[cfg(feature = "native")]
+pub async fn throttle(duration: Duration) {
+    let duration = duration.max(crate::ratelimit::exhausted_for().unwrap_or_default());
+    RATE_LIMIT_WAITS.fetch_add(1, Ordering::Relaxed);
+    RATE_LIMIT_WAIT_MILLIS.fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    tokio::time::sleep(duration).await;
+}
+
+/// Every counter, in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    // Samples are `(labels, value)`, with empty labels for a counter that has none.
+    let mut counter = |name: &str, help: &str, samples: &[(String, String)]| {
+        out.push_str(&format!("# HELP {} {}\n# TYPE {} counter\n", name, help, name));
+        for (labels, value) in samples {
+            if labels.is_empty() {
+                out.push_str(&format!("{} {}\n", name, value));
+            } else {
+                out.push_str(&format!("{}{{{}}} {}\n", name, labels, value));
+            }
+        }
+    };
+    let load = |x: &AtomicU64| vec![(String::new(), x.load(Ordering::Relaxed).to_string())];
+    counter("nation_api_requests_total", "API requests sent.", &load(&REQUESTS));
+    let failures: Vec<_> = FAILURE_KINDS.iter().zip(&FAILURES)
+        .map(|(kind, count)| (format!("kind=\"{}\"", kind), count.load(Ordering::Relaxed).to_string()))
+        .collect();
+    counter("nation_api_failures_total", "API requests that failed, by kind.", &failures);
+    counter("nation_rate_limit_waits_total", "Pauses to stay under a rate limit.", &load(&RATE_LIMIT_WAITS));
+    let seconds = RATE_LIMIT_WAIT_MILLIS.load(Ordering::Relaxed) as f64 / 1000.0;
+    counter("nation_rate_limit_wait_seconds_total", "Time spent paused for rate limits.",
+            &[(String::new(), seconds.to_string())]);
+    counter("nation_nations_pinged_total", "Successful nation pings.", &load(&NATIONS_PINGED));
+    counter("nation_issues_answered_total", "Issues answered or dismissed.", &load(&ISSUES_ANSWERED));
+    out
+}
+
+/// Listen for scrapes on `addr`.
+/// Kept apart from [`serve`] so a bad address fails before any work starts.
+#[cfg(feature = "native")]
+pub async fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    TcpListener::bind(addr).await
+}
+
+/// Answer `GET /metrics` on `listener` until the process exits.
+#[cfg(feature = "native")]
+pub async fn serve(listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(async move {
+                    if let Err(e) = respond(stream).await {
+                        eprintln!("Metrics request failed: {}", e);
+                    }
+                });
+            },
+            Err(e) => {
+                eprintln!("Couldn't accept metrics connection: {}", e);
+                // Errors like running out of file descriptors last a while,
+                // and retrying straight away would only spin.
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            },
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+async fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    // Only the request line matters, and scrapers send small requests.
+    let mut buf = [0; 1024];
+    let read = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..read]);
+    let mut words = request.lines().next().unwrap_or_default().split_whitespace();
+    let (status, content_type, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", "text/plain; version=0.0.4", render()),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "not found\n".to_owned()),
+        _ => ("405 Method Not Allowed", "text/plain", "method not allowed\n".to_owned()),
+    };
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, content_type, body.len(), body);
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown(std::net::Shutdown::Write)
+}
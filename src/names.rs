@@ -0,0 +1,112 @@
+//! Nation names made from patterns like `Frontier {adjective} {noun}`,
+//! for naming puppets.
+use core::str::FromStr;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Longest name the site accepts.
+pub const MAX_LENGTH: usize = 40;
+
+pub const ADJECTIVES: &[&str] = &[
+    "Amber", "Ancient", "Azure", "Bold", "Brave", "Bright", "Broken", "Calm", "Crimson", "Distant",
+    "Eastern", "Emerald", "Eternal", "Fallen", "Free", "Frozen", "Gilded", "Golden", "Grand", "Great",
+    "Hidden", "Holy", "Iron", "Last", "Lone", "Lost", "Northern", "Quiet", "Red", "Royal",
+    "Scarlet", "Silent", "Silver", "Southern", "Stone", "Sunken", "United", "Western", "Wild", "Winter",
+];
+pub const NOUNS: &[&str] = &[
+    "Archipelago", "Bay", "Canyon", "Cape", "Citadel", "Coast", "Colony", "Commonwealth", "Coves", "Dominion",
+    "Duchy", "Empire", "Federation", "Fjord", "Forest", "Frontier", "Harbor", "Haven", "Highlands", "Isles",
+    "Kingdom", "Lagoon", "Marches", "Meadow", "Mesa", "Outpost", "Peaks", "Plains", "Realm", "Reach",
+    "Republic", "Ridge", "Shore", "Steppe", "Straits", "Summit", "Tundra", "Union", "Valley", "Wilds",
+];
+
+/// Whether the site would take `name` for a new nation:
+/// letters, digits, spaces and hyphens, up to [`MAX_LENGTH`] characters.
+pub fn valid_name(name: &str) -> bool {
+    let name = name.trim();
+    !name.is_empty()
+        && name.chars().count() <= MAX_LENGTH
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    /// A word from the list with this name.
+    Slot(String),
+}
+
+/// Text with `{list}` slots, each filled with a word from that list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(Vec<Part>);
+#[derive(Error, Debug)]
+pub enum PatternError {
+    #[error("unclosed {{ in pattern")]
+    Unclosed,
+    #[error("empty {{}} in pattern")]
+    Empty,
+    #[error("no word list named {0:?}")]
+    UnknownList(String),
+}
+impl FromStr for Pattern {
+    type Err = PatternError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = input;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').ok_or(PatternError::Unclosed)? + start;
+            let slot = rest[start + 1..end].trim();
+            if slot.is_empty() {
+                return Err(PatternError::Empty);
+            }
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_owned()));
+            }
+            parts.push(Part::Slot(slot.to_lowercase()));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+        Ok(Pattern(parts))
+    }
+}
+
+/// Word lists to fill pattern slots from.
+#[derive(Debug, Clone)]
+pub struct Words(BTreeMap<String, Vec<String>>);
+impl Default for Words {
+    /// The built in `adjective` and `noun` lists.
+    fn default() -> Self {
+        let list = |words: &[&str]| words.iter().map(|&x| x.to_owned()).collect();
+        let mut lists = BTreeMap::new();
+        lists.insert("adjective".to_owned(), list(ADJECTIVES));
+        lists.insert("noun".to_owned(), list(NOUNS));
+        Words(lists)
+    }
+}
+impl Words {
+    /// Add a list, or replace the one with the same name.
+    pub fn insert(&mut self, name: &str, words: Vec<String>) {
+        self.0.insert(name.to_lowercase(), words);
+    }
+    /// Every valid name the pattern makes, in order.
+    pub fn expand(&self, pattern: &Pattern) -> Result<Vec<String>, PatternError> {
+        let choices = pattern.0.iter()
+            .map(|part| match part {
+                Part::Text(text) => Ok(vec![text.as_str()]),
+                Part::Slot(name) => match self.0.get(name) {
+                    Some(words) => Ok(words.iter().map(String::as_str).collect()),
+                    None => Err(PatternError::UnknownList(name.clone())),
+                },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(choices.into_iter()
+            .multi_cartesian_product()
+            .map(|parts| parts.concat().split_whitespace().join(" "))
+            .filter(|name| valid_name(name))
+            .unique()
+            .collect())
+    }
+}
@@ -0,0 +1,70 @@
+//! Notifications from long running commands like watches.
+//!
+//! By default they're just printed, but they can be handed to any command,
+//! which is enough to hook up desktop notifications, chat bots, or email.
+use std::io;
+use std::process::Command;
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+}
+impl Notification {
+    pub fn new(title: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            body: body.into(),
+        }
+    }
+}
+
+/// Where notifications go.
+#[derive(Debug, Clone, Default)]
+pub enum Notifier {
+    /// Print to standard output.
+    #[default]
+    Print,
+    /// Run a shell command, with the notification in the
+    /// `NATION_TITLE` and `NATION_BODY` environment variables.
+    Command(String),
+}
+impl Notifier {
+    pub fn from_command(command: Option<String>) -> Self {
+        command.map_or(Notifier::Print, Notifier::Command)
+    }
+    pub fn send(&self, notification: &Notification) -> io::Result<()> {
+        match self {
+            Notifier::Print => {
+                println!("{}: {}", notification.title, notification.body);
+                Ok(())
+            },
+            Notifier::Command(command) => {
+                let status = shell(command)
+                    .env("NATION_TITLE", &notification.title)
+                    .env("NATION_BODY", &notification.body)
+                    .status()?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!("notification command failed with {}", status)))
+                }
+            },
+        }
+    }
+}
+
+/// `command` to be run by the platform's shell.
+pub(crate) fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
@@ -0,0 +1,73 @@
+//! Regional polls.
+use chrono::Duration;
+use thiserror::Error;
+
+/// Fewest options a poll can offer.
+pub const MIN_OPTIONS: usize = 2;
+/// Most options a poll can offer.
+pub const MAX_OPTIONS: usize = 20;
+/// Shortest a poll can run, in hours.
+pub const MIN_HOURS: i64 = 1;
+/// Longest a poll can run, in days.
+pub const MAX_DAYS: i64 = 14;
+
+/// A poll an officer wants to open, before it's been checked.
+#[derive(Debug, Clone)]
+pub struct Draft {
+    pub question: String,
+    pub options: Vec<String>,
+    /// How long it stays open.
+    pub duration: Duration,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DraftError {
+    #[error("the poll needs a question")]
+    NoQuestion,
+    #[error("a poll needs at least {} options, got {0}", MIN_OPTIONS)]
+    TooFewOptions(usize),
+    #[error("a poll can have at most {} options, got {0}", MAX_OPTIONS)]
+    TooManyOptions(usize),
+    #[error("option {0} is empty")]
+    EmptyOption(usize),
+    #[error("{0:?} is offered more than once")]
+    DuplicateOption(String),
+    #[error("a poll has to run for at least {} hour", MIN_HOURS)]
+    TooShort,
+    #[error("a poll can run for at most {} days", MAX_DAYS)]
+    TooLong,
+}
+
+impl Draft {
+    /// Check the draft against the limits on polls, trimming
+    /// whitespace off the question and options.
+    pub fn check(mut self) -> Result<Self, DraftError> {
+        self.question = self.question.trim().to_owned();
+        if self.question.is_empty() {
+            return Err(DraftError::NoQuestion);
+        }
+        for option in &mut self.options {
+            *option = option.trim().to_owned();
+        }
+        match self.options.len() {
+            n if n < MIN_OPTIONS => return Err(DraftError::TooFewOptions(n)),
+            n if n > MAX_OPTIONS => return Err(DraftError::TooManyOptions(n)),
+            _ => (),
+        }
+        for (i, option) in self.options.iter().enumerate() {
+            if option.is_empty() {
+                return Err(DraftError::EmptyOption(i + 1));
+            }
+            if self.options[..i].iter().any(|x| x.eq_ignore_ascii_case(option)) {
+                return Err(DraftError::DuplicateOption(option.clone()));
+            }
+        }
+        if self.duration < Duration::hours(MIN_HOURS) {
+            return Err(DraftError::TooShort);
+        }
+        if self.duration > Duration::days(MAX_DAYS) {
+            return Err(DraftError::TooLong);
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,517 @@
+//! Nations and credentials saved on disk.
+use crate::api::{ResolvedShard, Shard};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt::Debug;
+use std::path::Path;
+use thiserror::Error;
+
+/// Session pin for the NationStates API.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pin {
+    pub(crate) value: u64,
+    pub(crate) timestamp: DateTime<Utc>,
+}
+impl Pin {
+    /// When the pin was handed out.
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+    /// Check validity based on timestamp.
+    /// Note that pins are also invalidated by additional logins,
+    /// which [`Auth::pin_state`] accounts for once one's been seen.
+    pub fn valid(&self) -> bool {
+        Utc::now().signed_duration_since(self.timestamp) < Duration::hours(2)
+    }
+}
+
+/// What's known about a stored pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinState {
+    Missing,
+    /// Not expired, and not known to have been rejected.
+    Valid,
+    Expired,
+    /// Refused by the API before it expired,
+    /// usually because the nation logged in somewhere else.
+    Rejected,
+}
+impl core::fmt::Display for PinState {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(match self {
+            PinState::Missing => "missing",
+            PinState::Valid => "valid",
+            PinState::Expired => "expired",
+            PinState::Rejected => "rejected",
+        })
+    }
+}
+
+/// Authentication information for the NationStates API.
+// A usable `Auth` will have at least one `Some` in its fields.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Auth {
+    // Storage should prefer storing autologin tokens over passwords.
+    pub password: Option<String>,
+    pub autologin: Option<String>,
+    pub pin: Option<Pin>,
+    /// When the API last refused the stored pin.
+    #[serde(default)]
+    pub pin_rejected: Option<DateTime<Utc>>,
+    /// When the current autologin was handed out.
+    #[serde(default)]
+    pub autologin_acquired: Option<DateTime<Utc>>,
+    /// Password from `NATION_PASSWORD_<NATION>`, used over the stored one and never saved.
+    #[serde(skip)]
+    pub env_password: Option<String>,
+    /// Autologin from `NATION_AUTOLOGIN_<NATION>`, used over the stored one and never saved.
+    #[serde(skip)]
+    pub env_autologin: Option<String>,
+    /// Never send or keep pins, logging in with the autologin or password every time.
+    /// For nations that are also played in a browser, where each side keeps
+    /// invalidating the other's pin.
+    #[serde(skip)]
+    pub pin_friendly: bool,
+    /// How long the stored pin lasted, if the API refused it well before it would have expired.
+    /// That usually means something else logged in as the nation. Not saved.
+    #[serde(skip)]
+    pub pin_cut_short: Option<Duration>,
+}
+/// A credential that logs in without a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Login<'a> {
+    Autologin(&'a str),
+    Password(&'a str),
+}
+impl Auth {
+    /// The credential to log in with when there's no usable pin.
+    /// Autologins go first, and credentials from the environment beat stored ones of the same kind.
+    pub fn login(&self) -> Option<Login<'_>> {
+        let autologin = self.env_autologin.as_deref().or(self.autologin.as_deref());
+        let password = self.env_password.as_deref().or(self.password.as_deref());
+        autologin.map(Login::Autologin).or_else(|| password.map(Login::Password))
+    }
+    /// The stored password on its own, for when the API refuses the autologin
+    /// that [`Auth::login`] prefers. `None` if there's no password, or no autologin to fall back from.
+    pub fn password_fallback(&self) -> Option<Auth> {
+        match self.login()? {
+            Login::Autologin(_) => Some(Auth {
+                password: Some(self.env_password.as_ref().or(self.password.as_ref())?.clone()),
+                pin_friendly: self.pin_friendly,
+                ..Auth::default()
+            }),
+            Login::Password(_) => None,
+        }
+    }
+    pub fn pin_state(&self) -> PinState {
+        match &self.pin {
+            None => PinState::Missing,
+            Some(pin) if matches!(self.pin_rejected, Some(x) if x >= pin.timestamp) => PinState::Rejected,
+            Some(pin) if !pin.valid() => PinState::Expired,
+            Some(_) => PinState::Valid,
+        }
+    }
+    /// Note that the API refused the stored pin, so it isn't tried again.
+    pub fn reject_pin(&mut self) {
+        let now = Utc::now();
+        self.pin_rejected = Some(now);
+        // Pins go unused for a while between runs, so only a refusal
+        // well inside the two hours says much.
+        if let Some(pin) = &self.pin {
+            let age = now.signed_duration_since(pin.timestamp);
+            if age < Duration::minutes(90) {
+                self.pin_cut_short = Some(age);
+            }
+        }
+    }
+    /// Store credentials handed back by the API.
+    pub fn update(&mut self, autologin: Option<String>, pin: Option<Pin>) {
+        if let Some(autologin) = autologin {
+            if self.autologin.as_ref() != Some(&autologin) {
+                self.autologin_acquired = Some(Utc::now());
+            }
+            self.autologin = Some(autologin);
+            // The stored password is kept: changing it on the site
+            // invalidates the autologin, and it's what we fall back to then.
+        }
+        if self.pin_friendly {
+            self.pin = None;
+        } else if let Some(pin) = pin {
+            self.pin = Some(pin);
+            self.pin_rejected = None;
+        }
+    }
+}
+impl Debug for Auth {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Auth")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Nation {
+    pub name: String,
+    /// Last successful ping, which counts as logging in.
+    #[serde(default)]
+    pub last_ping: Option<DateTime<Utc>>,
+    /// Last time one of the nation's issues was answered.
+    #[serde(default)]
+    pub last_issue: Option<DateTime<Utc>>,
+    /// Region the nation was in when it was added,
+    /// normally the one it was founded in.
+    #[serde(default)]
+    pub founding_region: Option<String>,
+    /// Groups the nation belongs to, for commands that run on many nations.
+    #[serde(default, deserialize_with = "crate::api::comma_list",
+            serialize_with = "serialize_groups", skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    pub auth: Auth,
+    /// Public details from the last time they were fetched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Details>,
+    /// Operator who last changed the nation's entry, for profiles shared
+    /// between people. See [`Profile::attribute`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed: Option<DateTime<Utc>>,
+    /// Refuse commands that would change the nation, like answering issues,
+    /// for credentials shared only to keep an eye on it.
+    /// Set with `readonly="true"` on the nation in the profile.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub readonly: bool,
+    /// Known only from environment variables, so never saved.
+    #[serde(skip)]
+    pub transient: bool,
+}
+/// Public details of a nation as last fetched, so they can be shown without asking the API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Details {
+    pub fetched: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// World Assembly status, like "WA Member".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wa: Option<String>,
+    /// In millions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub population: Option<u64>,
+    /// URL of the flag image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flag: Option<String>,
+    /// Government category, like "Civil Rights Lovefest".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Freedom descriptions, like "Excellent".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub civil_rights: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub economy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub political_freedom: Option<String>,
+}
+impl Details {
+    /// Shards to add to a request about the nation to get these.
+    pub fn shards() -> Vec<Shard> {
+        vec![Shard::Region, Shard::Wa, Shard::Population, Shard::Flag, Shard::Category, Shard::Freedom]
+    }
+    /// The details in a response, fetched just now.
+    /// `None` if it holds none of them.
+    pub fn from_shards(shards: &[ResolvedShard]) -> Option<Details> {
+        let mut details = Details {
+            fetched: Utc::now(), region: None, wa: None, population: None, flag: None,
+            category: None, civil_rights: None, economy: None, political_freedom: None,
+        };
+        for shard in shards {
+            match shard {
+                ResolvedShard::Region(x) => details.region = Some(x.clone()),
+                ResolvedShard::WaStatus(x) => details.wa = Some(x.clone()),
+                ResolvedShard::Population(x) => details.population = Some(*x),
+                ResolvedShard::Flag(x) => details.flag = Some(x.clone()),
+                ResolvedShard::Category(x) => details.category = Some(x.clone()),
+                ResolvedShard::Freedom(x) => {
+                    details.civil_rights = Some(x.civil_rights.clone());
+                    details.economy = Some(x.economy.clone());
+                    details.political_freedom = Some(x.political_freedom.clone());
+                },
+                _ => (),
+            }
+        }
+        let empty = details.region.is_none() && details.wa.is_none() && details.population.is_none() && details.flag.is_none()
+            && details.classifications().iter().all(|(_, x)| x.is_none());
+        if empty { None } else { Some(details) }
+    }
+    /// The government category and freedoms, named the way issue outcomes name them.
+    pub fn classifications(&self) -> [(&'static str, &Option<String>); 4] {
+        [
+            ("Government", &self.category),
+            ("Civil Rights", &self.civil_rights),
+            ("Economy", &self.economy),
+            ("Political Freedom", &self.political_freedom),
+        ]
+    }
+    /// How the classifications in `newer` differ from these, as `(what, from, to)`.
+    /// Ones missing from either side aren't counted as changes.
+    pub fn reclassified(&self, newer: &Details) -> Vec<(&'static str, String, String)> {
+        self.classifications().iter().zip(newer.classifications().iter())
+            .filter_map(|((what, old), (_, new))| match (old, new) {
+                (Some(old), Some(new)) if old != new => Some((*what, old.clone(), new.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+    /// Take in a reclassification from an issue outcome, where `kind` is
+    /// `govt`, or `0`, `1`, `2` for civil rights, economy, and political freedom.
+    pub fn reclassify(&mut self, kind: &str, to: &str) {
+        let field = match kind {
+            "govt" => &mut self.category,
+            "0" => &mut self.civil_rights,
+            "1" => &mut self.economy,
+            "2" => &mut self.political_freedom,
+            _ => return,
+        };
+        *field = Some(to.to_owned());
+    }
+    /// Keep what's known from `older` where these details lack it,
+    /// since not every request fetches every shard.
+    pub fn or_older(mut self, older: Option<&Details>) -> Details {
+        if let Some(older) = older {
+            self.region = self.region.or_else(|| older.region.clone());
+            self.wa = self.wa.or_else(|| older.wa.clone());
+            self.population = self.population.or(older.population);
+            self.flag = self.flag.or_else(|| older.flag.clone());
+            self.category = self.category.or_else(|| older.category.clone());
+            self.civil_rights = self.civil_rights.or_else(|| older.civil_rights.clone());
+            self.economy = self.economy.or_else(|| older.economy.clone());
+            self.political_freedom = self.political_freedom.or_else(|| older.political_freedom.clone());
+        }
+        self
+    }
+}
+/// Groups are kept comma separated in one attribute.
+fn serialize_groups<S: Serializer>(groups: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&groups.join(","))
+}
+impl Nation {
+    pub fn in_group(&self, group: &str) -> bool {
+        self.groups.iter().any(|x| x.eq_ignore_ascii_case(group))
+    }
+    /// Add the nation to a group, returning whether it wasn't in it already.
+    pub fn join(&mut self, group: &str) -> bool {
+        if self.in_group(group) {
+            return false;
+        }
+        self.groups.push(group.trim().to_owned());
+        true
+    }
+    /// Remove the nation from a group, returning whether it was in it.
+    pub fn leave(&mut self, group: &str) -> bool {
+        let before = self.groups.len();
+        self.groups.retain(|x| !x.eq_ignore_ascii_case(group));
+        self.groups.len() != before
+    }
+    /// Last activity on record that keeps the nation from ceasing to exist.
+    pub fn last_active(&self) -> Option<DateTime<Utc>> {
+        self.last_ping.max(self.last_issue)
+    }
+}
+/// Prefix of environment variables holding a nation's password.
+pub const PASSWORD_VAR: &str = "NATION_PASSWORD_";
+/// Prefix of environment variables holding a nation's autologin.
+pub const AUTOLOGIN_VAR: &str = "NATION_AUTOLOGIN_";
+
+/// How a nation's name appears in its environment variables:
+/// "Testlandia Two" is `TESTLANDIA_TWO`.
+pub fn env_suffix(name: &str) -> String {
+    crate::canonical_name(name).chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename = "nations")]
+pub struct Nations {
+    #[serde(rename(deserialize = "$value", serialize = "nation"))]
+    pub inner: Vec<Nation>,
+}
+impl Nations {
+    /// Make a new collection of `Nation`s.
+    pub fn new() -> Self {
+        Self { inner: Vec::new() }
+    }
+}
+
+// TODO: Consider separating the manually authored
+// profile and cached data retrieved from the API
+// into two separate files.
+// This is low priority because no
+// customization options come to mind.
+#[derive(Serialize, Debug)]
+pub struct Profile {
+    pub nations: Nations,
+}
+/// The nations that get written to disk.
+#[derive(Serialize)]
+#[serde(rename = "nations")]
+struct SavedNations<'a> {
+    #[serde(rename = "nation")]
+    inner: Vec<&'a Nation>,
+}
+#[derive(Error, Debug)]
+pub enum ProfileError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("xml error: {0}")]
+    XmlError(#[from] quick_xml::DeError),
+}
+impl Profile {
+    pub fn load(path: &Path) -> Result<Self, ProfileError> {
+        let file = match std::fs::File::open(path).map_err(|e| (e.kind(), e)) {
+            Ok(f) => f,
+            Err((std::io::ErrorKind::NotFound, _)) => return Ok(Self::default()),
+            Err((_, e)) => Err(e)?,
+        };
+        let reader = std::io::BufReader::new(file);
+        let nations = quick_xml::de::from_reader(reader)?;
+        Ok(Self { nations })
+    }
+    /// Take credentials from `NATION_PASSWORD_<NATION>` and `NATION_AUTOLOGIN_<NATION>`.
+    /// Nations that aren't in the profile are added for this run only.
+    pub fn apply_env(&mut self) {
+        self.apply_vars(std::env::vars());
+    }
+    fn apply_vars<I: IntoIterator<Item = (String, String)>>(&mut self, vars: I) {
+        for (key, value) in vars {
+            let (suffix, is_password) = if let Some(x) = key.strip_prefix(PASSWORD_VAR) {
+                (x, true)
+            } else if let Some(x) = key.strip_prefix(AUTOLOGIN_VAR) {
+                (x, false)
+            } else {
+                continue;
+            };
+            if suffix.is_empty() || value.is_empty() {
+                continue;
+            }
+            let index = match self.nations.inner.iter().position(|x| env_suffix(&x.name) == suffix) {
+                Some(i) => i,
+                None => {
+                    self.nations.inner.push(Nation {
+                        name: suffix.to_lowercase(),
+                        transient: true,
+                        ..Nation::default()
+                    });
+                    self.nations.inner.len() - 1
+                },
+            };
+            let auth = &mut self.nations.inner[index].auth;
+            if is_password {
+                auth.env_password = Some(value);
+            } else {
+                auth.env_autologin = Some(value);
+            }
+        }
+    }
+    /// Mark nations whose entries differ from the ones saved at `path` as
+    /// changed by `operator` just now, so people sharing a profile can tell
+    /// who changed what. Nations that aren't saved there yet count as changed.
+    pub fn attribute(&mut self, path: &Path, operator: &str) -> Result<(), ProfileError> {
+        let mut saved = Profile::load(path)?;
+        for nation in self.nations.inner.iter_mut().filter(|x| !x.transient) {
+            let changed = match saved.nations.inner.iter_mut().find(|x| x.name == nation.name) {
+                Some(before) => fingerprint(before)? != fingerprint(nation)?,
+                None => true,
+            };
+            if changed {
+                nation.changed_by = Some(operator.to_owned());
+                nation.changed = Some(Utc::now());
+            }
+        }
+        Ok(())
+    }
+    pub fn save(&self, path: &Path) -> Result<(), ProfileError> {
+        let saved = SavedNations {
+            inner: self.nations.inner.iter().filter(|x| !x.transient).collect(),
+        };
+        // Running purely on environment variables shouldn't leave a profile behind.
+        if saved.inner.is_empty() && !path.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Written next to the profile and renamed over it, so being killed
+        // partway through leaves the old profile rather than half a new one.
+        let mut name = path.file_name().unwrap_or_default().to_owned();
+        name.push(".tmp");
+        let temp = path.with_file_name(name);
+        match std::fs::remove_file(&temp) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => (),
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        // The profile holds passwords, so it's for the owner only.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let write = || -> Result<(), ProfileError> {
+            let mut writer = std::io::BufWriter::new(options.open(&temp)?);
+            quick_xml::se::to_writer(&mut writer, &saved)?;
+            writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            Ok(std::fs::rename(&temp, path)?)
+        };
+        let result = write();
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+}
+/// A nation's entry as saved, leaving out who changed it last,
+/// which isn't a change in itself.
+fn fingerprint(nation: &mut Nation) -> Result<String, ProfileError> {
+    let changed_by = nation.changed_by.take();
+    let changed = nation.changed.take();
+    let xml = quick_xml::se::to_string(nation);
+    nation.changed_by = changed_by;
+    nation.changed = changed;
+    Ok(xml?)
+}
+/// Permission bits on the profile at `path` that let other users in,
+/// or `None` if only its owner can read and write it.
+/// Always `None` where Unix permissions don't apply.
+pub fn loose_permissions(path: &Path) -> std::io::Result<Option<u32>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode() & 0o777;
+        Ok(if mode & 0o077 != 0 { Some(mode) } else { None })
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::metadata(path)?;
+        Ok(None)
+    }
+}
+/// Make the profile at `path` readable and writable by its owner only.
+/// Fails with [`std::io::ErrorKind::Unsupported`] where Unix permissions
+/// don't apply, since access there is up to the file's ACL.
+pub fn restrict_permissions(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::metadata(path)?;
+        Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "only Unix file permissions can be restricted"))
+    }
+}
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            nations: Nations::new(),
+        }
+    }
+}
+
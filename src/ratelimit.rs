@@ -0,0 +1,223 @@
+//! The API's own account of how close a client is to its rate limit.
+//!
+//! Every response says how many requests are left in the current window
+//! and when it resets. Keeping the latest of those around lets waits
+//! stretch to the end of the window when it's used up, rather than going
+//! only by fixed pauses between requests.
+//!
+//! On top of that, a minimum delay between requests can be set, for
+//! sharing the limit with other tools on the same address. Processes on
+//! one machine can also share their state through a file, so that
+//! between them they stay under the limit.
+use lazy_static::lazy_static;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Rate limit headers from one response. Any of them may be missing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Requests allowed per window, from `RateLimit-Limit`.
+    pub limit: Option<u32>,
+    /// Requests left in the current window, from `RateLimit-Remaining`.
+    pub remaining: Option<u32>,
+    /// Seconds until the window resets, from `RateLimit-Reset`.
+    pub reset: Option<u64>,
+    /// Requests counted against this client, from `X-ratelimit-requests-seen`.
+    pub requests_seen: Option<u32>,
+}
+impl RateLimit {
+    /// Read the headers through `header`, which looks one up by name.
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Self {
+        fn number<T: std::str::FromStr>(value: Option<&str>) -> Option<T> {
+            value?.trim().parse().ok()
+        }
+        Self {
+            limit: number(header("RateLimit-Limit")),
+            remaining: number(header("RateLimit-Remaining")),
+            reset: number(header("RateLimit-Reset")),
+            requests_seen: number(header("X-ratelimit-requests-seen")),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no rate limit headers");
+        }
+        let show = |x: Option<u32>| x.map_or_else(|| "?".to_owned(), |x| x.to_string());
+        write!(f, "{}/{} left", show(self.remaining), show(self.limit))?;
+        if let Some(reset) = self.reset {
+            write!(f, ", resets in {}s", reset)?;
+        }
+        if let Some(seen) = self.requests_seen {
+            write!(f, ", {} seen", seen)?;
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    /// The latest headers, and when they arrived.
+    static ref LATEST: Mutex<Option<(Instant, RateLimit)>> = Mutex::new(None);
+    /// The minimum delay between requests, and when the next one may go out.
+    static ref SPACING: Mutex<(Duration, Option<Instant>)> = Mutex::new((Duration::from_secs(0), None));
+    /// State file shared with other processes, if any.
+    static ref SHARED: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+/// Keep at least `delay` between requests from now on,
+/// however much of the API's limit is left.
+pub fn set_min_delay(delay: Duration) {
+    SPACING.lock().unwrap().0 = delay;
+}
+
+/// Coordinate with every other process sharing the state file at `path`:
+/// requests are spaced out so that together they stay under the API's
+/// limit, and a window one of them has used up holds back the rest.
+pub fn share_through(path: PathBuf) {
+    *SHARED.lock().unwrap() = Some(path);
+}
+
+/// Claim the next slot for a request, returning how long to wait for it.
+#[cfg(feature = "native")]
+fn reserve_slot() -> Option<Duration> {
+    let mut spacing = SPACING.lock().unwrap();
+    let (delay, next) = *spacing;
+    let now = Instant::now();
+    let mut start = next.map_or(now, |x| x.max(now));
+    if let Some(path) = &*SHARED.lock().unwrap() {
+        // Going it alone beats not sending anything.
+        if let Ok(shared) = shared::reserve(path, start - now, delay.max(shared::SPACING)) {
+            start = now + shared;
+        }
+    }
+    spacing.1 = Some(start + delay);
+    Some(start - now).filter(|x| *x > Duration::from_secs(0))
+}
+
+/// Wait until a request may go out: after the minimum delay since the
+/// last one, and after the API's window if the last response said it's used up.
+pub(crate) async fn wait_turn() {
+    #[cfg(feature = "native")]
+    if let Some(wait) = reserve_slot().max(exhausted_for()) {
+        crate::metrics::throttle(wait).await;
+    }
+}
+
+/// Keep the headers from a response that just arrived.
+pub(crate) fn observe(rate_limit: RateLimit) {
+    // There's no clock to time the window with in a browser.
+    if cfg!(feature = "native") && !rate_limit.is_empty() {
+        *LATEST.lock().unwrap() = Some((Instant::now(), rate_limit));
+    }
+    #[cfg(feature = "native")]
+    if let (Some(path), Some(wait)) = (&*SHARED.lock().unwrap(), exhausted_for()) {
+        let _ = shared::exhausted(path, wait);
+    }
+}
+
+/// The headers from the latest response that had any.
+pub fn latest() -> Option<RateLimit> {
+    LATEST.lock().unwrap().map(|(_, x)| x)
+}
+
+/// How long until the window resets, if the latest response said it's used up.
+pub fn exhausted_for() -> Option<Duration> {
+    let (seen, rate_limit) = (*LATEST.lock().unwrap())?;
+    if rate_limit.remaining != Some(0) {
+        return None;
+    }
+    // Resets count whole seconds, so allow for one more.
+    let reset = Duration::from_secs(rate_limit.reset? + 1);
+    reset.checked_sub(seen.elapsed())
+}
+
+/// The state file holds two Unix times in milliseconds: when the next
+/// request may go out, and when a used up window resets. It's only read
+/// and written while holding a lock file next to it.
+#[cfg(feature = "native")]
+mod shared {
+    use std::fs::{self, OpenOptions};
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Shared requests are spaced out to fit the API's limit of
+    /// 50 requests in 30 seconds, however many processes are sending them.
+    pub(super) const SPACING: Duration = Duration::from_millis(600);
+    /// A lock held longer than this was left behind by a process that died.
+    const STALE: Duration = Duration::from_secs(5);
+
+    struct Lock(PathBuf);
+    impl Lock {
+        fn acquire(state: &Path) -> io::Result<Self> {
+            let path = state.with_extension("lock");
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let gave_up = SystemTime::now() + STALE;
+            loop {
+                match OpenOptions::new().write(true).create_new(true).open(&path) {
+                    Ok(_) => return Ok(Lock(path)),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                        let age = fs::metadata(&path)?.modified()?.elapsed().unwrap_or_default();
+                        if age > STALE {
+                            let _ = fs::remove_file(&path);
+                        } else if SystemTime::now() > gave_up {
+                            return Err(io::Error::new(io::ErrorKind::TimedOut, "rate limit state stayed locked"));
+                        }
+                        // Nobody holds it for more than a read and a write.
+                        std::thread::sleep(Duration::from_millis(5));
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+    impl Drop for Lock {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn millis(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+    }
+
+    fn read(path: &Path) -> io::Result<(u64, u64)> {
+        let text = match fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(e) => return Err(e),
+        };
+        let mut numbers = text.split_whitespace().map(|x| x.parse().unwrap_or(0));
+        Ok((numbers.next().unwrap_or(0), numbers.next().unwrap_or(0)))
+    }
+
+    fn write(path: &Path, next: u64, reset: u64) -> io::Result<()> {
+        fs::write(path, format!("{} {}\n", next, reset))
+    }
+
+    /// Claim the first slot at least `after` from now, keeping the next one
+    /// `spacing` after it. Returns how long from now the slot is.
+    pub(super) fn reserve(path: &Path, after: Duration, spacing: Duration) -> io::Result<Duration> {
+        let _lock = Lock::acquire(path)?;
+        let now = millis(SystemTime::now());
+        let (next, reset) = read(path)?;
+        let start = (now + after.as_millis() as u64).max(next).max(reset);
+        write(path, start + spacing.as_millis() as u64, reset)?;
+        Ok(Duration::from_millis(start - now))
+    }
+
+    /// Hold everyone back until the window resets, `wait` from now.
+    pub(super) fn exhausted(path: &Path, wait: Duration) -> io::Result<()> {
+        let _lock = Lock::acquire(path)?;
+        let (next, reset) = read(path)?;
+        write(path, next, reset.max(millis(SystemTime::now() + wait)))
+    }
+}
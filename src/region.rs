@@ -0,0 +1,564 @@
+//! Regions, and keeping track of who lives in them.
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc, Weekday};
+use core::fmt;
+use core::str::FromStr;
+use thiserror::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::api::{ResolvedShard, Shard};
+use crate::profile::ProfileError;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub name: String,
+}
+
+/// The residents of a region at some point in time.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename = "snapshot")]
+pub struct Snapshot {
+    pub region: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(rename = "nation", default)]
+    pub nations: Vec<Member>,
+}
+impl Snapshot {
+    pub fn new(region: &str, nations: Vec<String>) -> Self {
+        Self {
+            region: region.to_owned(),
+            timestamp: Utc::now(),
+            nations: nations.into_iter().map(|name| Member { name }).collect(),
+        }
+    }
+    /// Load a snapshot, if one has been saved.
+    pub fn load(path: &Path) -> Result<Option<Self>, ProfileError> {
+        let file = match std::fs::File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => Err(e)?,
+        };
+        Ok(Some(quick_xml::de::from_reader(std::io::BufReader::new(file))?))
+    }
+    pub fn save(&self, path: &Path) -> Result<(), ProfileError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let writer = std::fs::File::create(path)?;
+        Ok(quick_xml::se::to_writer(writer, self)?)
+    }
+    /// Who arrived and who left between this snapshot and a newer one.
+    pub fn changes(&self, newer: &Snapshot) -> Changes {
+        let old: HashSet<_> = self.nations.iter().map(|x| &x.name).collect();
+        let new: HashSet<_> = newer.nations.iter().map(|x| &x.name).collect();
+        Changes {
+            arrivals: newer.nations.iter().filter(|x| !old.contains(&x.name)).map(|x| x.name.clone()).collect(),
+            departures: self.nations.iter().filter(|x| !new.contains(&x.name)).map(|x| x.name.clone()).collect(),
+        }
+    }
+}
+
+/// Difference between two snapshots of a region.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub arrivals: Vec<String>,
+    /// Nations no longer in the region.
+    /// This includes both nations that moved out and nations that ceased to exist.
+    pub departures: Vec<String>,
+}
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.arrivals.is_empty() && self.departures.is_empty()
+    }
+}
+
+/// The API sends `0` in place of a founder or delegate when there isn't one.
+pub fn officeholder(name: String) -> Option<String> {
+    Some(name).filter(|x| x != "0" && !x.is_empty())
+}
+
+/// A tag to search regions by, written `tag` to require it
+/// or `-tag` to exclude regions that have it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagFilter {
+    pub tag: String,
+    pub exclude: bool,
+}
+impl FromStr for TagFilter {
+    type Err = ParseTagError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let (exclude, tag) = match input.strip_prefix('-') {
+            Some(tag) => (true, tag),
+            None => (false, input),
+        };
+        if tag.is_empty() {
+            return Err(ParseTagError(input.to_owned()));
+        }
+        Ok(Self {
+            tag: crate::canonical_name(tag),
+            exclude,
+        })
+    }
+}
+impl fmt::Display for TagFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.exclude {
+            f.write_str("-")?;
+        }
+        f.write_str(&self.tag)
+    }
+}
+#[derive(Error, Debug)]
+#[error("invalid tag {0:?}")]
+pub struct ParseTagError(String);
+
+/// A single regional power, as the API abbreviates them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Power {
+    Executive,
+    WorldAssembly,
+    Appearance,
+    BorderControl,
+    Communications,
+    Embassies,
+    Polls,
+}
+impl Power {
+    pub fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'X' => Some(Power::Executive),
+            'W' => Some(Power::WorldAssembly),
+            'A' => Some(Power::Appearance),
+            'B' => Some(Power::BorderControl),
+            'C' => Some(Power::Communications),
+            'E' => Some(Power::Embassies),
+            'P' => Some(Power::Polls),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for Power {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Power::Executive => "Executive",
+            Power::WorldAssembly => "World Assembly",
+            Power::Appearance => "Appearance",
+            Power::BorderControl => "Border Control",
+            Power::Communications => "Communications",
+            Power::Embassies => "Embassies",
+            Power::Polls => "Polls",
+        })
+    }
+}
+
+/// Set of regional powers, sent by the API as a string of letters like `XABCEP`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Authority(pub Vec<Power>);
+impl Authority {
+    pub fn has(&self, power: Power) -> bool {
+        self.0.contains(&power)
+    }
+}
+impl FromStr for Authority {
+    type Err = core::convert::Infallible;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        // Unknown letters are skipped, in case new powers get added.
+        Ok(Authority(input.chars().filter_map(Power::from_letter).collect()))
+    }
+}
+impl fmt::Display for Authority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("none");
+        }
+        for (i, power) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", power)?;
+        }
+        Ok(())
+    }
+}
+
+/// Region tags, as the API documentation lists them for `regionsbytag`.
+pub const TAGS: &[&str] = &[
+    "Anarchist", "Anime", "Anti-Capitalist", "Anti-Communist", "Anti-Fascist",
+    "Anti-General Assembly", "Anti-Security Council", "Anti-World Assembly", "Capitalist",
+    "Casual", "Colony", "Commended", "Communist", "Condemned", "Conservative", "Cyberpunk",
+    "Defender", "Democratic", "Eco-Friendly", "Egalitarian", "Embassy Collector", "Enormous",
+    "FT FTL", "FT FTLi", "FT STL", "Fandom", "Fantasy Tech", "Fascist", "Featured", "Feminist",
+    "Founderless", "Free Trade", "Frontier", "Future Tech", "Game Player", "Gargantuan",
+    "Generalite", "Governorless", "Human-Only", "Imperialist", "Independent", "Industrial",
+    "Injunction", "International Federalist", "Invader", "Isolationist", "Issues Player",
+    "Jump Point", "LGBT", "Large", "Liberal", "Liberated", "Libertarian", "Magical", "Map",
+    "Medium", "Mercenary", "Minuscule", "Modern Tech", "Monarchist", "Multi-Species",
+    "National Sovereigntist", "Neutral", "New", "Non-English", "Offsite Chat", "Offsite Forums",
+    "Outer Space", "P2TM", "Pacifist", "Parody", "Password", "Past Tech", "Patriarchal",
+    "Post Apocalyptic", "Post-Modern Tech", "Puppet Storage", "Regional Government", "Religious",
+    "Role Player", "Security Council", "Serious", "Silly", "Small", "Snarky", "Social",
+    "Socialist", "Sports", "Steampunk", "Surreal", "Theocratic", "Totalitarian", "Trading Cards",
+    "Video Game", "World Assembly",
+];
+/// Tags the game sets and clears by itself, which officers can't change.
+pub const AUTOMATIC_TAGS: &[&str] = &[
+    "Commended", "Condemned", "Enormous", "Featured", "Founderless", "Frontier", "Gargantuan",
+    "Governorless", "Injunction", "Large", "Liberated", "Medium", "Minuscule", "New", "Password",
+    "Small",
+];
+/// The tag in [`TAGS`] that `tag` names, ignoring case and spacing.
+pub fn known_tag(tag: &str) -> Option<&'static str> {
+    let tag = crate::canonical_name(tag);
+    TAGS.iter().copied().find(|x| crate::canonical_name(x) == tag)
+}
+
+/// A regional officer, from the `officers` shard.
+#[derive(Debug, Deserialize)]
+pub struct Officer {
+    #[serde(rename = "NATION")]
+    pub nation: String,
+    #[serde(rename = "OFFICE")]
+    pub office: String,
+    #[serde(rename = "AUTHORITY", deserialize_with = "crate::api::from_text")]
+    pub authority: Authority,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Officers {
+    #[serde(rename = "OFFICER", default)]
+    pub inner: Vec<Officer>,
+}
+
+/// How long a nation has been around and when it was last seen,
+/// for deciding who to eject from a region.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Residency {
+    pub nation: String,
+    /// `None` for nations older than the API's records.
+    pub founded: Option<DateTime<Utc>>,
+    pub first_login: Option<DateTime<Utc>>,
+    pub last_login: Option<DateTime<Utc>>,
+}
+impl Residency {
+    /// The shards a [`Residency`] is made of.
+    pub fn shards() -> Vec<Shard> {
+        vec![Shard::FoundedTime, Shard::FirstLogin, Shard::LastLogin]
+    }
+    /// Pick the residency shards for `nation` out of a response, ignoring anything else.
+    pub fn from_shards(nation: &str, shards: impl IntoIterator<Item = ResolvedShard>) -> Self {
+        let mut residency = Self {
+            nation: nation.to_owned(),
+            ..Self::default()
+        };
+        for shard in shards {
+            match shard {
+                ResolvedShard::FoundedTime(x) => residency.founded = x,
+                ResolvedShard::FirstLogin(x) => residency.first_login = x,
+                ResolvedShard::LastLogin(x) => residency.last_login = x,
+                _ => (),
+            }
+        }
+        residency
+    }
+}
+
+/// How a nation's influence score has been changing,
+/// going by scores recorded over time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InfluenceTrend {
+    /// The latest score, and when it was recorded.
+    pub score: f64,
+    pub recorded: DateTime<Utc>,
+    /// Average gain per day, from a straight line through the history.
+    pub per_day: f64,
+}
+impl InfluenceTrend {
+    /// Fit a trend to scores, oldest first. Needs at least two of them
+    /// recorded at different times.
+    pub fn from_history(history: &[(DateTime<Utc>, f64)]) -> Option<Self> {
+        let (first, _) = *history.first()?;
+        let &(recorded, score) = history.last()?;
+        let days: Vec<_> = history.iter()
+            .map(|(time, score)| ((*time - first).num_seconds() as f64 / 86400.0, *score))
+            .collect();
+        let n = days.len() as f64;
+        let mean_day = days.iter().map(|x| x.0).sum::<f64>() / n;
+        let mean_score = days.iter().map(|x| x.1).sum::<f64>() / n;
+        let spread: f64 = days.iter().map(|x| (x.0 - mean_day).powi(2)).sum();
+        if spread == 0.0 {
+            return None;
+        }
+        let per_day = days.iter().map(|x| (x.0 - mean_day) * (x.1 - mean_score)).sum::<f64>() / spread;
+        Some(Self { score, recorded, per_day })
+    }
+    /// Roughly when the score reaches `target` at the current rate,
+    /// or `None` if it isn't growing.
+    pub fn reaches(&self, target: f64) -> Option<DateTime<Utc>> {
+        if self.score >= target {
+            return Some(self.recorded);
+        }
+        if self.per_day <= 0.0 {
+            return None;
+        }
+        let seconds = (target - self.score) / self.per_day * 86400.0;
+        Some(self.recorded + chrono::Duration::seconds(seconds.ceil() as i64))
+    }
+}
+
+/// Who holds power in a region.
+#[derive(Debug, Default)]
+pub struct Leadership {
+    pub founder: Option<String>,
+    pub founder_auth: Authority,
+    pub delegate: Option<String>,
+    pub delegate_auth: Authority,
+    pub officers: Vec<Officer>,
+}
+impl Leadership {
+    /// The shards a [`Leadership`] is made of.
+    pub fn shards() -> Vec<Shard> {
+        vec![Shard::Founder, Shard::FounderAuth, Shard::Delegate, Shard::DelegateAuth, Shard::Officers]
+    }
+    /// Pick the leadership shards out of a response, ignoring anything else.
+    pub fn from_shards(shards: impl IntoIterator<Item = ResolvedShard>) -> Self {
+        let mut leadership = Self::default();
+        for shard in shards {
+            match shard {
+                ResolvedShard::Founder(x) => leadership.founder = officeholder(x),
+                ResolvedShard::FounderAuth(x) => leadership.founder_auth = x,
+                ResolvedShard::Delegate(x) => leadership.delegate = officeholder(x),
+                ResolvedShard::DelegateAuth(x) => leadership.delegate_auth = x,
+                ResolvedShard::Officers(x) => leadership.officers = x.inner,
+                _ => (),
+            }
+        }
+        leadership
+    }
+    /// Every power `nation` holds, whether as founder, delegate or officer.
+    pub fn authority(&self, nation: &str) -> Authority {
+        let nation = crate::canonical_name(nation);
+        let is = |name: &str| crate::canonical_name(name) == nation;
+        let mut powers = Vec::new();
+        if self.founder.as_deref().is_some_and(is) {
+            powers.extend(&self.founder_auth.0);
+        }
+        if self.delegate.as_deref().is_some_and(is) {
+            powers.extend(&self.delegate_auth.0);
+        }
+        for officer in self.officers.iter().filter(|x| is(&x.nation)) {
+            powers.extend(&officer.authority.0);
+        }
+        powers.sort();
+        powers.dedup();
+        Authority(powers)
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct Tags {
+    #[serde(rename = "TAG", default)]
+    pub inner: Vec<String>,
+}
+impl Tags {
+    pub fn contains(&self, tag: &str) -> bool {
+        let tag = crate::canonical_name(tag);
+        self.inner.iter().any(|x| crate::canonical_name(x) == tag)
+    }
+}
+
+/// Where an embassy between two regions stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbassyStatus {
+    Open,
+    /// Agreed to, and being built.
+    Pending,
+    /// The other region asked for it.
+    Invited,
+    /// This region asked for it.
+    Requested,
+    /// The other region turned it down.
+    Rejected,
+    /// Being closed.
+    Closing,
+}
+impl fmt::Display for EmbassyStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            EmbassyStatus::Open => "open",
+            EmbassyStatus::Pending => "being built",
+            EmbassyStatus::Invited => "waiting for this region to accept",
+            EmbassyStatus::Requested => "waiting for the other region to accept",
+            EmbassyStatus::Rejected => "rejected",
+            EmbassyStatus::Closing => "closing",
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Embassy {
+    /// Missing for open embassies.
+    #[serde(rename = "type", default)]
+    pub kind: Option<String>,
+    #[serde(rename = "$value")]
+    pub region: String,
+}
+impl Embassy {
+    pub fn status(&self) -> EmbassyStatus {
+        match self.kind.as_deref() {
+            Some("pending") => EmbassyStatus::Pending,
+            Some("invited") => EmbassyStatus::Invited,
+            Some("requested") => EmbassyStatus::Requested,
+            Some("rejected") => EmbassyStatus::Rejected,
+            Some("closing") => EmbassyStatus::Closing,
+            _ => EmbassyStatus::Open,
+        }
+    }
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Embassies {
+    #[serde(rename = "EMBASSY", default)]
+    pub inner: Vec<Embassy>,
+}
+impl Embassies {
+    /// Where the embassy with `region` stands, if there is one.
+    pub fn status(&self, region: &str) -> Option<EmbassyStatus> {
+        let region = crate::canonical_name(region);
+        self.inner.iter().find(|x| crate::canonical_name(&x.region) == region).map(Embassy::status)
+    }
+}
+
+/// Something an officer with Embassies authority can do about an embassy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbassyAction {
+    Request,
+    Accept,
+    Reject,
+    Close,
+}
+impl EmbassyAction {
+    /// Whether this can be done to an embassy where it stands,
+    /// `None` meaning there is no embassy.
+    pub fn allowed(self, status: Option<EmbassyStatus>) -> bool {
+        use EmbassyStatus::*;
+        match self {
+            EmbassyAction::Request => matches!(status, None | Some(Rejected)),
+            EmbassyAction::Accept | EmbassyAction::Reject => status == Some(Invited),
+            // Closing an embassy that's only been asked for withdraws the request.
+            EmbassyAction::Close => matches!(status, Some(Open) | Some(Pending) | Some(Requested)),
+        }
+    }
+}
+impl fmt::Display for EmbassyAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            EmbassyAction::Request => "request",
+            EmbassyAction::Accept => "accept",
+            EmbassyAction::Reject => "reject",
+            EmbassyAction::Close => "close",
+        })
+    }
+}
+
+/// One of the two daily updates, which go through the regions one after
+/// another, bringing influence, endorsements, and delegates up to date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UpdateKind {
+    /// Starts at midnight in New York and takes about two hours.
+    Major,
+    /// Starts at noon in New York and takes under an hour.
+    Minor,
+}
+impl UpdateKind {
+    /// When this update starts on `day`, as the calendar in New York has it.
+    ///
+    /// ```
+    /// use chrono::{NaiveDate, TimeZone, Utc};
+    /// use nation::region::UpdateKind;
+    /// // Midnight in New York is 4am UTC in summer, and noon is 5pm UTC in winter.
+    /// let day = |month, day| NaiveDate::from_ymd_opt(2026, month, day).unwrap();
+    /// assert_eq!(UpdateKind::Major.start(day(7, 1)), Utc.with_ymd_and_hms(2026, 7, 1, 4, 0, 0).unwrap());
+    /// assert_eq!(UpdateKind::Minor.start(day(12, 1)), Utc.with_ymd_and_hms(2026, 12, 1, 17, 0, 0).unwrap());
+    /// ```
+    pub fn start(self, day: NaiveDate) -> DateTime<Utc> {
+        let hour = match self {
+            UpdateKind::Major => 0,
+            UpdateKind::Minor => 12,
+        };
+        let offset = if eastern_daylight_time(day, hour) { 4 } else { 5 };
+        Utc.from_utc_datetime(&day.and_time(chrono::NaiveTime::MIN)) + Duration::hours(hour + offset)
+    }
+    /// The update that had last started at `time`, and how long before `time` it did.
+    pub fn at(time: DateTime<Utc>) -> (UpdateKind, Duration) {
+        let day = time.date_naive();
+        let (kind, start) = [day - Duration::days(1), day].iter()
+            .flat_map(|&day| [UpdateKind::Major, UpdateKind::Minor].iter().map(move |&kind| (kind, kind.start(day))))
+            .filter(|(_, start)| *start <= time)
+            .max_by_key(|(_, start)| *start)
+            .expect("yesterday's updates started before today");
+        (kind, time - start)
+    }
+}
+impl fmt::Display for UpdateKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            UpdateKind::Major => "major",
+            UpdateKind::Minor => "minor",
+        })
+    }
+}
+
+/// Whether New York keeps daylight saving time at `hour` o'clock on `day`,
+/// which it does from 2am on the second Sunday in March until 2am on the
+/// first Sunday in November.
+fn eastern_daylight_time(day: NaiveDate, hour: i64) -> bool {
+    let sunday = |month, n| NaiveDate::from_weekday_of_month_opt(day.year(), month, Weekday::Sun, n);
+    let (begins, ends) = match (sunday(3, 2), sunday(11, 1)) {
+        (Some(begins), Some(ends)) => (begins, ends),
+        _ => return false,
+    };
+    let since = |date: NaiveDate| day > date || (day == date && hour >= 2);
+    since(begins) && !since(ends)
+}
+
+/// How far into an update a region tends to update, from times it was seen doing so.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateWindow {
+    pub kind: UpdateKind,
+    /// How many updates it was seen in.
+    pub seen: usize,
+    pub earliest: Duration,
+    pub median: Duration,
+    pub latest: Duration,
+}
+impl UpdateWindow {
+    /// A window for each update the region was seen in, major first.
+    /// The same time given twice counts once.
+    pub fn estimate(times: impl IntoIterator<Item = DateTime<Utc>>) -> Vec<UpdateWindow> {
+        let mut offsets: BTreeMap<UpdateKind, Vec<Duration>> = BTreeMap::new();
+        let times: std::collections::BTreeSet<_> = times.into_iter().collect();
+        for time in times {
+            let (kind, offset) = UpdateKind::at(time);
+            offsets.entry(kind).or_default().push(offset);
+        }
+        offsets.into_iter()
+            .map(|(kind, mut offsets)| {
+                offsets.sort();
+                UpdateWindow {
+                    kind,
+                    seen: offsets.len(),
+                    earliest: offsets[0],
+                    median: offsets[offsets.len() / 2],
+                    latest: offsets[offsets.len() - 1],
+                }
+            })
+            .collect()
+    }
+    /// When the region should next update in this window's update, after `now`.
+    pub fn next(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.date_naive();
+        (0..3).map(|days| self.kind.start(today + Duration::days(days)) + self.median)
+            .find(|x| *x > now)
+            .expect("an update happens every day")
+    }
+}
@@ -0,0 +1,72 @@
+//! Regional message boards.
+use chrono::{DateTime, Utc};
+use core::fmt;
+use core::str::FromStr;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Whether a post can still be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostStatus {
+    Visible,
+    /// Hidden by a regional officer, but still readable.
+    Suppressed,
+    /// Deleted by its author.
+    Deleted,
+    /// Suppressed by a moderator, and no longer readable.
+    Moderated,
+}
+#[derive(Error, Debug)]
+#[error("unknown post status {0:?}")]
+pub struct ParseStatusError(String);
+impl FromStr for PostStatus {
+    type Err = ParseStatusError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim() {
+            "0" => Ok(PostStatus::Visible),
+            "1" => Ok(PostStatus::Suppressed),
+            "2" => Ok(PostStatus::Deleted),
+            "9" => Ok(PostStatus::Moderated),
+            _ => Err(ParseStatusError(input.to_owned())),
+        }
+    }
+}
+impl fmt::Display for PostStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PostStatus::Visible => "visible",
+            PostStatus::Suppressed => "suppressed",
+            PostStatus::Deleted => "deleted",
+            PostStatus::Moderated => "suppressed by a moderator",
+        })
+    }
+}
+
+/// A post on a regional message board, from the `messages` shard.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Post {
+    pub id: u64,
+    #[serde(rename = "TIMESTAMP", with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+    /// Author.
+    #[serde(rename = "NATION")]
+    pub nation: String,
+    #[serde(rename = "STATUS", deserialize_with = "crate::api::from_text")]
+    pub status: PostStatus,
+    /// Who suppressed the post, if anyone did.
+    #[serde(rename = "SUPPRESSOR", default)]
+    pub suppressor: Option<String>,
+    #[serde(rename = "LIKES", default)]
+    pub likes: u32,
+    #[serde(rename = "LIKERS", default, deserialize_with = "crate::api::colon_list")]
+    pub likers: Vec<String>,
+    /// Text of the post, in BBCode.
+    #[serde(rename = "MESSAGE", default)]
+    pub message: String,
+}
+/// Oldest first.
+#[derive(Debug, Deserialize, Default)]
+pub struct Messages {
+    #[serde(rename = "POST", default)]
+    pub posts: Vec<Post>,
+}
@@ -0,0 +1,106 @@
+//! Requests and commands sent as one of the profile's nations, logging in
+//! again when the API refuses the stored credentials and keeping whatever
+//! new ones it hands back.
+//!
+//! [`Request::send_retry`] and [`Command::send`] retry the same way, but
+//! leave storing credentials and marking refused pins to the caller.
+use serde::de::DeserializeOwned;
+
+use crate::api::{self, Action, Command, Failure, Request, Response, Shard, Target};
+use crate::http::HttpClient;
+use crate::Nation;
+
+/// When to log in again after the API refuses credentials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Log in with the autologin or password when the pin is refused.
+    /// Pins go bad whenever something else logs in as the nation.
+    pub refresh_pin: bool,
+    /// Log in with the password when the autologin is refused,
+    /// as it is once the password has been changed on the site.
+    pub password_fallback: bool,
+}
+impl RetryPolicy {
+    /// Give up at the first refusal.
+    pub const NEVER: RetryPolicy = RetryPolicy { refresh_pin: false, password_fallback: false };
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { refresh_pin: true, password_fallback: true }
+    }
+}
+
+/// Sends as `nation`, with its stored credentials.
+///
+/// A refused pin is marked as rejected on the nation, and the pin and
+/// autologin from a successful response replace the stored ones, so the
+/// nation only needs saving afterwards.
+pub struct Session<'a, C> {
+    client: &'a C,
+    nation: &'a mut Nation,
+    policy: RetryPolicy,
+    version: u16,
+    base: &'a str,
+}
+impl<'a, C: HttpClient> Session<'a, C> {
+    pub fn new(client: &'a C, nation: &'a mut Nation) -> Self {
+        Session { client, nation, policy: RetryPolicy::default(), version: crate::API_VERSION, base: crate::api::base() }
+    }
+    pub fn policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    /// API version to ask for, normally [`crate::API_VERSION`].
+    pub fn version(mut self, version: u16) -> Self {
+        self.version = version;
+        self
+    }
+    /// Send to an API other than the live one, such as a mock server.
+    pub fn base(mut self, base: &'a str) -> Self {
+        self.base = base;
+        self
+    }
+    /// Fetch shards of the nation, private ones included.
+    pub async fn request(&mut self, shards: Vec<Shard>) -> Result<Response, Failure> {
+        let url = Request {
+            target: Target::Nation(&self.nation.name),
+            auth: None,
+            shards,
+            version: self.version,
+        }.url_at(self.base);
+        self.send(&url).await
+    }
+    /// Send a private command. Each command has its own response format, hence `T`.
+    pub async fn command<T: DeserializeOwned + Send + 'static>(&mut self, action: Action) -> Result<Response<T>, Failure> {
+        let url = Command {
+            nation: &self.nation.name,
+            auth: &self.nation.auth,
+            action,
+            version: self.version,
+            readonly: self.nation.readonly,
+        }.url_at(self.base);
+        let result = api::send_command(self.client, &self.nation.name, self.nation.readonly, &url,
+                                       &self.nation.auth, self.policy).await;
+        self.keep_credentials(&result);
+        result
+    }
+    async fn send<T: DeserializeOwned + Send + 'static>(&mut self, url: &str) -> Result<Response<T>, Failure> {
+        let result = api::send_url_policy(self.client, url, Some(&self.nation.auth), self.policy).await;
+        self.keep_credentials(&result);
+        result
+    }
+    /// Keep any new credentials a response hands back, and forget a pin it refused.
+    fn keep_credentials<T>(&mut self, result: &Result<Response<T>, Failure>) {
+        let auth = &mut self.nation.auth;
+        match result {
+            Ok(response) => {
+                if response.pin_refused {
+                    auth.reject_pin();
+                }
+                auth.update(response.autologin.clone(), response.pin.clone());
+            },
+            Err(Failure::BadPin) => auth.reject_pin(),
+            Err(_) => (),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! A nation's appearance: its motto, pretitle, flag, banner, and the
+//! free text fields like its capital, and themes that set them across
+//! many nations at once.
+//!
+//! The API has no commands for changing these, so changes are checked
+//! here and then made on the site's settings page.
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::api::{ResolvedShard, Shard};
+use crate::banners::Banners;
+
+/// Where a logged in nation changes its appearance.
+pub const SETTINGS_PAGE: &str = "https://www.nationstates.net/page=settings";
+/// Population, in millions, a nation needs before it can pick its own pretitle.
+pub const PRETITLE_POPULATION: u64 = 250;
+
+/// Free text a nation can fill in about itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Field {
+    Capital,
+    Leader,
+    Religion,
+    Currency,
+    Animal,
+}
+impl Field {
+    pub const ALL: [Field; 5] = [Field::Capital, Field::Leader, Field::Religion, Field::Currency, Field::Animal];
+    pub fn shard(self) -> Shard {
+        match self {
+            Field::Capital => Shard::Capital,
+            Field::Leader => Shard::Leader,
+            Field::Religion => Shard::Religion,
+            Field::Currency => Shard::Currency,
+            Field::Animal => Shard::Animal,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Capital => "capital",
+            Field::Leader => "leader",
+            Field::Religion => "religion",
+            Field::Currency => "currency",
+            Field::Animal => "animal",
+        }
+    }
+}
+
+/// What a nation looks like now, as far as the public shards say.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Appearance {
+    pub motto: Option<String>,
+    /// Like "Republic".
+    pub pretitle: Option<String>,
+    /// URL of the flag image.
+    pub flag: Option<String>,
+    /// Code of the banner on display, like `v1`.
+    pub banner: Option<String>,
+    /// In millions, which decides whether the pretitle can be changed.
+    pub population: Option<u64>,
+    /// Free text fields, left out where they're blank.
+    pub fields: BTreeMap<Field, String>,
+}
+impl Appearance {
+    /// The shards an [`Appearance`] is made of.
+    pub fn shards() -> Vec<Shard> {
+        let mut shards = vec![Shard::Motto, Shard::Pretitle, Shard::Flag, Shard::Banner, Shard::Population];
+        shards.extend(Field::ALL.iter().map(|x| x.shard()));
+        shards
+    }
+    /// Pick the appearance shards out of a response, ignoring anything else.
+    pub fn from_shards(shards: impl IntoIterator<Item = ResolvedShard>) -> Self {
+        let mut appearance = Self::default();
+        for shard in shards {
+            match shard {
+                ResolvedShard::Motto(x) => appearance.motto = Some(x),
+                ResolvedShard::Pretitle(x) => appearance.pretitle = Some(x),
+                ResolvedShard::Flag(x) => appearance.flag = Some(x),
+                ResolvedShard::Banner(x) => appearance.banner = Some(x),
+                ResolvedShard::Population(x) => appearance.population = Some(x),
+                ResolvedShard::Capital(x) => appearance.set_field(Field::Capital, x),
+                ResolvedShard::Leader(x) => appearance.set_field(Field::Leader, x),
+                ResolvedShard::Religion(x) => appearance.set_field(Field::Religion, x),
+                ResolvedShard::Currency(x) => appearance.set_field(Field::Currency, x),
+                ResolvedShard::Animal(x) => appearance.set_field(Field::Animal, x),
+                _ => (),
+            }
+        }
+        appearance
+    }
+    fn set_field(&mut self, field: Field, value: String) {
+        if !value.trim().is_empty() {
+            self.fields.insert(field, value);
+        }
+    }
+}
+
+/// Image formats the site takes for flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Svg,
+}
+impl FlagFormat {
+    /// Tell the format of an image from its first bytes, rather than trusting its name.
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(FlagFormat::Png)
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            Some(FlagFormat::Jpeg)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(FlagFormat::Gif)
+        } else if String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).contains("<svg") {
+            Some(FlagFormat::Svg)
+        } else {
+            None
+        }
+    }
+}
+impl fmt::Display for FlagFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FlagFormat::Png => "PNG",
+            FlagFormat::Jpeg => "JPEG",
+            FlagFormat::Gif => "GIF",
+            FlagFormat::Svg => "SVG",
+        })
+    }
+}
+
+/// A change to a nation's appearance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Motto(String),
+    Pretitle(String),
+    /// The image to upload, as read from disk.
+    Flag(Vec<u8>),
+    /// Banner code, like `v1`.
+    Banner(String),
+    Field(Field, String),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ChangeError {
+    #[error("the {0} is empty")]
+    Empty(&'static str),
+    #[error("the {0} has to fit on one line")]
+    LineBreak(&'static str),
+    #[error("a nation needs {} million people to pick its pretitle, and this one has {0} million", PRETITLE_POPULATION)]
+    PretitleLocked(u64),
+    #[error("the flag isn't a PNG, JPEG, GIF, or SVG image")]
+    FlagFormat,
+    #[error("banner {0} isn't one the nation has unlocked")]
+    Locked(String),
+}
+
+impl Change {
+    /// What's being changed, for messages.
+    pub fn setting(&self) -> &'static str {
+        match self {
+            Change::Motto(_) => "motto",
+            Change::Pretitle(_) => "pretitle",
+            Change::Flag(_) => "flag",
+            Change::Banner(_) => "banner",
+            Change::Field(field, _) => field.name(),
+        }
+    }
+    /// Check the change can be made to a nation that looks like `current`.
+    /// Banner changes are only checked against `banners` if it's given.
+    pub fn check(&self, current: &Appearance, banners: Option<&Banners>) -> Result<(), ChangeError> {
+        match self {
+            Change::Motto(text) | Change::Pretitle(text) | Change::Field(_, text) => {
+                if text.trim().is_empty() {
+                    return Err(ChangeError::Empty(self.setting()));
+                }
+                if text.contains(&['\n', '\r'][..]) {
+                    return Err(ChangeError::LineBreak(self.setting()));
+                }
+                match (self, current.population) {
+                    (Change::Pretitle(_), Some(population)) if population < PRETITLE_POPULATION => {
+                        Err(ChangeError::PretitleLocked(population))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Change::Flag(bytes) => FlagFormat::sniff(bytes).map(|_| ()).ok_or(ChangeError::FlagFormat),
+            Change::Banner(code) => match banners {
+                Some(banners) if !banners.codes.contains(code) => Err(ChangeError::Locked(code.clone())),
+                _ => Ok(()),
+            },
+        }
+    }
+    /// Whether `current` already looks the way this would make it.
+    /// A new flag always counts as a change, since the site renames uploads.
+    pub fn is_noop(&self, current: &Appearance) -> bool {
+        match self {
+            Change::Motto(x) => current.motto.as_deref() == Some(x.trim()),
+            Change::Pretitle(x) => current.pretitle.as_deref() == Some(x.trim()),
+            Change::Flag(_) => false,
+            Change::Banner(x) => current.banner.as_deref() == Some(x.as_str()),
+            Change::Field(field, x) => current.fields.get(field).map(String::as_str) == Some(x.trim()),
+        }
+    }
+}
+
+/// Settings a theme gives nations. Text can mention `{nation}`, which
+/// becomes the name of the nation it's applied to.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ThemeSettings {
+    pub motto: Option<String>,
+    pub pretitle: Option<String>,
+    /// Image to upload, relative to the theme file.
+    pub flag: Option<PathBuf>,
+    pub banner: Option<String>,
+    pub capital: Option<String>,
+    pub leader: Option<String>,
+    pub religion: Option<String>,
+    pub currency: Option<String>,
+    pub animal: Option<String>,
+}
+
+/// A theme file, in TOML: settings for every nation at the top, and
+/// settings for particular nations under `[nations.<name>]` taking their place.
+#[derive(Debug, Default, Deserialize)]
+pub struct Theme {
+    #[serde(flatten)]
+    pub defaults: ThemeSettings,
+    #[serde(default)]
+    pub nations: BTreeMap<String, ThemeSettings>,
+    /// Directory flags are found relative to.
+    #[serde(skip)]
+    pub base: PathBuf,
+}
+
+#[derive(Error, Debug)]
+pub enum ThemeError {
+    #[error("{0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("{0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+impl Theme {
+    pub fn load(path: &Path) -> Result<Self, ThemeError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ThemeError::Io(path.to_owned(), e))?;
+        let mut theme: Self = toml::from_str(&text)?;
+        theme.base = path.parent().map(Path::to_owned).unwrap_or_default();
+        Ok(theme)
+    }
+    /// The settings for `nation`: its own where it has them, the defaults otherwise.
+    pub fn settings(&self, nation: &str) -> ThemeSettings {
+        let name = crate::canonical_name(nation);
+        let own = self.nations.iter()
+            .find(|(x, _)| crate::canonical_name(x) == name)
+            .map(|(_, x)| x.clone())
+            .unwrap_or_default();
+        let defaults = self.defaults.clone();
+        ThemeSettings {
+            motto: own.motto.or(defaults.motto),
+            pretitle: own.pretitle.or(defaults.pretitle),
+            flag: own.flag.or(defaults.flag),
+            banner: own.banner.or(defaults.banner),
+            capital: own.capital.or(defaults.capital),
+            leader: own.leader.or(defaults.leader),
+            religion: own.religion.or(defaults.religion),
+            currency: own.currency.or(defaults.currency),
+            animal: own.animal.or(defaults.animal),
+        }
+    }
+    /// The changes the theme makes to `nation`, reading its flag if it has one.
+    pub fn changes(&self, nation: &str) -> Result<Vec<Change>, ThemeError> {
+        let settings = self.settings(nation);
+        let fill = |text: String| text.replace("{nation}", nation);
+        let mut changes = Vec::new();
+        changes.extend(settings.motto.map(fill).map(Change::Motto));
+        changes.extend(settings.pretitle.map(fill).map(Change::Pretitle));
+        if let Some(flag) = settings.flag {
+            let path = self.base.join(flag);
+            changes.push(Change::Flag(std::fs::read(&path).map_err(|e| ThemeError::Io(path, e))?));
+        }
+        changes.extend(settings.banner.map(Change::Banner));
+        let fields = [settings.capital, settings.leader, settings.religion, settings.currency, settings.animal];
+        for (field, value) in Field::ALL.iter().zip(fields) {
+            changes.extend(value.map(|x| Change::Field(*field, fill(x))));
+        }
+        Ok(changes)
+    }
+}
@@ -0,0 +1,161 @@
+//! Sending telegrams through the API.
+//!
+//! Telegrams are sent from templates that have to be written on the site first.
+//! Sending one needs an API client key as well as the template's ID and secret key.
+use crate::api::Failure;
+use crate::http::HttpClient;
+use core::fmt;
+use core::str::FromStr;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Minimum time between two recruitment telegrams.
+pub const RECRUITMENT_INTERVAL: Duration = Duration::from_secs(180);
+/// Minimum time between two non-recruitment telegrams.
+pub const INTERVAL: Duration = Duration::from_secs(30);
+
+/// A telegram template, written as `tgid:key` on the command line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub tgid: u64,
+    pub secret: String,
+    /// Marked as a recruitment template on the site,
+    /// which holds it to the slower recruitment rate limit.
+    #[serde(default)]
+    pub recruitment: bool,
+}
+impl Template {
+    /// Minimum time between two telegrams from this template.
+    pub fn interval(&self) -> Duration {
+        if self.recruitment { RECRUITMENT_INTERVAL } else { INTERVAL }
+    }
+}
+#[derive(Error, Debug)]
+#[error("expected a template as tgid:key, got {0:?}")]
+pub struct ParseTemplateError(String);
+impl FromStr for Template {
+    type Err = ParseTemplateError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut parts = input.splitn(2, ':');
+        match (parts.next().map(str::parse), parts.next()) {
+            (Some(Ok(tgid)), Some(secret)) if !secret.is_empty() => Ok(Template {
+                tgid,
+                secret: secret.to_owned(),
+                recruitment: false,
+            }),
+            _ => Err(ParseTemplateError(input.to_owned())),
+        }
+    }
+}
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The secret key stays out of logs.
+        write!(f, "%TEMPLATE-{}%", self.tgid)
+    }
+}
+
+/// Templates saved under names, so the secret keys needn't be typed out each time.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Templates {
+    #[serde(default)]
+    pub templates: std::collections::BTreeMap<String, Template>,
+}
+#[derive(Error, Debug)]
+pub enum TemplatesError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+}
+impl Templates {
+    /// Load saved templates, or none if nothing's been saved yet.
+    pub fn load(path: &Path) -> Result<Self, TemplatesError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(serde_json::from_str(&text)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+    pub fn save(&self, path: &Path) -> Result<(), TemplatesError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // Secret keys let anyone send as the template's author.
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        serde_json::to_writer_pretty(options.open(path)?, self)?;
+        Ok(())
+    }
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+}
+
+/// A single telegram to a single recipient.
+#[derive(Debug)]
+pub struct Telegram<'a> {
+    /// API client key the telegram is sent on behalf of.
+    pub client_key: &'a str,
+    pub template: &'a Template,
+    pub to: &'a str,
+}
+impl Telegram<'_> {
+    pub fn url(&self) -> String {
+        crate::api::Query::new(crate::api::base())
+            .param("a", "sendTG")
+            .param("client", self.client_key)
+            .param("tgid", self.template.tgid.to_string())
+            .param("key", &self.template.secret)
+            .param("to", self.to)
+            .build()
+    }
+    /// What sending the telegram would do, with the keys redacted.
+    pub fn preview(&self) -> String {
+        format!("GET {}", crate::cassette::redact(&self.url()))
+    }
+    /// Queue the telegram for delivery.
+    /// Respecting the telegram rate limits is up to the caller.
+    pub async fn send<C: HttpClient>(&self, client: &C) -> Result<(), Failure> {
+        let result = if crate::api::read_only() {
+            Err(crate::api::refuse("everything"))
+        } else {
+            self.send_unlogged(client).await
+        };
+        crate::audit::record(None, &self.url(), result.as_ref().map(|_| ()).map_err(ToString::to_string));
+        result
+    }
+    async fn send_unlogged<C: HttpClient>(&self, client: &C) -> Result<(), Failure> {
+        crate::ratelimit::wait_turn().await;
+        let response = client.get(&self.url(), &[]).await.map_err(Failure::Network)?;
+        let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        if status == StatusCode::OK {
+            return Ok(());
+        }
+        // Rejected keys come back as a 403 saying which key was wrong.
+        let body = response.body.text().await.unwrap_or_default();
+        match (status, crate::api::error_reason(&body)) {
+            (StatusCode::FORBIDDEN, None) => Err(Failure::BadAuth),
+            (status, reason) => Err(Failure::Other(status, reason)),
+        }
+    }
+}
+
+/// Lengths of the site's telegram delivery queues,
+/// from the world `tgqueue` shard.
+#[derive(Debug, serde::Deserialize)]
+pub struct Queue {
+    /// Telegrams sent by hand on the site.
+    #[serde(rename = "MANUAL")]
+    pub manual: u32,
+    /// Mass telegrams sent through stamps.
+    #[serde(rename = "MASS")]
+    pub mass: u32,
+    /// Telegrams sent through the API.
+    #[serde(rename = "API")]
+    pub api: u32,
+}
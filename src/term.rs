@@ -0,0 +1,104 @@
+//! Formatting for the terminal: colors for statuses, and tables that fit
+//! the width of the window.
+//!
+//! Colors are only used when stdout is a terminal, unless asked for with
+//! `--color=always`. Setting `NO_COLOR` turns them off the same way
+//! `--color=never` does, following <https://no-color.org>.
+use comfy_table::{Attribute, Cell, ContentArrangement};
+use owo_colors::OwoColorize;
+use std::fmt;
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// When writing to a terminal that isn't `dumb`, and `NO_COLOR` isn't set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        })
+    }
+}
+impl FromStr for ColorChoice {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("expected auto, always, or never, not {:?}", s)),
+        }
+    }
+}
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Decide once whether output gets colored.
+pub fn set_color(choice: ColorChoice) {
+    let color = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => std::io::stdout().is_terminal()
+            && std::env::var_os("NO_COLOR").is_none_or(|x| x.is_empty())
+            && std::env::var_os("TERM").is_none_or(|x| x != "dumb"),
+    };
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+/// Whether output gets colored.
+pub fn color() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+/// Something that went as it should, in green.
+pub fn good(text: impl fmt::Display) -> String {
+    if color() { text.green().to_string() } else { text.to_string() }
+}
+
+/// Something that failed, in red.
+pub fn bad(text: impl fmt::Display) -> String {
+    if color() { text.red().to_string() } else { text.to_string() }
+}
+
+/// Something worth a look that isn't a failure, in yellow.
+pub fn warn(text: impl fmt::Display) -> String {
+    if color() { text.yellow().to_string() } else { text.to_string() }
+}
+
+/// Headings and names to pick out.
+pub fn bold(text: impl fmt::Display) -> String {
+    if color() { text.bold().to_string() } else { text.to_string() }
+}
+
+/// Lay out rows under their column names, two spaces apart. On a terminal,
+/// columns are wrapped to fit the window; otherwise lines are as long as they need to be.
+pub fn table(columns: &[String], rows: &[Vec<String>]) -> String {
+    let mut table = comfy_table::Table::new();
+    table.load_preset(comfy_table::presets::NOTHING)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    if color() {
+        table.enforce_styling();
+    }
+    table.set_header(columns.iter().map(|x| {
+        let cell = Cell::new(x);
+        if color() { cell.add_attribute(Attribute::Bold) } else { cell }
+    }));
+    for row in rows {
+        table.add_row(row);
+    }
+    let last = columns.len().saturating_sub(1);
+    for (i, column) in table.column_iter_mut().enumerate() {
+        column.set_padding((0, if i == last { 0 } else { 2 }));
+    }
+    table.lines().map(|x| x.trim_end().to_owned()).collect::<Vec<_>>().join("\n")
+}
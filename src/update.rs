@@ -0,0 +1,199 @@
+//! Replacing the running binary with the latest GitHub release,
+//! for people who didn't install with cargo.
+//!
+//! A release carries one binary per platform, named like
+//! `nation-x86_64-linux` or `nation-x86_64-windows.exe`, and a `SHA256SUMS`
+//! file listing each binary's checksum the way `sha256sum` prints them.
+//! A binary is only installed if its checksum is listed there and matches.
+//! Releases aren't signed, so the checksum guards against broken downloads,
+//! not against someone who can change the release itself.
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The latest release, through the GitHub API.
+pub const LATEST_RELEASE: &str = "https://api.github.com/repos/green-narofsky/nation-rs/releases/latest";
+/// The release file holding every binary's checksum.
+pub const CHECKSUMS: &str = "SHA256SUMS";
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    /// Like `v0.2.0`.
+    pub tag_name: String,
+    pub assets: Vec<Asset>,
+}
+#[derive(Debug, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+impl Release {
+    fn asset(&self, name: &str) -> Result<&Asset, UpdateError> {
+        self.assets.iter()
+            .find(|x| x.name == name)
+            .ok_or_else(|| UpdateError::Missing(name.to_owned(), self.tag_name.clone()))
+    }
+    /// Whether this release is newer than the running binary.
+    pub fn is_newer(&self) -> bool {
+        match (parse_version(&self.tag_name), parse_version(env!("CARGO_PKG_VERSION"))) {
+            (Some(release), Some(current)) => release > current,
+            // Not worth replacing the binary over a tag that can't be read.
+            _ => false,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum UpdateError {
+    #[error("{0}")]
+    Network(#[from] reqwest::Error),
+    #[error("GitHub answered with {0}")]
+    Status(reqwest::StatusCode),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("unexpected release details from GitHub: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no {0} in release {1}")]
+    Missing(String, String),
+    #[error("{0} isn't listed in the release's {}", CHECKSUMS)]
+    Unlisted(String),
+    #[error("{name} doesn't match its checksum: expected {expected}, got {actual}")]
+    Checksum { name: String, expected: String, actual: String },
+}
+
+/// `v1.2.3` or `1.2.3` as numbers, ignoring anything after a `-` or `+`.
+fn parse_version(tag: &str) -> Option<(u64, u64, u64)> {
+    let tag = tag.trim().trim_start_matches('v');
+    let tag = tag.split(&['-', '+'][..]).next()?;
+    let mut parts = tag.split('.').map(|x| x.parse().ok());
+    Some((parts.next()??, parts.next().unwrap_or(Some(0))?, parts.next().unwrap_or(Some(0))?))
+}
+
+/// Name of the release binary for the platform this was built for.
+pub fn asset_name() -> String {
+    format!("nation-{}-{}{}", std::env::consts::ARCH, std::env::consts::OS, std::env::consts::EXE_SUFFIX)
+}
+
+/// The checksum listed for `name` in a `SHA256SUMS` file.
+fn listed_checksum<'a>(sums: &'a str, name: &str) -> Option<&'a str> {
+    sums.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let sum = fields.next()?;
+        // sha256sum marks files read in binary mode with a star.
+        let file = fields.next()?.trim_start_matches('*');
+        if file == name { Some(sum) } else { None }
+    })
+}
+
+async fn get(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, UpdateError> {
+    use tokio_compat_02::FutureExt;
+    // Asked for explicitly, so a change of GitHub's default doesn't change the fields.
+    let response = client.get(url)
+        .header(reqwest::header::ACCEPT, "application/vnd.github.v3+json, */*")
+        .send().compat().await?;
+    if !response.status().is_success() {
+        return Err(UpdateError::Status(response.status()));
+    }
+    Ok(response.bytes().compat().await?.to_vec())
+}
+
+/// The latest release.
+pub async fn latest(client: &reqwest::Client) -> Result<Release, UpdateError> {
+    Ok(serde_json::from_slice(&get(client, LATEST_RELEASE).await?)?)
+}
+
+/// Download this platform's binary from `release`, check it against the
+/// release's checksums, and put it in place of the one at `exe`.
+pub async fn install(client: &reqwest::Client, release: &Release, exe: &Path) -> Result<(), UpdateError> {
+    let name = asset_name();
+    let binary = release.asset(&name)?;
+    let sums = get(client, &release.asset(CHECKSUMS)?.browser_download_url).await?;
+    let sums = String::from_utf8_lossy(&sums);
+    let expected = listed_checksum(&sums, &name).ok_or_else(|| UpdateError::Unlisted(name.clone()))?;
+    let bytes = get(client, &binary.browser_download_url).await?;
+    let actual = Sha256::digest(&bytes).iter().map(|x| format!("{:02x}", x)).collect::<String>();
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(UpdateError::Checksum { name, expected: expected.to_owned(), actual });
+    }
+    replace(exe, &bytes)
+}
+
+/// Swap the binary at `exe` for `bytes`. The new one is written next to it
+/// first, so a failed write leaves the old one alone. Windows won't let a
+/// running binary be overwritten or deleted, but will let it be renamed,
+/// so the old one is moved aside rather than replaced directly.
+fn replace(exe: &Path, bytes: &[u8]) -> Result<(), UpdateError> {
+    let new = sibling(exe, "new");
+    let old = sibling(exe, "old");
+    std::fs::write(&new, bytes)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&new, std::fs::Permissions::from_mode(0o755))?;
+    }
+    let _ = std::fs::remove_file(&old);
+    std::fs::rename(exe, &old)?;
+    if let Err(e) = std::fs::rename(&new, exe) {
+        let _ = std::fs::rename(&old, exe);
+        return Err(e.into());
+    }
+    // Still in use on Windows, so it stays until the next update.
+    let _ = std::fs::remove_file(&old);
+    Ok(())
+}
+
+/// `exe` with `.suffix` added, like `nation.exe.old`.
+fn sibling(exe: &Path, suffix: &str) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_owned();
+    name.push(".");
+    name.push(suffix);
+    exe.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUMS: &str = "\
+0123abcd  nation-x86_64-linux
+4567ef01 *nation-x86_64-windows.exe
+";
+
+    #[test]
+    fn checksums_by_name() {
+        assert_eq!(listed_checksum(SUMS, "nation-x86_64-linux"), Some("0123abcd"));
+        // Listed with sha256sum's binary mode star.
+        assert_eq!(listed_checksum(SUMS, "nation-x86_64-windows.exe"), Some("4567ef01"));
+    }
+
+    #[test]
+    fn unlisted_checksums() {
+        assert_eq!(listed_checksum(SUMS, "nation-aarch64-macos"), None);
+        assert_eq!(listed_checksum(SUMS, "nation-x86_64"), None);
+        assert_eq!(listed_checksum("", "nation-x86_64-linux"), None);
+        assert_eq!(listed_checksum("0123abcd\n", "0123abcd"), None);
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(parse_version("v0.2.0"), Some((0, 2, 0)));
+        assert_eq!(parse_version("1.10.3"), Some((1, 10, 3)));
+        assert_eq!(parse_version("v2"), Some((2, 0, 0)));
+        assert_eq!(parse_version("v1.2.3-rc.1"), Some((1, 2, 3)));
+        assert_eq!(parse_version("1.2.3+build.5"), Some((1, 2, 3)));
+        assert_eq!(parse_version("nightly"), None);
+        assert_eq!(parse_version("v1.x"), None);
+        assert!(parse_version("v0.10.0") > parse_version("v0.9.9"));
+    }
+
+    #[test]
+    fn newer_releases() {
+        let release = |tag: &str| Release { tag_name: tag.into(), assets: Vec::new() };
+        assert!(release("v999.0.0").is_newer());
+        assert!(!release("v0.0.0").is_newer());
+        assert!(!release(concat!("v", env!("CARGO_PKG_VERSION"))).is_newer());
+        assert!(!release(concat!("v", env!("CARGO_PKG_VERSION"), "-rc.1")).is_newer());
+        assert!(!release("latest").is_newer());
+    }
+}
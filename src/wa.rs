@@ -0,0 +1,308 @@
+//! World Assembly data.
+use chrono::{DateTime, Utc};
+use core::fmt;
+use core::str::FromStr;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// One of the two World Assembly councils.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Council {
+    General,
+    Security,
+}
+impl Council {
+    /// Numeric ID used by the API's `wa` parameter.
+    pub fn id(self) -> u8 {
+        match self {
+            Council::General => 1,
+            Council::Security => 2,
+        }
+    }
+}
+#[derive(Error, Debug)]
+#[error("unknown council {0:?}, expected \"ga\" or \"sc\"")]
+pub struct ParseCouncilError(String);
+impl FromStr for Council {
+    type Err = ParseCouncilError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "ga" | "1" | "general" | "general assembly" => Ok(Council::General),
+            "sc" | "2" | "security" | "security council" => Ok(Council::Security),
+            _ => Err(ParseCouncilError(input.to_owned())),
+        }
+    }
+}
+impl fmt::Display for Council {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Council::General => "General Assembly",
+            Council::Security => "Security Council",
+        })
+    }
+}
+
+/// A single General Secretariat ruling on a proposal.
+#[derive(Debug, Deserialize)]
+pub struct Ruling {
+    #[serde(rename = "NATION")]
+    pub nation: String,
+    #[serde(rename = "REASON", default)]
+    pub reason: String,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct RulingLog {
+    #[serde(rename = "ENTRY", default)]
+    pub entries: Vec<Ruling>,
+}
+/// General Secretariat legality review of a GA proposal.
+#[derive(Debug, Deserialize, Default)]
+pub struct GenSec {
+    #[serde(rename = "LOGLEGAL", default)]
+    pub legal: RulingLog,
+    #[serde(rename = "LOGILLEGAL", default)]
+    pub illegal: RulingLog,
+}
+
+/// Where a proposal stands with the General Secretariat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegalStatus {
+    /// No rulings yet, or not subject to review (SC proposals).
+    Unreviewed,
+    Legal,
+    Illegal,
+}
+impl fmt::Display for LegalStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LegalStatus::Unreviewed => "unreviewed",
+            LegalStatus::Legal => "legal",
+            LegalStatus::Illegal => "illegal",
+        })
+    }
+}
+
+/// A proposal waiting in a council's queue.
+#[derive(Debug, Deserialize)]
+pub struct Proposal {
+    #[serde(rename = "ID")]
+    pub id: String,
+    #[serde(rename = "NAME")]
+    pub name: String,
+    #[serde(rename = "CATEGORY")]
+    pub category: String,
+    /// Category specific detail, like strength or the target of a commendation.
+    #[serde(rename = "OPTION", default)]
+    pub option: String,
+    #[serde(rename = "PROPOSED_BY")]
+    pub proposed_by: String,
+    #[serde(rename = "CREATED", with = "chrono::serde::ts_seconds")]
+    pub created: DateTime<Utc>,
+    /// Delegates currently approving this proposal.
+    #[serde(rename = "APPROVALS", default, deserialize_with = "crate::api::colon_list")]
+    pub approvals: Vec<String>,
+    #[serde(rename = "GENSEC")]
+    pub gensec: Option<GenSec>,
+}
+impl Proposal {
+    /// Legality by majority of General Secretariat rulings so far.
+    pub fn legal_status(&self) -> LegalStatus {
+        let gensec = match &self.gensec {
+            Some(x) => x,
+            None => return LegalStatus::Unreviewed,
+        };
+        let (legal, illegal) = (gensec.legal.entries.len(), gensec.illegal.entries.len());
+        if legal == 0 && illegal == 0 {
+            LegalStatus::Unreviewed
+        } else if illegal > legal {
+            LegalStatus::Illegal
+        } else {
+            LegalStatus::Legal
+        }
+    }
+}
+#[derive(Debug, Deserialize)]
+pub struct Proposals {
+    #[serde(rename = "PROPOSAL", default)]
+    pub inner: Vec<Proposal>,
+}
+
+/// How a delegate's vote changed, as recorded in the delegate log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoteAction {
+    For,
+    Against,
+    Withdrew,
+}
+#[derive(Error, Debug)]
+#[error("unknown vote action {0:?}")]
+pub struct ParseVoteActionError(String);
+impl FromStr for VoteAction {
+    type Err = ParseVoteActionError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "FOR" => Ok(VoteAction::For),
+            "AGAINST" => Ok(VoteAction::Against),
+            "WITHDREW" => Ok(VoteAction::Withdrew),
+            _ => Err(ParseVoteActionError(input.to_owned())),
+        }
+    }
+}
+impl fmt::Display for VoteAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            VoteAction::For => "for",
+            VoteAction::Against => "against",
+            VoteAction::Withdrew => "withdrew",
+        })
+    }
+}
+/// An entry in the `dellog` shard.
+#[derive(Debug, Deserialize)]
+pub struct DelegateLogEntry {
+    #[serde(rename = "NATION")]
+    pub nation: String,
+    #[serde(rename = "ACTION", deserialize_with = "crate::api::from_text")]
+    pub action: VoteAction,
+    #[serde(rename = "VOTES")]
+    pub votes: u32,
+    #[serde(rename = "TIMESTAMP", with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct DelegateLog {
+    #[serde(rename = "ENTRY", default)]
+    pub entries: Vec<DelegateLogEntry>,
+}
+/// A delegate's current vote, from the `delvotes` shard.
+#[derive(Debug, Deserialize)]
+pub struct DelegateVote {
+    #[serde(rename = "NATION")]
+    pub nation: String,
+    #[serde(rename = "VOTES")]
+    pub votes: u32,
+    #[serde(rename = "TIMESTAMP", with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct DelegateVotes {
+    #[serde(rename = "DELEGATE", default)]
+    pub delegates: Vec<DelegateVote>,
+}
+/// Hourly vote totals, from the `votetrack` shard.
+#[derive(Debug, Deserialize, Default)]
+pub struct VoteTrack {
+    #[serde(rename = "N", default)]
+    pub hourly: Vec<u32>,
+}
+
+/// A resolution, either at vote or already passed.
+///
+/// The vote related shards are only sent along with
+/// the resolution currently at vote.
+// When nothing is at vote, the API sends an empty element,
+// so everything has to be defaultable.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct Resolution {
+    #[serde(rename = "NAME")]
+    pub name: String,
+    #[serde(rename = "CATEGORY")]
+    pub category: String,
+    #[serde(rename = "OPTION")]
+    pub option: String,
+    #[serde(rename = "PROPOSED_BY")]
+    pub proposed_by: String,
+    #[serde(rename = "TOTAL_VOTES_FOR")]
+    pub votes_for: u32,
+    #[serde(rename = "TOTAL_VOTES_AGAINST")]
+    pub votes_against: u32,
+    #[serde(rename = "TOTAL_NATIONS_FOR")]
+    pub nations_for: u32,
+    #[serde(rename = "TOTAL_NATIONS_AGAINST")]
+    pub nations_against: u32,
+    #[serde(rename = "VOTE_TRACK_FOR")]
+    pub track_for: VoteTrack,
+    #[serde(rename = "VOTE_TRACK_AGAINST")]
+    pub track_against: VoteTrack,
+    #[serde(rename = "DELLOG")]
+    pub delegate_log: DelegateLog,
+    #[serde(rename = "DELVOTES_FOR")]
+    pub delegates_for: DelegateVotes,
+    #[serde(rename = "DELVOTES_AGAINST")]
+    pub delegates_against: DelegateVotes,
+}
+impl Resolution {
+    /// Whether the API actually sent a resolution.
+    pub fn exists(&self) -> bool {
+        !self.name.is_empty()
+    }
+    /// Hourly `(for, against)` totals since voting opened.
+    pub fn vote_track(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.track_for.hourly.iter().copied()
+            .zip(self.track_against.hourly.iter().copied())
+    }
+}
+
+/// A Security Council resolution's mark on the nation or region it targets.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Badge {
+    /// Like `commend`, `condemn`, or `liberate`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Number of the Security Council resolution, as taken by
+    /// [`crate::api::Shard::Resolution`].
+    #[serde(rename = "$value")]
+    pub resolution: u32,
+}
+impl Badge {
+    /// What the resolution did, like "commended".
+    pub fn action(&self) -> &str {
+        match self.kind.as_str() {
+            "commend" => "commended",
+            "condemn" => "condemned",
+            "liberate" => "liberated",
+            other => other,
+        }
+    }
+}
+#[derive(Debug, Deserialize, Default)]
+pub struct Badges {
+    #[serde(rename = "WABADGE", default)]
+    pub inner: Vec<Badge>,
+}
+
+/// Whether a Security Council category changes what can be done at the
+/// borders of the region it targets, like a liberation, which keeps it
+/// from having a password.
+pub fn affects_borders(category: &str) -> bool {
+    ["Liberation", "Injunction"].iter().any(|x| x.eq_ignore_ascii_case(category))
+}
+
+/// The region a Security Council resolution or proposal is aimed at, from
+/// its option, which reads like `R:the_region`, or `N:` for a nation.
+pub fn target_region(option: &str) -> Option<&str> {
+    option.strip_prefix("R:")
+}
+
+/// Which resolution to ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionId {
+    /// The resolution currently at vote.
+    Current,
+    /// A passed resolution, by its number in the council.
+    Passed(u32),
+}
+#[derive(Error, Debug)]
+#[error("expected \"current\" or a resolution number, got {0:?}")]
+pub struct ParseResolutionIdError(String);
+impl FromStr for ResolutionId {
+    type Err = ParseResolutionIdError;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if input.eq_ignore_ascii_case("current") {
+            return Ok(ResolutionId::Current);
+        }
+        input.parse().map(ResolutionId::Passed)
+            .map_err(|_| ParseResolutionIdError(input.to_owned()))
+    }
+}
@@ -0,0 +1,25 @@
+//! World shards about nations in general, rather than any one nation or region.
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// A recently founded nation, from the `newnationdetails` shard.
+#[derive(Debug, Deserialize, Clone)]
+pub struct NewNation {
+    pub name: String,
+    #[serde(rename = "TIME", with = "chrono::serde::ts_seconds")]
+    pub founded: DateTime<Utc>,
+    /// Region the nation was founded in.
+    #[serde(rename = "REGION")]
+    pub region: String,
+}
+impl NewNation {
+    pub fn founded_in(&self, region: &str) -> bool {
+        crate::canonical_name(&self.region) == crate::canonical_name(region)
+    }
+}
+/// Newest first, like the `newnations` shard.
+#[derive(Debug, Deserialize, Default)]
+pub struct NewNationDetails {
+    #[serde(rename = "NEWNATION", default)]
+    pub inner: Vec<NewNation>,
+}
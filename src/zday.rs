@@ -0,0 +1,37 @@
+//! Z-Day, the yearly zombie event.
+//!
+//! The API reports how nations and regions are faring, but has no
+//! commands for the event: curing, exterminating, and researching are
+//! all done on the site.
+use serde::Deserialize;
+
+/// What a nation can do about zombies.
+pub const ACTIONS: [&str; 3] = ["cure", "exterminate", "research"];
+
+/// How a nation or region is faring, in millions of people.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Zombie {
+    /// What the nation is doing about zombies, like "research".
+    /// Empty for regions, and for nations that haven't picked anything.
+    #[serde(rename = "ZACTION", default)]
+    pub action: String,
+    /// What the nation will do once it's able to.
+    #[serde(rename = "ZACTIONINTENDED", default)]
+    pub intended: String,
+    #[serde(rename = "SURVIVORS")]
+    pub survivors: u64,
+    #[serde(rename = "ZOMBIES")]
+    pub zombies: u64,
+    #[serde(rename = "DEAD")]
+    pub dead: u64,
+}
+impl Zombie {
+    /// Change in survivors, zombies, and dead since `earlier`.
+    pub fn since(&self, earlier: &Zombie) -> [i64; 3] {
+        [
+            self.survivors as i64 - earlier.survivors as i64,
+            self.zombies as i64 - earlier.zombies as i64,
+            self.dead as i64 - earlier.dead as i64,
+        ]
+    }
+}
@@ -0,0 +1,412 @@
+//! `api` requests against a mock NationStates server.
+use mockito::{mock, Matcher};
+use nation::api::{Action, Command, Failure, Request, ResolvedShard, RetryFailure, Shard, Target};
+use nation::issues::AnswerResponse;
+use nation::http::{Body, HttpError, HttpResponse};
+use nation::session::{RetryPolicy, Session};
+use nation::{Auth, Nation, PinState};
+
+const PATH: &str = "/cgi-bin/api.cgi";
+const PING: &str = r#"<NATION id="testlandia"><PING>1</PING></NATION>"#;
+
+fn base() -> String {
+    format!("{}{}", mockito::server_url(), PATH)
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder().user_agent(nation::USER_AGENT).build().unwrap()
+}
+
+fn ping<'a>(name: &'a str, auth: Option<&'a Auth>) -> Request<'a> {
+    Request {
+        target: Target::Nation(name),
+        auth,
+        shards: vec![Shard::Ping],
+        version: nation::API_VERSION,
+    }
+}
+
+fn password() -> Auth {
+    Auth {
+        password: Some("hunter2".into()),
+        ..Auth::default()
+    }
+}
+
+#[tokio::test]
+async fn ping_succeeds() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("nation".into(), "testlandia".into()),
+            Matcher::UrlEncoded("q".into(), "ping".into()),
+        ]))
+        .match_header("X-Password", "hunter2")
+        .with_header("X-Pin", "1234567890")
+        .with_header("X-Autologin", "token")
+        .with_body(PING)
+        .create();
+    let auth = password();
+    let response = ping("testlandia", Some(&auth)).send_at(&client(), &base()).await.unwrap();
+    assert!(matches!(response.data.inner.as_slice(), [ResolvedShard::Ping]));
+    assert_eq!(response.autologin.as_deref(), Some("token"));
+    assert!(response.pin.is_some());
+}
+
+#[tokio::test]
+async fn names_are_encoded() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::UrlEncoded("nation".into(), "Mad Hatter's Tea".into()))
+        .with_body(r#"<NATION id="mad_hatter's_tea"><NAME>Mad Hatter's Tea</NAME></NATION>"#)
+        .create();
+    let request = Request {
+        target: Target::Nation("Mad Hatter's Tea"),
+        auth: None,
+        shards: vec![Shard::Name],
+        version: nation::API_VERSION,
+    };
+    let response = request.send_at(&client(), &base()).await.unwrap();
+    assert!(matches!(response.data.inner.as_slice(), [ResolvedShard::Name(x)] if x == "Mad Hatter's Tea"));
+}
+
+#[tokio::test]
+async fn forbidden_without_pin() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .with_status(403)
+        .create();
+    let auth = password();
+    let result = ping("testlandia", Some(&auth)).send_at(&client(), &base()).await;
+    assert!(matches!(result, Err(Failure::BadAuth)));
+}
+
+#[tokio::test]
+async fn forbidden_with_pin_retries() {
+    let _login = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .match_header("X-Password", "hunter2")
+        .with_header("X-Pin", "1234567890")
+        .with_header("X-Autologin", "token")
+        .with_body(PING)
+        .create();
+    let _pin = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .match_header("X-Pin", "1234567890")
+        .with_status(403)
+        .create();
+    let _autologin = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .match_header("X-Pin", Matcher::Missing)
+        .match_header("X-Autologin", "token")
+        .with_body(PING)
+        .expect(1)
+        .create();
+    let client = client();
+    let mut auth = password();
+    let response = ping("testlandia", Some(&auth)).send_at(&client, &base()).await.unwrap();
+    auth.update(response.autologin, response.pin);
+
+    let result = ping("testlandia", Some(&auth)).send_at(&client, &base()).await;
+    assert!(matches!(result, Err(Failure::BadPin)));
+    let response = ping("testlandia", Some(&auth)).send_retry_at(&client, &base()).await;
+    assert!(response.is_ok());
+    _autologin.assert();
+}
+
+#[tokio::test]
+async fn refused_autologin_falls_back_to_password() {
+    let _autologin = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .match_header("X-Autologin", "stale")
+        .with_status(403)
+        .create();
+    let _password = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .match_header("X-Autologin", Matcher::Missing)
+        .match_header("X-Password", "hunter2")
+        .with_header("X-Autologin", "fresh")
+        .with_body(PING)
+        .expect(1)
+        .create();
+    let mut auth = Auth {
+        autologin: Some("stale".into()),
+        ..password()
+    };
+    let response = ping("testlandia", Some(&auth)).send_retry_at(&client(), &base()).await.unwrap();
+    assert!(response.autologin_refused);
+    auth.update(response.autologin, response.pin);
+    assert_eq!(auth.autologin.as_deref(), Some("fresh"));
+    _password.assert();
+}
+
+#[test]
+fn password_outlives_first_autologin() {
+    let mut auth = password();
+    auth.update(Some("first".into()), None);
+    assert_eq!(auth.autologin.as_deref(), Some("first"));
+    let fallback = auth.password_fallback().unwrap();
+    assert_eq!(fallback.password.as_deref(), Some("hunter2"));
+    assert!(fallback.autologin.is_none());
+}
+
+#[tokio::test]
+async fn session_refreshes_refused_pin() {
+    let _autologin = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .match_header("X-Pin", Matcher::Missing)
+        .match_header("X-Autologin", "session")
+        .with_header("X-Pin", "3333")
+        .with_body(PING)
+        .create();
+    let client = client();
+    let base = base();
+    let mut nation = Nation {
+        name: "testlandia".into(),
+        auth: Auth { autologin: Some("session".into()), ..Auth::default() },
+        ..Nation::default()
+    };
+    // Pick up a pin, then have it refused.
+    Session::new(&client, &mut nation).base(&base).request(vec![Shard::Ping]).await.unwrap();
+    let _refused = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .match_header("X-Pin", "3333")
+        .with_status(403)
+        .create();
+    let result = Session::new(&client, &mut nation).base(&base).policy(RetryPolicy::NEVER)
+        .request(vec![Shard::Ping]).await;
+    assert!(matches!(result, Err(Failure::BadPin)));
+    assert_eq!(nation.auth.pin_state(), PinState::Rejected);
+    let response = Session::new(&client, &mut nation).base(&base).request(vec![Shard::Ping]).await.unwrap();
+    assert!(!response.pin_refused);
+    assert_eq!(nation.auth.pin_state(), PinState::Valid);
+}
+
+#[tokio::test]
+async fn no_credentials() {
+    let auth = Auth::default();
+    let result = ping("testlandia", Some(&auth)).send_at(&client(), &base()).await;
+    assert!(matches!(result, Err(Failure::NoAuth)));
+}
+
+#[tokio::test]
+async fn rate_limited() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .with_status(429)
+        .with_header("Retry-After", "30")
+        .create();
+    let result = ping("testlandia", None).send_at(&client(), &base()).await;
+    assert!(matches!(result, Err(Failure::RateLimited(Some(30)))));
+    let result = ping("testlandia", None).send_retry_at(&client(), &base()).await;
+    assert!(matches!(result, Err(RetryFailure::RateLimited(Some(30)))));
+}
+
+#[tokio::test]
+async fn malformed_xml() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .with_body(r#"<NATION id="testlandia"><PING>1</PI"#)
+        .create();
+    let result = ping("testlandia", None).send_at(&client(), &base()).await;
+    assert!(matches!(result, Err(Failure::Malformed(_))));
+}
+
+#[tokio::test]
+async fn unknown_shard() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::UrlEncoded("nation".into(), "newland".into()))
+        .with_body(r#"<NATION id="newland"><PING>1</PING><NEWSHARD>2</NEWSHARD></NATION>"#)
+        .create();
+    let response = ping("newland", None).send_at(&client(), &base()).await.unwrap();
+    assert!(matches!(&response.data.inner[..], [ResolvedShard::Ping, ResolvedShard::Unknown { name, .. }]
+                     if name == "NEWSHARD"));
+}
+
+#[tokio::test]
+async fn not_found() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .with_status(404)
+        .with_body("<h1 style=\"color:red\">Not Found</h1>\n<p style=\"font-size:small\">Unknown nation: &quot;nowhere&quot;.")
+        .create();
+    let result = ping("nowhere", None).send_at(&client(), &base()).await;
+    assert!(matches!(result, Err(Failure::Other(reqwest::StatusCode::NOT_FOUND, Some(reason)))
+                     if reason == "Unknown nation: \"nowhere\"."));
+}
+
+#[tokio::test]
+async fn pin_friendly_skips_pins() {
+    let _login = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .match_header("X-Pin", Matcher::Missing)
+        .match_header("X-Autologin", "token")
+        .with_header("X-Pin", "1234567890")
+        .with_body(PING)
+        .expect(2)
+        .create();
+    let client = client();
+    let mut auth = Auth {
+        autologin: Some("token".into()),
+        pin_friendly: true,
+        ..Auth::default()
+    };
+    for _ in 0..2 {
+        let response = ping("testlandia", Some(&auth)).send_at(&client, &base()).await.unwrap();
+        auth.update(response.autologin, response.pin);
+        assert!(auth.pin.is_none());
+    }
+    _login.assert();
+}
+
+#[tokio::test]
+async fn rate_limit_headers() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::UrlEncoded("nation".into(), "ratelimitland".into()))
+        .with_header("RateLimit-Limit", "50")
+        .with_header("RateLimit-Remaining", "49")
+        .with_header("RateLimit-Reset", "30")
+        .with_header("X-ratelimit-requests-seen", "1")
+        .with_body(PING)
+        .create();
+    let response = ping("ratelimitland", None).send_at(&client(), &base()).await.unwrap();
+    assert_eq!(response.rate_limit, nation::ratelimit::RateLimit {
+        limit: Some(50),
+        remaining: Some(49),
+        reset: Some(30),
+        requests_seen: Some(1),
+    });
+}
+
+/// Answers every request with the same body, without a network.
+struct Canned(&'static str);
+impl nation::http::HttpClient for Canned {
+    async fn get(&self, _url: &str, _headers: &[(&str, String)]) -> Result<HttpResponse, HttpError> {
+        Ok(HttpResponse { status: 200, headers: Default::default(), body: Body::whole(self.0) })
+    }
+    async fn post(&self, url: &str, headers: &[(&str, String)], _body: Vec<u8>) -> Result<HttpResponse, HttpError> {
+        self.get(url, headers).await
+    }
+}
+
+#[tokio::test]
+async fn custom_client() {
+    let response = ping("testlandia", None).send(&Canned(PING)).await.unwrap();
+    assert!(matches!(response.data.inner.as_slice(), [ResolvedShard::Ping]));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_motto() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::UrlEncoded("nation".into(), "blockland".into()))
+        .with_body(r#"<NATION id="blockland"><MOTTO>Wait for it</MOTTO></NATION>"#)
+        .create();
+    let request = Request {
+        target: Target::Nation("blockland"),
+        auth: None,
+        shards: vec![Shard::Motto],
+        version: nation::API_VERSION,
+    };
+    let client = nation::blocking::Client::with_client(client()).unwrap();
+    let response = client.send_at(&request, &base()).unwrap();
+    assert!(matches!(response.data.inner.as_slice(), [ResolvedShard::Motto(x)] if x == "Wait for it"));
+}
+
+#[tokio::test]
+async fn commands_are_audited() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::UrlEncoded("c".into(), "issue".into()))
+        .with_body(r#"<NATION id="auditland"><ISSUE id="7" choice="1"><OK>1</OK></ISSUE></NATION>"#)
+        .create();
+    let log = std::env::temp_dir().join(format!("nation-audit-{}.jsonl", std::process::id()));
+    nation::audit::log_to(&log, Some("tester".into()));
+    let mut nation = Nation { name: "auditland".into(), auth: password(), ..Nation::default() };
+    let action = Action::AnswerIssue { issue: 7, option: 1 };
+    let response = Session::new(&client(), &mut nation).base(&base()).command::<AnswerResponse>(action).await.unwrap();
+    assert_eq!(response.data.issue.ok, Some(1));
+    // Commands sent without a session are logged too, refused ones included.
+    let auth = password();
+    let action = Action::AnswerIssue { issue: 7, option: 2 };
+    let command = Command { nation: "auditland2", auth: &auth, action, version: 11, readonly: true };
+    assert!(command.send::<AnswerResponse>(&client()).await.is_err());
+    let entries = nation::audit::read(&log).unwrap();
+    let _ = std::fs::remove_file(&log);
+    let entry = entries.iter().find(|x| x.nation.as_deref() == Some("auditland")).unwrap();
+    assert_eq!((entry.operator.as_deref(), entry.command.as_str(), entry.outcome.as_str()), (Some("tester"), "issue", "ok"));
+    assert_eq!(entry.params.get("option").map(String::as_str), Some("1"));
+    let entry = entries.iter().find(|x| x.nation.as_deref() == Some("auditland2")).unwrap();
+    assert_eq!(entry.outcome, "not sent, since auditland2 is read-only");
+}
+
+#[tokio::test]
+async fn read_only_nations_send_no_commands() {
+    let m = mock("GET", PATH)
+        .match_query(Matcher::UrlEncoded("nation".into(), "lookonly".into()))
+        .expect(0)
+        .create();
+    let mut nation = Nation { name: "lookonly".into(), auth: password(), readonly: true, ..Nation::default() };
+    let action = Action::AnswerIssue { issue: 7, option: 1 };
+    let result = Session::new(&client(), &mut nation).base(&base()).command::<AnswerResponse>(action).await;
+    assert!(matches!(result, Err(Failure::ReadOnly(x)) if x == "lookonly"));
+    m.assert();
+}
+
+#[tokio::test]
+async fn read_only_commands_are_not_sent() {
+    // Refused before anything is sent, so the live API is never reached.
+    let auth = password();
+    let command = Command {
+        nation: "lookonlytoo",
+        auth: &auth,
+        action: Action::AnswerIssue { issue: 7, option: 1 },
+        version: 11,
+        readonly: true,
+    };
+    let result = command.send::<AnswerResponse>(&client()).await;
+    assert!(matches!(result, Err(RetryFailure::ReadOnly(x)) if x == "lookonlytoo"));
+}
+
+#[test]
+fn cli_uses_api_base() {
+    let _m = mock("GET", PATH)
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("region".into(), "testregionia".into()),
+            Matcher::UrlEncoded("q".into(), "factbook".into()),
+        ]))
+        .with_body(r#"<REGION id="testregionia"><FACTBOOK>[b]Welcome![/b]</FACTBOOK></REGION>"#)
+        .create();
+    // Keep the binary's config, profile, and rate limit state out of the real ones.
+    let home = std::env::temp_dir().join(format!("nation-api-base-{}", std::process::id()));
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_nation"))
+        .args(["--api-base", &base(), "region", "factbook", "testregionia", "--raw"])
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env_remove("NATION_API_BASE")
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&home);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "[b]Welcome![/b]");
+}
+
+#[test]
+fn hooks_never_see_secrets() {
+    let home = std::env::temp_dir().join(format!("nation-hook-secrets-{}", std::process::id()));
+    let summary = home.join("summary.json");
+    std::fs::create_dir_all(home.join("config/nation")).unwrap();
+    std::fs::write(home.join("config/nation/config.toml"),
+                   format!("[hooks.\"tg template add\"]\npost = \"cat > '{}'\"\n", summary.display())).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_nation"))
+        .args(["tg", "template", "add", "welcome", "--tgid", "1234", "--key=hunter2"])
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .output()
+        .unwrap();
+    let summary = std::fs::read_to_string(&summary);
+    let _ = std::fs::remove_dir_all(&home);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let summary = summary.unwrap();
+    assert!(!summary.contains("hunter2"), "{}", summary);
+    assert!(summary.contains("--key=<redacted>"), "{}", summary);
+}
@@ -0,0 +1,60 @@
+//! Recording responses from a mock server and replaying them without it.
+//! Kept apart from the other API tests since the cassette is global.
+use mockito::{mock, Matcher};
+use nation::api::{Failure, Request, ResolvedShard, Shard, Target};
+use nation::cassette::{self, Cassette};
+use nation::Auth;
+
+const PATH: &str = "/cgi-bin/api.cgi";
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder().user_agent(nation::USER_AGENT).build().unwrap()
+}
+
+#[tokio::test]
+async fn record_and_replay() {
+    let dir = std::env::temp_dir().join(format!("nation-cassette-{}", std::process::id()));
+    let path = dir.join("cassette.json");
+    let base = format!("{}{}", mockito::server_url(), PATH);
+    let auth = Auth {
+        password: Some("hunter2".into()),
+        ..Auth::default()
+    };
+    let request = Request {
+        target: Target::Nation("testlandia"),
+        auth: Some(&auth),
+        shards: vec![Shard::Name],
+        version: nation::API_VERSION,
+    };
+
+    let live = mock("GET", PATH)
+        .match_query(Matcher::Any)
+        .with_header("X-Pin", "1234567890")
+        .with_header("X-Autologin", "token")
+        .with_body(r#"<NATION id="testlandia"><NAME>Testlandia</NAME></NATION>"#)
+        .expect(1)
+        .create();
+    cassette::insert(Cassette::record(&path));
+    let recorded = request.send_at(&client(), &base).await.unwrap();
+    assert!(recorded.autologin.is_some());
+    cassette::eject();
+    live.assert();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.contains("Testlandia"));
+    assert!(!text.contains("token"));
+    assert!(!text.contains("1234567890"));
+    assert!(!text.contains("hunter2"));
+
+    cassette::insert(Cassette::replay(&path).unwrap());
+    let replayed = request.send_at(&client(), &base).await.unwrap();
+    assert!(matches!(replayed.data.inner.as_slice(), [ResolvedShard::Name(x)] if x == "Testlandia"));
+    assert!(replayed.autologin.is_none());
+    // Each recorded response plays once.
+    let again = request.send_at(&client(), &base).await;
+    assert!(matches!(again, Err(Failure::NotRecorded(_))));
+    cassette::eject();
+    live.assert();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,214 @@
+//! Parsing a response for every shard.
+//!
+//! Each shard has a response in `tests/fixtures`, shaped like what the API
+//! sends with names swapped for test ones. A new `Shard` variant won't
+//! compile here until it has a fixture, and that fixture has to parse.
+use nation::api::{Data, ResolvedShard, Shard};
+use nation::census::CensusScale;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+/// The fixture for a shard. There's no catch-all arm, so adding
+/// a shard means adding a fixture for it.
+fn fixture(shard: &Shard) -> &'static str {
+    match shard {
+        Shard::Ping => "ping",
+        Shard::Name => "name",
+        Shard::FullName => "fullname",
+        Shard::Motto => "motto",
+        Shard::Pretitle => "type",
+        Shard::Capital => "capital",
+        Shard::Leader => "leader",
+        Shard::Religion => "religion",
+        Shard::Currency => "currency",
+        Shard::Animal => "animal",
+        Shard::Category => "category",
+        Shard::Freedom => "freedom",
+        Shard::Region => "region",
+        Shard::Wa => "wa",
+        Shard::WaBadges => "wabadges",
+        Shard::Zombie => "zombie",
+        Shard::Population => "population",
+        Shard::Influence => "influence",
+        Shard::Gdp => "gdp",
+        Shard::Income => "income",
+        Shard::Richest => "richest",
+        Shard::Poorest => "poorest",
+        Shard::Tax => "tax",
+        Shard::MajorIndustry => "majorindustry",
+        Shard::Sectors => "sectors",
+        Shard::Endorsements => "endorsements",
+        Shard::Flag => "flag",
+        Shard::FoundedTime => "foundedtime",
+        Shard::FirstLogin => "firstlogin",
+        Shard::LastLogin => "lastlogin",
+        Shard::Nations => "nations",
+        Shard::Founder => "founder",
+        Shard::FounderAuth => "founderauth",
+        Shard::Delegate => "delegate",
+        Shard::DelegateAuth => "delegateauth",
+        Shard::DelegateVoteCount => "delegatevotes",
+        Shard::Officers => "officers",
+        Shard::Tags => "tags",
+        Shard::Factbook => "factbook",
+        Shard::Embassies => "embassies",
+        Shard::Messages { .. } => "messages",
+        Shard::CensusRanks { .. } => "censusranks",
+        Shard::RegionsByTag { .. } => "regionsbytag",
+        Shard::Census { .. } => "census",
+        Shard::Proposals => "proposals",
+        Shard::Members => "members",
+        Shard::Resolution { .. } => "resolution",
+        Shard::VoteTrack => "votetrack",
+        Shard::DelegateLog => "dellog",
+        Shard::DelegateVotes => "delvotes",
+        Shard::Happenings { .. } => "happenings",
+        Shard::NewNations => "newnations",
+        Shard::NewNationDetails => "newnationdetails",
+        Shard::TgCanRecruit { .. } => "tgcanrecruit",
+        Shard::TgQueue => "tgqueue",
+        Shard::Deck { .. } => "deck",
+        Shard::CardTrades { .. } => "cardtrades",
+        Shard::CardMarkets { .. } => "cardmarkets",
+        Shard::Issues => "issues",
+        Shard::Banners => "banners",
+        Shard::Banner => "banner",
+        Shard::Policies => "policies",
+        Shard::Dispatches => "dispatches",
+        Shard::DispatchList => "dispatchlist",
+        Shard::Dispatch { .. } => "dispatch",
+    }
+}
+
+/// One of every shard.
+fn every_shard() -> Vec<Shard> {
+    let scale = CensusScale::from_id(66).unwrap();
+    vec![
+        Shard::Ping,
+        Shard::Name,
+        Shard::FullName,
+        Shard::Motto,
+        Shard::Pretitle,
+        Shard::Capital,
+        Shard::Leader,
+        Shard::Religion,
+        Shard::Currency,
+        Shard::Animal,
+        Shard::Category,
+        Shard::Freedom,
+        Shard::Region,
+        Shard::Wa,
+        Shard::WaBadges,
+        Shard::Zombie,
+        Shard::Population,
+        Shard::Influence,
+        Shard::Gdp,
+        Shard::Income,
+        Shard::Richest,
+        Shard::Poorest,
+        Shard::Tax,
+        Shard::MajorIndustry,
+        Shard::Sectors,
+        Shard::Endorsements,
+        Shard::Flag,
+        Shard::FoundedTime,
+        Shard::FirstLogin,
+        Shard::LastLogin,
+        Shard::Nations,
+        Shard::Founder,
+        Shard::FounderAuth,
+        Shard::Delegate,
+        Shard::DelegateAuth,
+        Shard::DelegateVoteCount,
+        Shard::Officers,
+        Shard::Tags,
+        Shard::Factbook,
+        Shard::Embassies,
+        Shard::Messages { limit: None, offset: None, from_id: None },
+        Shard::CensusRanks { scale, start: None },
+        Shard::RegionsByTag { tags: vec!["casual".parse().unwrap()] },
+        Shard::Census { scales: vec![scale], modes: Vec::new(), from: None, to: None },
+        Shard::Proposals,
+        Shard::Members,
+        Shard::Resolution { id: None },
+        Shard::VoteTrack,
+        Shard::DelegateLog,
+        Shard::DelegateVotes,
+        Shard::Happenings { view: None, filter: Vec::new(), limit: None },
+        Shard::NewNations,
+        Shard::NewNationDetails,
+        Shard::TgCanRecruit { from: None },
+        Shard::TgQueue,
+        Shard::Deck { nation: "testlandia".into() },
+        Shard::CardTrades { id: 1, season: 2 },
+        Shard::CardMarkets { id: 1, season: 2 },
+        Shard::Issues,
+        Shard::Banners,
+        Shard::Banner,
+        Shard::Policies,
+        Shard::Dispatches,
+        Shard::DispatchList,
+        Shard::Dispatch { id: 1 },
+    ]
+}
+
+#[test]
+fn every_shard_parses() {
+    for shard in every_shard() {
+        let name = fixture(&shard);
+        let path = fixtures().join(format!("{}.xml", name));
+        let xml = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("no fixture for {:?} at {}: {}", shard, path.display(), e));
+        let data: Data = quick_xml::de::from_str(&xml)
+            .unwrap_or_else(|e| panic!("{}.xml doesn't parse: {}", name, e));
+        assert!(!data.inner.is_empty(), "{}.xml parsed to nothing", name);
+    }
+}
+
+#[test]
+fn every_fixture_is_used() {
+    let used: BTreeSet<_> = every_shard().iter().map(|x| format!("{}.xml", fixture(x))).collect();
+    for entry in std::fs::read_dir(fixtures()).unwrap() {
+        let name = entry.unwrap().file_name().into_string().unwrap();
+        assert!(used.contains(&name), "{} isn't the fixture for any shard", name);
+    }
+}
+
+#[test]
+fn fixtures_keep_their_details() {
+    let parse = |name: &str| -> Vec<ResolvedShard> {
+        let xml = std::fs::read_to_string(fixtures().join(name)).unwrap();
+        quick_xml::de::from_str::<Data>(&xml).unwrap().inner
+    };
+    match &parse("messages.xml")[..] {
+        [ResolvedShard::Messages(x)] => {
+            assert_eq!(x.posts.len(), 2);
+            assert_eq!(x.posts[1].suppressor.as_deref(), Some("testlandia"));
+        },
+        other => panic!("unexpected {:?}", other),
+    }
+    match &parse("wabadges.xml")[..] {
+        [ResolvedShard::WaBadges(x)] => {
+            assert_eq!(x.inner[0].resolution, 123);
+            assert_eq!(x.inner[1].action(), "liberated");
+        },
+        other => panic!("unexpected {:?}", other),
+    }
+    match &parse("zombie.xml")[..] {
+        [ResolvedShard::Zombie(x)] => {
+            assert_eq!((x.action.as_str(), x.intended.as_str()), ("research", "exterminate"));
+            assert_eq!((x.survivors, x.zombies, x.dead), (4210, 38, 12));
+        },
+        other => panic!("unexpected {:?}", other),
+    }
+    match &parse("cardtrades.xml")[..] {
+        [ResolvedShard::CardId(1), ResolvedShard::Season(2), ResolvedShard::Trades(x)] => {
+            assert_eq!(x.last_price(), Some(10.0));
+        },
+        other => panic!("unexpected {:?}", other),
+    }
+}